@@ -133,8 +133,7 @@ fn messages<'a>(mut rng: impl Rng + 'a, len: usize) -> impl Iterator<Item = Game
 }
 
 pub fn criterion_benchmark(c: &mut Criterion) {
-    let mut buffer = Vec::with_capacity(1 << 14);
-    buffer.resize(buffer.capacity(), 0);
+    let mut buffer = vec![0; 1 << 14];
     let rng = SmallRng::seed_from_u64(42);
 
     const LEN: usize = 100;
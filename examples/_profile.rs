@@ -1,3 +1,5 @@
+#![allow(unexpected_cfgs)]
+
 extern crate alkahest;
 
 extern crate rand;
@@ -125,8 +127,7 @@ fn messages<'a>(mut rng: impl Rng + 'a, len: usize) -> impl Iterator<Item = Game
 }
 
 pub fn main() {
-    let mut buffer = Vec::with_capacity(1 << 14);
-    buffer.resize(buffer.capacity(), 0);
+    let mut buffer = vec![0; 1 << 14];
     let rng = SmallRng::seed_from_u64(42);
 
     const LEN: usize = 100;
@@ -74,8 +74,7 @@ fn main() {
 
     let mut mesh = black_box(mesh);
 
-    let mut buffer = Vec::new();
-    buffer.resize(TRIG_COUNT * size_of::<Triangle>() + 32, 0);
+    let mut buffer = vec![0; TRIG_COUNT * size_of::<Triangle>() + 32];
 
     for _ in 0..10_000 {
         let size = do_serialize(&mesh, &mut buffer);
@@ -0,0 +1,95 @@
+use crate::{generic_param_idents, is_generic_ty, split_unknown_variant};
+
+/// Expression constructing fields from `Unstructured`.
+fn construct_fields(fields: &syn::Fields) -> proc_macro2::TokenStream {
+    let arbitrary = quote::quote! {
+        ::alkahest::private::arbitrary::Arbitrary::arbitrary(__u)?
+    };
+
+    match fields {
+        syn::Fields::Unit => quote::quote! {},
+        syn::Fields::Unnamed(fields) => {
+            let fields = fields.unnamed.iter().map(|_| &arbitrary);
+            quote::quote! { ( #(#fields),* ) }
+        }
+        syn::Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote::quote! { { #(#names: #arbitrary),* } }
+        }
+    }
+}
+
+pub fn derive(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = &input.ident;
+
+    // `unknown` variant holds tag that only deserialization produces,
+    // so it is never generated.
+    let known = match &input.data {
+        syn::Data::Enum(data) => Some(split_unknown_variant(data)?.0),
+        _ => None,
+    };
+
+    let all_fields: Vec<&syn::Field> = match (&input.data, &known) {
+        (syn::Data::Struct(data), _) => data.fields.iter().collect(),
+        (syn::Data::Enum(_), Some(data)) => {
+            data.variants.iter().flat_map(|v| v.fields.iter()).collect()
+        }
+        (syn::Data::Enum(_), None) => unreachable!(),
+        (syn::Data::Union(data), _) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "`arbitrary` is not supported for unions",
+            ))
+        }
+    };
+
+    let body = match (&input.data, &known) {
+        (syn::Data::Struct(data), _) => {
+            let fields = construct_fields(&data.fields);
+            quote::quote! {
+                ::alkahest::private::Result::Ok(#ident #fields)
+            }
+        }
+        (syn::Data::Enum(_), Some(data)) => {
+            let count = data.variants.len();
+            let ids = 0..count;
+            let variants = data.variants.iter().map(|v| {
+                let variant_ident = &v.ident;
+                let fields = construct_fields(&v.fields);
+                quote::quote! { #ident::#variant_ident #fields }
+            });
+            quote::quote! {
+                match __u.choose_index(#count)? {
+                    #( #ids => ::alkahest::private::Result::Ok(#variants), )*
+                    _ => ::alkahest::private::unreachable!(),
+                }
+            }
+        }
+        _ => unreachable!(),
+    };
+
+    let mut generics = input.generics.clone();
+    let (_, type_generics, _) = input.generics.split_for_impl();
+
+    let mut generic_field_types: Vec<&syn::Type> = all_fields.iter().map(|f| &f.ty).collect();
+    generic_field_types
+        .retain(|ty| is_generic_ty(ty, &generic_param_idents(input.generics.params.iter())));
+
+    let predicates = generic_field_types.iter().map(|ty| -> syn::WherePredicate {
+        syn::parse_quote! { #ty: ::alkahest::private::arbitrary::Arbitrary<'__alkahest_arbitrary> }
+    });
+    generics.make_where_clause().predicates.extend(predicates);
+    generics
+        .params
+        .insert(0, syn::parse_quote!('__alkahest_arbitrary));
+
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    Ok(quote::quote! {
+        impl #impl_generics ::alkahest::private::arbitrary::Arbitrary<'__alkahest_arbitrary> for #ident #type_generics #where_clause {
+            fn arbitrary(__u: &mut ::alkahest::private::arbitrary::Unstructured<'__alkahest_arbitrary>) -> ::alkahest::private::arbitrary::Result<Self> {
+                #body
+            }
+        }
+    })
+}
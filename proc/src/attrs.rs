@@ -31,7 +31,7 @@ proc_easy::easy_parse! {
         lt_token: syn::Token![<],
         lifetime: syn::Lifetime,
         comma_token: syn::Token![,],
-        formula: syn::Path,
+        formula: syn::Type,
         gt_token: syn::Token![>],
     }
 }
@@ -54,8 +54,77 @@ proc_easy::easy_parse! {
     }
 }
 
+type Predicates = syn::punctuated::Punctuated<syn::WherePredicate, syn::Token![,]>;
+
+/// Predicates for generated impls
+/// in form `bound = "T: Bound"`.
+struct Bound {
+    name: syn::Ident,
+    predicates: Predicates,
+}
+
+/// Type of enum variant tag
+/// in form `tag = u16`.
+struct Tag {
+    name: syn::Ident,
+    ty: syn::Type,
+}
+
+enum Item {
+    Impl(ImplBlock),
+    Bound(Bound),
+    Tag(Tag),
+
+    /// Enum without variant tag
+    /// in form `untagged`.
+    Untagged(syn::Ident),
+
+    /// Struct deserialized from tuple formula
+    /// in form `from_tuple`.
+    FromTuple(syn::Ident),
+
+    /// `Arbitrary` impl for fuzzing
+    /// in form `arbitrary`.
+    Arbitrary(syn::Ident),
+}
+
+impl syn::parse::Parse for Item {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::Ident) && input.peek2(syn::Token![=]) {
+            let name: syn::Ident = input.parse()?;
+            let _eq: syn::Token![=] = input.parse()?;
+            if name == "tag" {
+                let ty: syn::Type = input.parse()?;
+                Ok(Item::Tag(Tag { name, ty }))
+            } else if name == "bound" || name == "serialize_bound" || name == "deserialize_bound" {
+                let lit: syn::LitStr = input.parse()?;
+                let predicates = lit.parse_with(Predicates::parse_terminated)?;
+                Ok(Item::Bound(Bound { name, predicates }))
+            } else {
+                Err(syn::Error::new(name.span(), "unknown alkahest argument"))
+            }
+        } else if input.peek(syn::Ident) && is_flag(input, "untagged") {
+            Ok(Item::Untagged(input.parse()?))
+        } else if input.peek(syn::Ident) && is_flag(input, "from_tuple") {
+            Ok(Item::FromTuple(input.parse()?))
+        } else if input.peek(syn::Ident) && is_flag(input, "arbitrary") {
+            Ok(Item::Arbitrary(input.parse()?))
+        } else {
+            Ok(Item::Impl(input.parse()?))
+        }
+    }
+}
+
+fn is_flag(input: syn::parse::ParseStream, name: &str) -> bool {
+    let fork = input.fork();
+    match fork.parse::<syn::Ident>() {
+        Ok(ident) => ident == name && (fork.is_empty() || fork.peek(syn::Token![,])),
+        Err(_) => false,
+    }
+}
+
 struct ImplBlocks {
-    blocks: syn::punctuated::Punctuated<ImplBlock, syn::Token![,]>,
+    blocks: syn::punctuated::Punctuated<Item, syn::Token![,]>,
 }
 
 impl syn::parse::Parse for ImplBlocks {
@@ -87,11 +156,21 @@ impl ImplBlock {
 
 pub struct FormulaArgs {
     pub generics: Option<syn::Generics>,
+
+    /// Type of enum variant tag.
+    pub tag: Option<syn::Type>,
+
+    /// Signals that enum variants are written without tag.
+    pub untagged: Option<syn::Ident>,
 }
 
 impl FormulaArgs {
     pub fn empty() -> Self {
-        FormulaArgs { generics: None }
+        FormulaArgs {
+            generics: None,
+            tag: None,
+            untagged: None,
+        }
     }
 }
 
@@ -99,6 +178,12 @@ pub struct SerializeArgs {
     pub formula: Option<syn::Path>,
     pub generics: Option<syn::Generics>,
     pub variant: Option<syn::Ident>,
+
+    /// Predicates that replace generated ones.
+    pub bound: Option<Vec<syn::WherePredicate>>,
+
+    /// Predicates added to generated ones.
+    pub extra_bound: Vec<syn::WherePredicate>,
 }
 
 impl SerializeArgs {
@@ -107,14 +192,26 @@ impl SerializeArgs {
             formula: None,
             generics: None,
             variant: None,
+            bound: None,
+            extra_bound: Vec::new(),
         }
     }
 }
 
 pub struct DeserializeArgs {
-    pub formula: Option<syn::Path>,
+    pub formula: Option<syn::Type>,
     pub generics: Option<syn::Generics>,
     pub lifetime: Option<syn::Lifetime>,
+
+    /// Signals that struct fields are mapped positionally
+    /// onto tuple formula.
+    pub from_tuple: Option<syn::Ident>,
+
+    /// Predicates that replace generated ones.
+    pub bound: Option<Vec<syn::WherePredicate>>,
+
+    /// Predicates added to generated ones.
+    pub extra_bound: Vec<syn::WherePredicate>,
 }
 
 impl DeserializeArgs {
@@ -123,6 +220,9 @@ impl DeserializeArgs {
             formula: None,
             generics: None,
             lifetime: None,
+            from_tuple: None,
+            bound: None,
+            extra_bound: Vec::new(),
         }
     }
 }
@@ -132,6 +232,9 @@ pub struct Args {
     pub serialize: Option<SerializeArgs>,
     pub serialize_ref: Option<SerializeArgs>,
     pub deserialize: Option<DeserializeArgs>,
+
+    /// Signals that `Arbitrary` impl should be generated.
+    pub arbitrary: Option<syn::Ident>,
 }
 
 impl Args {
@@ -143,10 +246,87 @@ impl Args {
         let mut serialize_ref: Option<SerializeArgs> = None;
         let mut deserialize: Option<DeserializeArgs> = None;
 
-        for block in blocks.blocks {
+        let mut bound: Option<Bound> = None;
+        let mut serialize_bound: Option<Bound> = None;
+        let mut deserialize_bound: Option<Bound> = None;
+        let mut tag: Option<Tag> = None;
+        let mut untagged: Option<syn::Ident> = None;
+        let mut from_tuple: Option<syn::Ident> = None;
+        let mut arbitrary: Option<syn::Ident> = None;
+
+        for item in blocks.blocks {
+            let block = match item {
+                Item::Impl(block) => block,
+                Item::Tag(item) => {
+                    if tag.is_some() {
+                        return Err(syn::Error::new_spanned(
+                            item.name,
+                            "Duplicate tag attribute",
+                        ));
+                    }
+                    tag = Some(item);
+                    continue;
+                }
+                Item::Untagged(ident) => {
+                    if untagged.is_some() {
+                        return Err(syn::Error::new_spanned(
+                            ident,
+                            "Duplicate untagged attribute",
+                        ));
+                    }
+                    untagged = Some(ident);
+                    continue;
+                }
+                Item::FromTuple(ident) => {
+                    if from_tuple.is_some() {
+                        return Err(syn::Error::new_spanned(
+                            ident,
+                            "Duplicate from_tuple attribute",
+                        ));
+                    }
+                    from_tuple = Some(ident);
+                    continue;
+                }
+                Item::Arbitrary(ident) => {
+                    if arbitrary.is_some() {
+                        return Err(syn::Error::new_spanned(
+                            ident,
+                            "Duplicate arbitrary attribute",
+                        ));
+                    }
+                    arbitrary = Some(ident);
+                    continue;
+                }
+                Item::Bound(item) => {
+                    let slot = if item.name == "bound" {
+                        &mut bound
+                    } else if item.name == "serialize_bound" {
+                        &mut serialize_bound
+                    } else {
+                        // Other names are rejected by `Item::parse`.
+                        &mut deserialize_bound
+                    };
+
+                    if slot.is_some() {
+                        return Err(syn::Error::new_spanned(
+                            item.name,
+                            "Duplicate bound attribute",
+                        ));
+                    }
+                    *slot = Some(item);
+                    continue;
+                }
+            };
+
             let (impl_trait, generics) = block.split();
             match impl_trait {
-                ImplTrait::Formula(_) => formula = Some(FormulaArgs { generics }),
+                ImplTrait::Formula(_) => {
+                    formula = Some(FormulaArgs {
+                        generics,
+                        tag: None,
+                        untagged: None,
+                    })
+                }
                 ImplTrait::Serialize(_, params) => {
                     let (formula, variant) = match params {
                         proc_easy::EasyMaybe::Just(params) => (
@@ -163,6 +343,8 @@ impl Args {
                         formula,
                         generics,
                         variant,
+                        bound: None,
+                        extra_bound: Vec::new(),
                     });
                 }
                 ImplTrait::SerializeRef(_, params) => {
@@ -181,12 +363,14 @@ impl Args {
                         formula,
                         generics,
                         variant,
+                        bound: None,
+                        extra_bound: Vec::new(),
                     });
                 }
                 ImplTrait::Deserialize(_, params) => {
                     let (formula, lifetime) = match params {
                         proc_easy::EasyMaybe::Just(params) => (
-                            Some(path_make_expr_style(params.formula)),
+                            Some(type_make_expr_style(params.formula)),
                             Some(params.lifetime),
                         ),
                         proc_easy::EasyMaybe::Nothing => (None, None),
@@ -196,20 +380,256 @@ impl Args {
                         formula,
                         generics,
                         lifetime,
+                        from_tuple: None,
+                        bound: None,
+                        extra_bound: Vec::new(),
                     });
                 }
             }
         }
 
+        if let Some(tag) = tag {
+            match &mut formula {
+                None => {
+                    return Err(syn::Error::new_spanned(
+                        tag.name,
+                        "`tag` requires `Formula`",
+                    ))
+                }
+                Some(args) => args.tag = Some(tag.ty),
+            }
+        }
+
+        if let Some(ident) = untagged {
+            match &mut formula {
+                None => {
+                    return Err(syn::Error::new_spanned(
+                        ident,
+                        "`untagged` requires `Formula`",
+                    ))
+                }
+                Some(args) => {
+                    if let Some(tag) = &args.tag {
+                        return Err(syn::Error::new_spanned(
+                            tag,
+                            "`tag` cannot be combined with `untagged`",
+                        ));
+                    }
+                    args.untagged = Some(ident);
+                }
+            }
+        }
+
+        if let Some(ident) = from_tuple {
+            match &mut deserialize {
+                Some(args) if args.formula.is_some() => args.from_tuple = Some(ident),
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        ident,
+                        "`from_tuple` requires `Deserialize` with tuple formula",
+                    ))
+                }
+            }
+        }
+
+        if let Some(bound) = bound {
+            if serialize.is_none() && serialize_ref.is_none() && deserialize.is_none() {
+                return Err(syn::Error::new_spanned(
+                    bound.name,
+                    "`bound` requires `Serialize`, `SerializeRef` or `Deserialize`",
+                ));
+            }
+
+            let predicates: Vec<_> = bound.predicates.into_iter().collect();
+            if let Some(args) = &mut serialize {
+                args.bound = Some(predicates.clone());
+            }
+            if let Some(args) = &mut serialize_ref {
+                args.bound = Some(predicates.clone());
+            }
+            if let Some(args) = &mut deserialize {
+                args.bound = Some(predicates);
+            }
+        }
+
+        if let Some(bound) = serialize_bound {
+            if serialize.is_none() && serialize_ref.is_none() {
+                return Err(syn::Error::new_spanned(
+                    bound.name,
+                    "`serialize_bound` requires `Serialize` or `SerializeRef`",
+                ));
+            }
+
+            let predicates: Vec<_> = bound.predicates.into_iter().collect();
+            if let Some(args) = &mut serialize {
+                args.extra_bound.clone_from(&predicates);
+            }
+            if let Some(args) = &mut serialize_ref {
+                args.extra_bound = predicates;
+            }
+        }
+
+        if let Some(bound) = deserialize_bound {
+            match &mut deserialize {
+                None => {
+                    return Err(syn::Error::new_spanned(
+                        bound.name,
+                        "`deserialize_bound` requires `Deserialize`",
+                    ))
+                }
+                Some(args) => args.extra_bound = bound.predicates.into_iter().collect(),
+            }
+        }
+
         Ok(Args {
             formula,
             serialize,
             serialize_ref,
             deserialize,
+            arbitrary,
         })
     }
 }
 
+/// Field attribute arguments
+/// in form `#[alkahest(as = "Formula")]`
+/// and `#[alkahest(skip_serializing_if = "path")]`.
+pub struct FieldArgs {
+    /// Formula of the field.
+    /// Field is serialized through `SerIter` over its `IntoIterator` impl.
+    pub as_formula: Option<syn::Type>,
+
+    /// Predicate that makes field omitted from serialized struct.
+    /// Called with reference to the field.
+    pub skip_serializing_if: Option<syn::ExprPath>,
+}
+
+impl FieldArgs {
+    pub fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut as_formula = None;
+        let mut skip_serializing_if = None;
+
+        for attr in &field.attrs {
+            if !attr.path().is_ident("alkahest") {
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("as") {
+                    if as_formula.is_some() {
+                        return Err(meta.error("Duplicate `as` attribute"));
+                    }
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    as_formula = Some(lit.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("skip_serializing_if") {
+                    if skip_serializing_if.is_some() {
+                        return Err(meta.error("Duplicate `skip_serializing_if` attribute"));
+                    }
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    skip_serializing_if = Some(lit.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("Expected `as` or `skip_serializing_if`"))
+                }
+            })?;
+        }
+
+        Ok(FieldArgs {
+            as_formula,
+            skip_serializing_if,
+        })
+    }
+}
+
+/// Enum variant attribute arguments
+/// in form `#[alkahest(tag_value = 10)]` or `#[alkahest(unknown)]`.
+pub struct VariantArgs {
+    /// Tag of the variant.
+    /// Overrides explicit discriminant and declaration order.
+    pub tag_value: Option<syn::LitInt>,
+
+    /// Variant that holds raw tag of variants unknown to the formula.
+    pub unknown: Option<syn::Path>,
+}
+
+impl VariantArgs {
+    pub fn parse(variant: &syn::Variant) -> syn::Result<Self> {
+        let mut tag_value = None;
+        let mut unknown = None;
+
+        for attr in &variant.attrs {
+            if !attr.path().is_ident("alkahest") {
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag_value") {
+                    if tag_value.is_some() {
+                        return Err(meta.error("Duplicate `tag_value` attribute"));
+                    }
+                    tag_value = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("unknown") {
+                    if unknown.is_some() {
+                        return Err(meta.error("Duplicate `unknown` attribute"));
+                    }
+                    unknown = Some(meta.path);
+                    Ok(())
+                } else {
+                    Err(meta.error("Expected `tag_value` or `unknown`"))
+                }
+            })?;
+        }
+
+        if let (Some(tag_value), Some(_)) = (&tag_value, &unknown) {
+            return Err(syn::Error::new_spanned(
+                tag_value,
+                "`tag_value` cannot be specified for `unknown` variant",
+            ));
+        }
+
+        Ok(VariantArgs { tag_value, unknown })
+    }
+}
+
+/// Removes `alkahest` attributes from fields and enum variants.
+pub fn strip_helper_args(data: &mut syn::Data) {
+    if let syn::Data::Enum(data) = data {
+        for variant in &mut data.variants {
+            variant
+                .attrs
+                .retain(|attr| !attr.path().is_ident("alkahest"));
+        }
+    }
+
+    let fields: Vec<&mut syn::Field> = match data {
+        syn::Data::Struct(data) => data.fields.iter_mut().collect(),
+        syn::Data::Enum(data) => data
+            .variants
+            .iter_mut()
+            .flat_map(|v| v.fields.iter_mut())
+            .collect(),
+        syn::Data::Union(data) => data.fields.named.iter_mut().collect(),
+    };
+
+    for field in fields {
+        field.attrs.retain(|attr| !attr.path().is_ident("alkahest"));
+    }
+}
+
+/// Makes path of the type usable in expressions.
+/// Other types are returned as-is.
+pub fn type_make_expr_style(ty: syn::Type) -> syn::Type {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => syn::Type::Path(syn::TypePath {
+            qself: None,
+            path: path_make_expr_style(path),
+        }),
+        ty => ty,
+    }
+}
+
 pub fn path_make_expr_style(mut path: syn::Path) -> syn::Path {
     for seg in &mut path.segments {
         if let syn::PathArguments::AngleBracketed(ref mut args) = seg.arguments {
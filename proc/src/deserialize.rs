@@ -1,10 +1,9 @@
-use std::collections::HashSet;
-
 use proc_macro2::TokenStream;
 
 use crate::{
-    attrs::DeserializeArgs, enum_field_order_checks, filter_type_param, is_generic_ty,
-    struct_field_order_checks,
+    attrs::DeserializeArgs, check_no_skip_in_variants, enum_field_order_checks, field_code,
+    generic_field_types, presence_size, split_unknown_variant, struct_field_order_checks,
+    FieldCode,
 };
 
 fn default_de_lifetime() -> syn::Lifetime {
@@ -37,13 +36,16 @@ fn de_lifetime(
 }
 
 struct Config {
-    formula: syn::Path,
+    formula: syn::Type,
     generics: syn::Generics,
 
     /// Signals if fields should be checked to match on formula.
     /// `false` if `formula` is inferred to `Self`.
     check_fields: bool,
 
+    /// Signals that fields are mapped positionally onto tuple formula.
+    from_tuple: bool,
+
     // /// Signals that it can deserialize
     // /// formulas with new fields appended.
     // non_exhaustive: bool,
@@ -52,8 +54,12 @@ struct Config {
 }
 
 impl Config {
-    fn for_type(args: DeserializeArgs, data: &syn::Data, generics: &syn::Generics) -> Self {
-        match (args.formula, args.generics) {
+    fn for_type(
+        args: DeserializeArgs,
+        data: &syn::Data,
+        generics: &syn::Generics,
+    ) -> syn::Result<Self> {
+        let mut cfg = match (args.formula, args.generics) {
             (None, None) => {
                 let mut formula_generics = syn::Generics {
                     lt_token: Some(<syn::Token![<]>::default()),
@@ -64,48 +70,30 @@ impl Config {
 
                 let de = de_lifetime(args.lifetime, &mut formula_generics, generics);
 
-                // Add predicates that fields implement
-                // `Formula + Deserialize<'__de, #field_type>`
+                // Add predicates that field formulas implement `Formula`
+                // and fields implement `Deserialize<'__de, #field_formula>`.
                 // Except that last one if `non_exhaustive` is not set.
-                match data {
+                // Unless predicates are specified with `bound` attribute.
+                let fields: Vec<&syn::Field> = match data {
                     syn::Data::Union(_) => unreachable!(),
-                    syn::Data::Struct(data) => {
-                        let mut all_generic_field_types: HashSet<_> =
-                            data.fields.iter().map(|f| &f.ty).collect();
-                        all_generic_field_types.retain(|ty| {
-                            is_generic_ty(ty, &filter_type_param(generics.params.iter()))
-                        });
-
-                        if !all_generic_field_types.is_empty() {
-                            let predicates = all_generic_field_types.iter().map(|&ty| -> syn::WherePredicate {
-                                syn::parse_quote! { #ty: ::alkahest::private::Formula + ::alkahest::private::Deserialize<#de, #ty> }
-                            });
-
-                            formula_generics
-                                .make_where_clause()
-                                .predicates
-                                .extend(predicates);
-                        }
-                    }
+                    syn::Data::Struct(data) => data.fields.iter().collect(),
                     syn::Data::Enum(data) => {
-                        let all_fields = data.variants.iter().flat_map(|v| v.fields.iter());
+                        data.variants.iter().flat_map(|v| v.fields.iter()).collect()
+                    }
+                };
 
-                        let mut all_generic_field_types: HashSet<_> =
-                            all_fields.map(|f| &f.ty).collect();
-                        all_generic_field_types.retain(|ty| {
-                            is_generic_ty(ty, &filter_type_param(generics.params.iter()))
+                if args.bound.is_none() {
+                    let types = generic_field_types(fields.into_iter(), &generics.params)?;
+                    if !types.is_empty() {
+                        let predicates = types.into_iter().flat_map(|(ty, as_formula)| -> [syn::WherePredicate; 2] {
+                            let formula = as_formula.unwrap_or_else(|| ty.clone());
+                            [
+                                syn::parse_quote! { #formula: ::alkahest::private::Formula },
+                                syn::parse_quote! { #ty: ::alkahest::private::Deserialize<#de, #formula> },
+                            ]
                         });
-
-                        if !all_generic_field_types.is_empty() {
-                            let predicates = all_generic_field_types.iter().map(|&ty| -> syn::WherePredicate {
-                                syn::parse_quote! { #ty: ::alkahest::private::Formula + ::alkahest::private::Deserialize<#de, #ty> }
-                            });
-
-                            formula_generics
-                                .make_where_clause()
-                                .predicates
-                                .extend(predicates);
-                        }
+                        let where_clause = formula_generics.make_where_clause();
+                        where_clause.predicates.extend(predicates);
                     }
                 }
 
@@ -113,6 +101,7 @@ impl Config {
                     formula: syn::parse_quote! { Self },
                     generics: formula_generics,
                     check_fields: false,
+                    from_tuple: false,
                     // non_exhaustive,
                     de,
                 }
@@ -124,6 +113,7 @@ impl Config {
                     formula: syn::parse_quote! { Self },
                     generics: formula_generics,
                     check_fields: false,
+                    from_tuple: false,
                     // non_exhaustive,
                     de,
                 }
@@ -142,6 +132,7 @@ impl Config {
                     formula,
                     generics: formula_generics,
                     check_fields: false,
+                    from_tuple: false,
                     de,
                 }
             }
@@ -152,10 +143,42 @@ impl Config {
                     formula,
                     generics: formula_generics,
                     check_fields: true,
+                    from_tuple: false,
                     de,
                 }
             }
+        };
+
+        if args.from_tuple.is_some() {
+            cfg.from_tuple = true;
+            cfg.check_fields = false;
+        }
+
+        if let Some(bound) = args.bound {
+            cfg.generics.make_where_clause().predicates.extend(bound);
+        }
+
+        if !args.extra_bound.is_empty() {
+            cfg.generics
+                .make_where_clause()
+                .predicates
+                .extend(args.extra_bound);
         }
+
+        Ok(cfg)
+    }
+}
+
+/// Returns path of the formula type.
+/// Fields are matched by name on formulas with path,
+/// other formulas require `from_tuple`.
+fn formula_type_path(formula: &syn::Type) -> syn::Result<&syn::Path> {
+    match formula {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => Ok(path),
+        _ => Err(syn::Error::new_spanned(
+            formula,
+            "Formula must be a path, use `from_tuple` for tuple formulas",
+        )),
     }
 }
 
@@ -163,7 +186,8 @@ impl Config {
 pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<TokenStream> {
     let ident = &input.ident;
 
-    let cfg = Config::for_type(args, &input.data, &input.generics);
+    let from_tuple = args.from_tuple.clone();
+    let cfg = Config::for_type(args, &input.data, &input.generics)?;
 
     match &input.data {
         syn::Data::Union(_) => Err(syn::Error::new_spanned(
@@ -172,7 +196,12 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
         )),
         syn::Data::Struct(data) => {
             let field_checks = if cfg.check_fields {
-                struct_field_order_checks(data, None, &input.ident, &cfg.formula)
+                struct_field_order_checks(
+                    data,
+                    None,
+                    &input.ident,
+                    formula_type_path(&cfg.formula)?,
+                )
             } else {
                 TokenStream::new()
             };
@@ -194,8 +223,6 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                     .extend(where_clause.predicates);
             }
 
-            let field_ids: Vec<_> = (0..data.fields.len()).collect();
-
             let bound_names = data
                 .fields
                 .iter()
@@ -281,7 +308,32 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                 syn::Fields::Unit => quote::quote! {},
             };
 
-            let field_count = data.fields.len();
+            let pattern = if cfg.from_tuple {
+                quote::quote! { ( #(ref #bound_names,)* ) }
+            } else {
+                let formula_path = formula_type_path(formula_path)?;
+                quote::quote! { #formula_path #bind_ref_names }
+            };
+            let field_codes = data
+                .fields
+                .iter()
+                .zip(&bound_names)
+                .map(|(field, name)| field_code(field, name, formula_path, &pattern, false))
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            let presence_size = presence_size(&data.fields)?;
+            let is_self = matches!(formula_path, syn::Type::Path(syn::TypePath { qself: None, path }) if path.is_ident("Self"));
+            if presence_size > 0 && (cfg.from_tuple || !is_self) {
+                let skip_if = field_codes.iter().find_map(|c| c.skip_if.as_ref());
+                return Err(syn::Error::new_spanned(
+                    skip_if,
+                    "`skip_serializing_if` requires struct to be its own formula",
+                ));
+            }
+
+            let read_fields = struct_read_fields(&field_codes, &bound_names, presence_size, false);
+            let read_in_place_fields =
+                struct_read_fields(&field_codes, &bound_names, presence_size, true);
 
             let (_impl_generics, type_generics, _where_clause) = input.generics.split_for_impl();
             let (impl_deserialize_generics, _type_deserialize_generics, where_serialize_clause) =
@@ -292,13 +344,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                     fn deserialize(mut de: ::alkahest::private::Deserializer<#de>) -> ::alkahest::private::Result<Self, ::alkahest::private::DeserializeError> {
                         #field_checks
 
-                        #(
-                            let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                #formula_path #bind_ref_names => #bound_names,
-                                _ => unreachable!(),
-                            });
-                            let #bound_names = with_formula.read_field(&mut de, #field_count == 1 + #field_ids)?;
-                        )*
+                        #read_fields
                         // #consume_tail
                         // de.finish()?;
 
@@ -312,13 +358,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
 
                         let #ident #bind_ref_mut_names = *self;
 
-                        #(
-                            let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                #formula_path #bind_ref_names => #bound_names,
-                                _ => unreachable!(),
-                            });
-                            with_formula.read_in_place(#bound_names, &mut de, #field_count == 1 + #field_ids)?;
-                        )*
+                        #read_in_place_fields
                         // #consume_tail
                         // de.finish()?;
                         ::alkahest::private::Result::Ok(())
@@ -327,14 +367,49 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
             })
         }
         syn::Data::Enum(data) => {
+            if let Some(from_tuple) = from_tuple {
+                return Err(syn::Error::new_spanned(
+                    from_tuple,
+                    "`from_tuple` can be specified only for structs",
+                ));
+            }
+
+            check_no_skip_in_variants(data)?;
+
+            let (known, unknown) = split_unknown_variant(data)?;
+            let data = &known;
+
+            let formula_path = formula_type_path(&cfg.formula)?;
+
+            // Tags unknown to the formula are kept in `unknown` variant if there is one.
+            let (read_unknown, read_unknown_in_place) = match &unknown {
+                None => (
+                    quote::quote! {
+                        invalid => ::alkahest::private::Result::Err(::alkahest::private::DeserializeError::WrongVariant { got: ::alkahest::private::Into::<::alkahest::private::u32>::into(invalid), count: #formula_path::__ALKAHEST_FORMULA_VARIANT_COUNT }),
+                    },
+                    quote::quote! {
+                        (invalid, _) => ::alkahest::private::Result::Err(::alkahest::private::DeserializeError::WrongVariant { got: ::alkahest::private::Into::<::alkahest::private::u32>::into(invalid), count: #formula_path::__ALKAHEST_FORMULA_VARIANT_COUNT }),
+                    },
+                ),
+                Some(variant) => (
+                    quote::quote! {
+                        unknown => ::alkahest::private::Result::Ok(#ident::#variant(::alkahest::private::unknown_tag::<#formula_path>(::alkahest::private::Into::<::alkahest::private::u32>::into(unknown)))),
+                    },
+                    quote::quote! {
+                        (unknown, me) => {
+                            *me = #ident::#variant(::alkahest::private::unknown_tag::<#formula_path>(::alkahest::private::Into::<::alkahest::private::u32>::into(unknown)));
+                            ::alkahest::private::Result::Ok(())
+                        }
+                    },
+                ),
+            };
+
             let field_checks = if cfg.check_fields {
-                enum_field_order_checks(data, &input.ident, &cfg.formula)
+                enum_field_order_checks(data, &input.ident, formula_path)
             } else {
                 TokenStream::new()
             };
 
-            let formula_path = &cfg.formula;
-
             let de = cfg.de;
 
             let mut deserialize_generics = input.generics.clone();
@@ -470,6 +545,25 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                 })
                 .collect();
 
+            let with_formulas = data
+                .variants
+                .iter()
+                .zip(&bound_names)
+                .zip(&bind_ref_names)
+                .map(|((v, names), bind_ref_names)| {
+                    let variant_name = &v.ident;
+                    let pattern = quote::quote! { #formula_path::#variant_name #bind_ref_names };
+                    v.fields
+                        .iter()
+                        .zip(names)
+                        .map(|(field, name)| {
+                            field_code(field, name, formula_path, &pattern, false)
+                                .map(|c| c.with_formula)
+                        })
+                        .collect::<syn::Result<Vec<_>>>()
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+
             let (_impl_generics, type_generics, _where_clause) = input.generics.split_for_impl();
             let (impl_deserialize_generics, _type_deserialize_generics, where_serialize_clause) =
                 deserialize_generics.split_for_impl();
@@ -479,16 +573,28 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                     fn deserialize(mut de: ::alkahest::private::Deserializer<#de>) -> ::alkahest::private::Result<Self, ::alkahest::private::DeserializeError> {
                         #field_checks
 
-                        let variant_idx = de.read_value::<::alkahest::private::u32, _>(false)?;
+                        if #formula_path::__ALKAHEST_FORMULA_UNTAGGED {
+                            #(
+                                let value = ::alkahest::private::read_untagged_variant(&de, |de: &mut ::alkahest::private::Deserializer<#de>| {
+                                    #(
+                                        let with_formula = #with_formulas;
+                                        let #bound_names = with_formula.read_field(de, #field_counts == 1 + #field_ids)?;
+                                    )*
+                                    ::alkahest::private::Result::Ok(#ident::#variant_names #bind_names)
+                                });
+                                if let ::alkahest::private::Option::Some(value) = value {
+                                    return ::alkahest::private::Result::Ok(value);
+                                }
+                            )*
+                            return ::alkahest::private::Result::Err(::alkahest::private::DeserializeError::Incompatible);
+                        }
+
+                        let variant_idx = ::alkahest::private::read_variant_tag(&mut de, #formula_path::__ALKAHEST_FORMULA_TAG)?;
                         match variant_idx {
                             #(
                                 #formula_path::#variant_name_ids => {
                                     #(
-                                        let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                            #[allow(unused_variables)]
-                                            #formula_path::#variant_names #bind_ref_names => #bound_names,
-                                            _ => unreachable!(),
-                                        });
+                                        let with_formula = #with_formulas;
                                         let #bound_names = with_formula.read_field(&mut de, #field_counts == 1 + #field_ids)?;
                                     )*
                                     // #consume_tail
@@ -496,7 +602,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                                     ::alkahest::private::Result::Ok(#ident::#variant_names #bind_names)
                                 }
                             )*
-                            invalid => ::alkahest::private::Result::Err(::alkahest::private::DeserializeError::WrongVariant(invalid)),
+                            #read_unknown
                         }
                     }
 
@@ -504,16 +610,17 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                     fn deserialize_in_place(&mut self, mut de: ::alkahest::private::Deserializer<#de>) -> Result<(), ::alkahest::private::DeserializeError> {
                         #field_checks
 
-                        let variant_idx = de.read_value::<::alkahest::private::u32, _>(false)?;
+                        if #formula_path::__ALKAHEST_FORMULA_UNTAGGED {
+                            *self = <Self as ::alkahest::private::Deserialize<#de, #formula_path>>::deserialize(de)?;
+                            return ::alkahest::private::Result::Ok(());
+                        }
+
+                        let variant_idx = ::alkahest::private::read_variant_tag(&mut de, #formula_path::__ALKAHEST_FORMULA_TAG)?;
                         match (variant_idx, self) {
                             #(
                                 (#formula_path::#variant_name_ids, #ident::#variant_names #bind_ref_mut_names) => {
                                     #(
-                                        let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                            #[allow(unused_variables)]
-                                            #formula_path::#variant_names #bind_ref_names => #bound_names,
-                                            _ => unreachable!(),
-                                        });
+                                        let with_formula = #with_formulas;
                                         with_formula.read_in_place(#bound_names, &mut de, #field_counts == 1 + #field_ids)?;
                                     )*
                                     // #consume_tail
@@ -524,11 +631,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                             #(
                                 (#formula_path::#variant_name_ids, me) => {
                                     #(
-                                        let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                            #[allow(unused_variables)]
-                                            #formula_path::#variant_names #bind_ref_names => #bound_names,
-                                            _ => unreachable!(),
-                                        });
+                                        let with_formula = #with_formulas;
                                         let #bound_names = with_formula.read_field(&mut de, #field_counts == 1 + #field_ids)?;
                                     )*
                                     // #consume_tail
@@ -537,7 +640,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                                     ::alkahest::private::Result::Ok(())
                                 }
                             )*
-                            (invalid, _) => ::alkahest::private::Result::Err(::alkahest::private::DeserializeError::WrongVariant(invalid)),
+                            #read_unknown_in_place
                         }
                     }
                 }
@@ -545,3 +648,59 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
         }
     }
 }
+
+/// Generates code that reads struct fields.
+///
+/// Reads presence bitmap first if `presence_size` is not zero.
+/// Fields missing in the bitmap are set to `Default::default()`.
+fn struct_read_fields(
+    field_codes: &[FieldCode],
+    names: &[syn::Ident],
+    presence_size: usize,
+    in_place: bool,
+) -> TokenStream {
+    let field_count = field_codes.len();
+    let mut tokens = TokenStream::new();
+
+    if presence_size > 0 {
+        tokens.extend(quote::quote! {
+            let __presence: [::alkahest::private::u8; #presence_size] = ::alkahest::private::WithFormula::<[::alkahest::private::u8; #presence_size]>::new().read_field(&mut de, false)?;
+        });
+    }
+
+    for (idx, (code, name)) in field_codes.iter().zip(names).enumerate() {
+        let with_formula = &code.with_formula;
+        let last = field_count == idx + 1;
+        let byte = idx / 8;
+        let bit = 1u8 << (idx % 8);
+
+        tokens.extend(match (&code.skip_if, in_place) {
+            (None, false) => quote::quote! {
+                let with_formula = #with_formula;
+                let #name = with_formula.read_field(&mut de, #last)?;
+            },
+            (None, true) => quote::quote! {
+                let with_formula = #with_formula;
+                with_formula.read_in_place(#name, &mut de, #last)?;
+            },
+            (Some(_), false) => quote::quote! {
+                let with_formula = #with_formula;
+                let #name = if __presence[#byte] & #bit != 0 {
+                    with_formula.read_field(&mut de, #last)?
+                } else {
+                    ::alkahest::private::Default::default()
+                };
+            },
+            (Some(_), true) => quote::quote! {
+                let with_formula = #with_formula;
+                if __presence[#byte] & #bit != 0 {
+                    with_formula.read_in_place(#name, &mut de, #last)?;
+                } else {
+                    *#name = ::alkahest::private::Default::default();
+                }
+            },
+        });
+    }
+
+    tokens
+}
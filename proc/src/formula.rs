@@ -3,33 +3,44 @@ use std::collections::HashSet;
 use proc_macro2::TokenStream;
 use syn::spanned::Spanned;
 
-use crate::{attrs::FormulaArgs, filter_type_param, is_generic_ty};
+use crate::{
+    attrs::{FormulaArgs, VariantArgs},
+    check_no_skip_in_variants, field_formula_ty, generic_param_idents, is_generic_ty,
+    presence_size, split_unknown_variant,
+};
 
 struct Config {
     formula_generics: syn::Generics,
 }
 
 impl Config {
-    pub fn from_args(args: FormulaArgs, generics: &syn::Generics, data: &syn::Data) -> Self {
+    pub fn from_args(
+        args: FormulaArgs,
+        generics: &syn::Generics,
+        data: &syn::Data,
+    ) -> syn::Result<Self> {
         let formula_generics = match args.generics {
             None => {
                 let all_field_types: Vec<_> = match data {
-                    syn::Data::Struct(data) => data.fields.iter().map(|field| &field.ty).collect(),
+                    syn::Data::Struct(data) => data
+                        .fields
+                        .iter()
+                        .map(field_formula_ty)
+                        .collect::<Result<_, _>>()?,
                     syn::Data::Enum(data) => data
                         .variants
                         .iter()
-                        .flat_map(|variant| variant.fields.iter().map(|field| &field.ty))
-                        .collect(),
+                        .flat_map(|variant| variant.fields.iter().map(field_formula_ty))
+                        .collect::<Result<_, _>>()?,
                     syn::Data::Union(_) => {
                         panic!("Alkahest does not support unions");
                     }
                 };
 
-                let mut all_generic_field_types: HashSet<_> =
-                    all_field_types.iter().copied().collect();
+                let mut all_generic_field_types: HashSet<_> = all_field_types.iter().collect();
 
                 all_generic_field_types
-                    .retain(|ty| is_generic_ty(ty, &filter_type_param(generics.params.iter())));
+                    .retain(|ty| is_generic_ty(ty, &generic_param_idents(generics.params.iter())));
 
                 let mut formula_generics = generics.clone();
                 if !all_generic_field_types.is_empty() {
@@ -57,7 +68,7 @@ impl Config {
             }
         };
 
-        Config { formula_generics }
+        Ok(Config { formula_generics })
     }
 }
 
@@ -65,7 +76,9 @@ impl Config {
 pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenStream> {
     let ident = &input.ident;
 
-    let config = Config::from_args(args, &input.generics, &input.data);
+    let tag = args.tag.clone();
+    let untagged = args.untagged.clone();
+    let config = Config::from_args(args, &input.generics, &input.data)?;
 
     match &input.data {
         syn::Data::Union(data) => Err(syn::Error::new_spanned(
@@ -73,8 +86,25 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
             "Formula cannot be derived for unions",
         )),
         syn::Data::Struct(data) => {
-            let all_field_types: Vec<_> = data.fields.iter().map(|field| &field.ty).collect();
-            let last_field_type = all_field_types.last().copied().into_iter();
+            if let Some(tag) = tag {
+                return Err(syn::Error::new_spanned(
+                    tag,
+                    "`tag` can be specified only for enums",
+                ));
+            }
+            if let Some(untagged) = untagged {
+                return Err(syn::Error::new_spanned(
+                    untagged,
+                    "`untagged` can be specified only for enums",
+                ));
+            }
+
+            let all_field_types: Vec<_> = data
+                .fields
+                .iter()
+                .map(field_formula_ty)
+                .collect::<Result<_, _>>()?;
+            let last_field_type = all_field_types.last().into_iter();
 
             let field_names_order = match &data.fields {
                 syn::Fields::Named(fields) => fields
@@ -91,6 +121,16 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
             };
 
             let field_ids: Vec<_> = (0..data.fields.len()).collect();
+            let field_count = data.fields.len();
+
+            // Struct with skippable fields starts with presence bitmap
+            // and its size depends on present fields.
+            let presence_size = presence_size(&data.fields)?;
+            let exact_size = if presence_size == 0 {
+                quote::quote! { {true #(; <#last_field_type as ::alkahest::private::Formula>::EXACT_SIZE)*} }
+            } else {
+                quote::quote! { false }
+            };
 
             let (formula_impl_generics, formula_type_generics, formula_where_clause) =
                 config.formula_generics.split_for_impl();
@@ -121,6 +161,10 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                         pub const #field_names_order: ::alkahest::private::usize = #field_ids;
                     )*
 
+                    #[doc(hidden)]
+                    #[allow(non_upper_case_globals)]
+                    pub const __ALKAHEST_FORMULA_FIELD_COUNT: ::alkahest::private::usize = #field_count;
+
                     // #(#with_fields)*
 
                     #[doc(hidden)]
@@ -133,7 +177,7 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                 impl #formula_impl_generics ::alkahest::private::Formula for #ident #formula_type_generics #formula_where_clause {
                     const MAX_STACK_SIZE: ::alkahest::private::Option<::alkahest::private::usize> = {
                         #[allow(unused_mut)]
-                        let mut max_size = Some(0);
+                        let mut max_size = Some(#presence_size);
                         #(
                             max_size = ::alkahest::private::sum_size(max_size, <#all_field_types as ::alkahest::private::Formula>::MAX_STACK_SIZE);
                         )*;
@@ -141,7 +185,7 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                         max_size
                     };
 
-                    const EXACT_SIZE: ::alkahest::private::bool = {true #(; <#last_field_type as ::alkahest::private::Formula>::EXACT_SIZE)*};
+                    const EXACT_SIZE: ::alkahest::private::bool = #exact_size;
 
                     const HEAPLESS: ::alkahest::private::bool = true #(&& <#all_field_types as ::alkahest::private::Formula>::HEAPLESS)*;
                 }
@@ -152,15 +196,34 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
             Ok(tokens)
         }
         syn::Data::Enum(data) => {
-            let all_field_types: Vec<Vec<&syn::Type>> = data
+            check_no_skip_in_variants(data)?;
+
+            let (known, unknown) = split_unknown_variant(data)?;
+            if let (Some(untagged), Some(_)) = (&untagged, &unknown) {
+                return Err(syn::Error::new_spanned(
+                    untagged,
+                    "`unknown` variant requires tagged enum",
+                ));
+            }
+
+            // Touch all variants including `unknown`.
+            let touch_data = data;
+            let data = &known;
+
+            let mut all_field_types: Vec<Vec<syn::Type>> = data
                 .variants
                 .iter()
-                .map(|variant| variant.fields.iter().map(|field| &field.ty).collect())
-                .collect();
+                .map(|variant| variant.fields.iter().map(field_formula_ty).collect())
+                .collect::<Result<_, _>>()?;
+
+            // `unknown` variant is serialized as a bare tag.
+            if unknown.is_some() {
+                all_field_types.push(Vec::new());
+            }
 
             let last_field_types: Vec<Vec<_>> = all_field_types
                 .iter()
-                .map(|variants| variants.last().copied().into_iter().collect())
+                .map(|variants| variants.last().into_iter().collect())
                 .collect();
 
             let field_names_order: Vec<Vec<syn::Ident>> = data
@@ -194,8 +257,23 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                 .map(|v| quote::format_ident!("__ALKAHEST_FORMULA_VARIANT_{}_IDX", v.ident))
                 .collect();
 
-            #[allow(clippy::cast_possible_truncation)]
-            let variant_ids: Vec<_> = (0..data.variants.len() as u32).collect();
+            let variant_field_count_ids: Vec<syn::Ident> = data
+                .variants
+                .iter()
+                .map(|v| quote::format_ident!("__ALKAHEST_FORMULA_VARIANT_{}_FIELD_COUNT", v.ident))
+                .collect();
+
+            let variant_field_counts: Vec<usize> =
+                data.variants.iter().map(|v| v.fields.len()).collect();
+
+            let tag = match tag {
+                None => syn::parse_quote! { u32 },
+                Some(tag) => check_tag_type(tag)?,
+            };
+            let variant_ids = variant_ids(data, &tag)?;
+
+            let is_untagged = untagged.is_some();
+            let tag_size = quote::quote! { <#tag as ::alkahest::private::Formula>::MAX_STACK_SIZE };
 
             let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
 
@@ -210,7 +288,7 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
             //     quote::quote! {}
             // };
 
-            let touch_variants = data
+            let touch_variants = touch_data
                 .variants
                 .iter()
                 .map(|v| {
@@ -262,6 +340,87 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                 })
                 .collect::<Vec<_>>();
 
+            let variant_count = data.variants.len() as u32;
+
+            let variant_names: Vec<String> =
+                data.variants.iter().map(|v| v.ident.to_string()).collect();
+            let unknown_variant_error = if variant_names.is_empty() {
+                format!("Variant is not found in formula `{ident}`. Formula has no variants")
+            } else {
+                let available = variant_names
+                    .iter()
+                    .map(|name| format!("`{name}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "Variant is not found in formula `{ident}`. Available variants: {available}"
+                )
+            };
+
+            // Untagged variants are told apart by consuming the whole value,
+            // so values must not be padded to the largest variant.
+            // Unsized formula makes every value carry its own size.
+            let (max_stack_size, exact_size) = if is_untagged {
+                (
+                    quote::quote! {
+                        const MAX_STACK_SIZE: ::alkahest::private::Option<::alkahest::private::usize> = ::alkahest::private::Option::None;
+                    },
+                    quote::quote! {
+                        const EXACT_SIZE: ::alkahest::private::bool = false;
+                    },
+                )
+            } else {
+                (
+                    quote::quote! {
+                        const MAX_STACK_SIZE: ::alkahest::private::Option<::alkahest::private::usize> = {
+                            #[allow(unused_mut)]
+                            let mut max_size = Some(0);
+
+                            #(
+                                let var_size = {
+                                    #[allow(unused_mut)]
+                                    let mut max_size = Some(0);
+                                    #(
+                                        max_size = ::alkahest::private::sum_size(max_size, <#all_field_types as ::alkahest::private::Formula>::MAX_STACK_SIZE);
+                                    )*;
+                                    max_size
+                                };
+                                max_size = ::alkahest::private::max_size(max_size, var_size);
+                            )*
+
+                            // #expand_size
+                            ::alkahest::private::sum_size(#tag_size, max_size)
+                        };
+                    },
+                    quote::quote! {
+                        #[allow(unused_assignments)]
+                        const EXACT_SIZE: ::alkahest::private::bool = true && {
+                            let mut exact = true;
+                            let mut common_size = None;
+                            #(
+                                #(exact &= <#last_field_types as ::alkahest::private::Formula>::EXACT_SIZE;)*
+
+                                let var_size = {
+                                    #[allow(unused_mut)]
+                                    let mut max_size = Some(0);
+                                    #(
+                                        max_size = ::alkahest::private::sum_size(max_size, <#all_field_types as ::alkahest::private::Formula>::MAX_STACK_SIZE);
+                                    )*;
+                                    max_size
+                                };
+                                exact &= match (common_size, var_size) {
+                                    (_, None) => false,
+                                    (None, _) => true,
+                                    (Some(common_size), Some(var_size)) => common_size == var_size,
+                                };
+                                common_size = var_size;
+                            )*
+                            exact
+                        };
+                    },
+                )
+            };
+
             Ok(quote::quote! {
                 impl #impl_generics #ident #type_generics #where_clause {
                     #(#(
@@ -273,9 +432,39 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                     #(
                         #[doc(hidden)]
                         #[allow(non_upper_case_globals)]
-                        pub const #variant_name_ids: u32 = #variant_ids;
+                        pub const #variant_name_ids: #tag = #variant_ids;
+                    )*
+
+                    #(
+                        #[doc(hidden)]
+                        #[allow(non_upper_case_globals)]
+                        pub const #variant_field_count_ids: ::alkahest::private::usize = #variant_field_counts;
                     )*
 
+                    #[doc(hidden)]
+                    #[allow(non_upper_case_globals)]
+                    pub const __ALKAHEST_FORMULA_TAG: #tag = 0;
+
+                    #[doc(hidden)]
+                    #[allow(non_upper_case_globals)]
+                    pub const __ALKAHEST_FORMULA_VARIANT_TAGS: &'static [#tag] = &[#(#variant_ids),*];
+
+                    #[doc(hidden)]
+                    #[allow(non_upper_case_globals)]
+                    pub const __ALKAHEST_FORMULA_UNTAGGED: ::alkahest::private::bool = #is_untagged;
+
+                    #[doc(hidden)]
+                    #[allow(non_upper_case_globals)]
+                    pub const __ALKAHEST_FORMULA_VARIANT_COUNT: ::alkahest::private::u32 = #variant_count;
+
+                    #[doc(hidden)]
+                    #[allow(non_upper_case_globals)]
+                    pub const __ALKAHEST_FORMULA_VARIANT_NAMES: &'static [&'static ::alkahest::private::str] = &[#(#variant_names),*];
+
+                    #[doc(hidden)]
+                    #[allow(non_upper_case_globals)]
+                    pub const __ALKAHEST_FORMULA_UNKNOWN_VARIANT: &'static ::alkahest::private::str = #unknown_variant_error;
+
                     #[doc(hidden)]
                     #[allow(dead_code, unused_variables)]
                     fn __alkahest_touch(&self) {
@@ -293,50 +482,9 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                 }
 
                 impl #formula_impl_generics ::alkahest::private::Formula for #ident #formula_type_generics #formula_where_clause {
-                    const MAX_STACK_SIZE: ::alkahest::private::Option<::alkahest::private::usize> = {
-                        #[allow(unused_mut)]
-                        let mut max_size = Some(0);
-
-                        #(
-                            let var_size = {
-                                #[allow(unused_mut)]
-                                let mut max_size = Some(0);
-                                #(
-                                    max_size = ::alkahest::private::sum_size(max_size, <#all_field_types as ::alkahest::private::Formula>::MAX_STACK_SIZE);
-                                )*;
-                                max_size
-                            };
-                            max_size = ::alkahest::private::max_size(max_size, var_size);
-                        )*
+                    #max_stack_size
 
-                        // #expand_size
-                        ::alkahest::private::sum_size(::alkahest::private::VARIANT_SIZE_OPT, max_size)
-                    };
-
-                    #[allow(unused_assignments)]
-                    const EXACT_SIZE: ::alkahest::private::bool = true && {
-                        let mut exact = true;
-                        let mut common_size = None;
-                        #(
-                            #(exact &= <#last_field_types as ::alkahest::private::Formula>::EXACT_SIZE;)*
-
-                            let var_size = {
-                                #[allow(unused_mut)]
-                                let mut max_size = Some(0);
-                                #(
-                                    max_size = ::alkahest::private::sum_size(max_size, <#all_field_types as ::alkahest::private::Formula>::MAX_STACK_SIZE);
-                                )*;
-                                max_size
-                            };
-                            exact &= match (common_size, var_size) {
-                                (_, None) => false,
-                                (None, _) => true,
-                                (Some(common_size), Some(var_size)) => common_size == var_size,
-                            };
-                            common_size = var_size;
-                        )*
-                        exact
-                    };
+                    #exact_size
 
                     const HEAPLESS: ::alkahest::private::bool = true #(#(&& <#all_field_types as ::alkahest::private::Formula>::HEAPLESS)*)*;
                 }
@@ -346,3 +494,91 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
         }
     }
 }
+
+/// Checks that enum tag type is supported.
+fn check_tag_type(tag: syn::Type) -> syn::Result<syn::Type> {
+    if let syn::Type::Path(syn::TypePath { qself: None, path }) = &tag {
+        if path.is_ident("u8") || path.is_ident("u16") || path.is_ident("u32") {
+            return Ok(tag);
+        }
+    }
+    Err(syn::Error::new_spanned(
+        tag,
+        "Enum tag must be one of `u8`, `u16` or `u32`",
+    ))
+}
+
+/// Computes wire discriminants of enum variants.
+///
+/// `tag_value` attribute takes precedence.
+/// Then explicit discriminants are used as-is,
+/// others are one greater than the previous variant's discriminant,
+/// just like Rust does.
+///
+/// Rejects duplicate discriminants and ones that do not fit the tag type.
+///
+/// Returns unsuffixed literals, so they fit the tag type.
+fn variant_ids(data: &syn::DataEnum, tag: &syn::Type) -> syn::Result<Vec<syn::LitInt>> {
+    let max = match tag {
+        syn::Type::Path(syn::TypePath { path, .. }) if path.is_ident("u8") => u32::from(u8::MAX),
+        syn::Type::Path(syn::TypePath { path, .. }) if path.is_ident("u16") => u32::from(u16::MAX),
+        _ => u32::MAX,
+    };
+
+    let mut seen = Vec::<(u32, &syn::Ident)>::new();
+    let mut next = Some(0u32);
+    data.variants
+        .iter()
+        .map(|variant| {
+            let args = VariantArgs::parse(variant)?;
+            let (id, span) = match (&args.tag_value, &variant.discriminant) {
+                (Some(lit), _) => (lit.base10_parse::<u32>()?, lit.span()),
+                (
+                    None,
+                    Some((
+                        _,
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Int(lit),
+                            ..
+                        }),
+                    )),
+                ) => (lit.base10_parse::<u32>()?, lit.span()),
+                (None, Some((_, expr))) => {
+                    return Err(syn::Error::new_spanned(
+                        expr,
+                        "Alkahest supports only integer literals as enum discriminants",
+                    ))
+                }
+                (None, None) => {
+                    let id = next.ok_or_else(|| {
+                        syn::Error::new_spanned(variant, "Enum discriminant overflows `u32`")
+                    })?;
+                    (id, variant.ident.span())
+                }
+            };
+            if id > max {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "Discriminant {} of variant `{}` does not fit enum tag type `{}`",
+                        id,
+                        variant.ident,
+                        quote::ToTokens::to_token_stream(tag),
+                    ),
+                ));
+            }
+            if let Some((_, other)) = seen.iter().find(|(seen_id, _)| *seen_id == id) {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "Discriminant {} of variant `{}` is already used by variant `{}`",
+                        id, variant.ident, other,
+                    ),
+                ));
+            }
+            seen.push((id, &variant.ident));
+            next = id.checked_add(1);
+            Ok(syn::LitInt::new(&id.to_string(), span))
+        })
+        .collect()
+}
@@ -1,19 +1,25 @@
 extern crate proc_macro;
 
+mod arbitrary;
 mod attrs;
 mod deserialize;
 mod formula;
 mod serialize;
 
-use attrs::{DeserializeArgs, FormulaArgs, SerializeArgs};
+use attrs::{DeserializeArgs, FieldArgs, FormulaArgs, SerializeArgs, VariantArgs};
 use proc_macro::TokenStream;
 
 #[proc_macro_attribute]
 pub fn alkahest(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let mut output = item.clone();
-    let input = syn::parse_macro_input!(item as syn::DeriveInput);
+    let mut input = syn::parse_macro_input!(item as syn::DeriveInput);
 
-    match alkahest_impl(attr, input) {
+    let result = alkahest_impl(attr, &input);
+
+    // Field and variant attributes are consumed by this macro.
+    attrs::strip_helper_args(&mut input.data);
+    let mut output = TokenStream::from(quote::quote!(#input));
+
+    match result {
         Ok(tokens) => output.extend(TokenStream::from(tokens)),
         Err(err) => output.extend(TokenStream::from(err.to_compile_error())),
     }
@@ -22,22 +28,25 @@ pub fn alkahest(attr: TokenStream, item: TokenStream) -> TokenStream {
 
 fn alkahest_impl(
     attr: TokenStream,
-    input: syn::DeriveInput,
+    input: &syn::DeriveInput,
 ) -> syn::Result<proc_macro2::TokenStream> {
     let mut output = proc_macro2::TokenStream::new();
     let attr = proc_macro2::TokenStream::from(attr);
     let args = attrs::Args::parse_attributes(attr)?;
     if let Some(args) = args.formula {
-        output.extend(formula::derive(args, &input)?);
+        output.extend(formula::derive(args, input)?);
     }
     if let Some(args) = args.serialize {
-        output.extend(serialize::derive(args, &input, false)?);
+        output.extend(serialize::derive(args, input, false)?);
     }
     if let Some(args) = args.serialize_ref {
-        output.extend(serialize::derive(args, &input, true)?);
+        output.extend(serialize::derive(args, input, true)?);
     }
     if let Some(args) = args.deserialize {
-        output.extend(deserialize::derive(args, &input)?);
+        output.extend(deserialize::derive(args, input)?);
+    }
+    if args.arbitrary.is_some() {
+        output.extend(arbitrary::derive(input)?);
     }
     Ok(output)
 }
@@ -46,7 +55,7 @@ fn alkahest_impl(
 ///
 /// This macro requires that type is either `struct` or `enum`.
 /// All fields must implement `Formula`.
-#[proc_macro_derive(Formula)]
+#[proc_macro_derive(Formula, attributes(alkahest))]
 pub fn derive_formula(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
     match formula::derive(FormulaArgs::empty(), &input) {
@@ -59,7 +68,7 @@ pub fn derive_formula(input: TokenStream) -> TokenStream {
 ///
 /// This macro requires that type is either `struct` or `enum`.
 /// All fields must implement `Serialize`.
-#[proc_macro_derive(Serialize)]
+#[proc_macro_derive(Serialize, attributes(alkahest))]
 pub fn derive_serialize(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
     match serialize::derive(SerializeArgs::empty(), &input, false) {
@@ -72,7 +81,7 @@ pub fn derive_serialize(input: TokenStream) -> TokenStream {
 ///
 /// This macro requires that type is either `struct` or `enum`.
 /// All fields must implement `Serialize`.
-#[proc_macro_derive(SerializeRef)]
+#[proc_macro_derive(SerializeRef, attributes(alkahest))]
 pub fn derive_serialize_ref(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
     match serialize::derive(SerializeArgs::empty(), &input, true) {
@@ -85,7 +94,7 @@ pub fn derive_serialize_ref(input: TokenStream) -> TokenStream {
 ///
 /// This macro requires that type is either `struct` or `enum`.
 /// All fields must implement `Deserialize`.
-#[proc_macro_derive(Deserialize)]
+#[proc_macro_derive(Deserialize, attributes(alkahest))]
 pub fn derive_deserialize(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
     match deserialize::derive(DeserializeArgs::empty(), &input) {
@@ -96,15 +105,10 @@ pub fn derive_deserialize(input: TokenStream) -> TokenStream {
 
 fn is_generic_path<'a>(
     path: &syn::Path,
-    params: &(impl Clone + Iterator<Item = &'a syn::TypeParam>),
+    params: &(impl Clone + Iterator<Item = &'a syn::Ident>),
 ) -> bool {
     path.segments.iter().any(|seg| {
-        if params.clone().any(|p| {
-            // if p.ident == "T" {
-            //     panic!();
-            // }
-            p.ident == seg.ident
-        }) {
+        if params.clone().any(|p| *p == seg.ident) {
             return true;
         }
         match &seg.arguments {
@@ -129,18 +133,21 @@ fn is_generic_path<'a>(
 //     params.any(|param| matches!(param, syn::GenericParam::Type(_)))
 // }
 
-fn filter_type_param<'a>(
+/// Returns identifiers of type and const generic parameters.
+/// Field types that mention them need bounds in generated impls.
+fn generic_param_idents<'a>(
     params: impl Clone + Iterator<Item = &'a syn::GenericParam>,
-) -> impl Clone + Iterator<Item = &'a syn::TypeParam> {
+) -> impl Clone + Iterator<Item = &'a syn::Ident> {
     params.filter_map(|param| match param {
-        syn::GenericParam::Type(param) => Some(param),
-        _ => None,
+        syn::GenericParam::Type(param) => Some(&param.ident),
+        syn::GenericParam::Const(param) => Some(&param.ident),
+        syn::GenericParam::Lifetime(_) => None,
     })
 }
 
 fn is_generic_ty<'a>(
     ty: &syn::Type,
-    params: &(impl Clone + Iterator<Item = &'a syn::TypeParam>),
+    params: &(impl Clone + Iterator<Item = &'a syn::Ident>),
 ) -> bool {
     match ty {
         syn::Type::Array(syn::TypeArray { elem, .. })
@@ -180,6 +187,78 @@ fn is_generic_ty<'a>(
     }
 }
 
+/// Checks that data has the same number of fields as formula.
+///
+/// Check is performed at compile time, unless formula has generic arguments
+/// that cannot be used in constant items.
+fn field_count_check(
+    count: usize,
+    count_const: &syn::Ident,
+    this: &str,
+    formula: &syn::Path,
+) -> proc_macro2::TokenStream {
+    let error = format!("Field count of `{this}` does not match formula's");
+
+    let has_arguments = formula
+        .segments
+        .iter()
+        .any(|seg| !matches!(seg.arguments, syn::PathArguments::None));
+
+    if has_arguments {
+        quote::quote!(::alkahest::private::debug_assert_eq!(#count, #formula::#count_const, #error);)
+    } else {
+        quote::quote!(
+            const _: () = ::alkahest::private::assert!(#count == #formula::#count_const, #error);
+        )
+    }
+}
+
+/// Checks at compile time that formula has the variant
+/// specified with `variant` argument.
+/// Error names variants available in the formula.
+fn variant_check(variant: &syn::Ident, formula: &syn::Path) -> proc_macro2::TokenStream {
+    let has_arguments = formula
+        .segments
+        .iter()
+        .any(|seg| !matches!(seg.arguments, syn::PathArguments::None));
+
+    if has_arguments {
+        return proc_macro2::TokenStream::new();
+    }
+
+    let name = variant.to_string();
+    quote::quote_spanned!(variant.span() =>
+        const _: () = ::alkahest::private::assert!(
+            ::alkahest::private::has_variant(#formula::__ALKAHEST_FORMULA_VARIANT_NAMES, #name),
+            "{}",
+            #formula::__ALKAHEST_FORMULA_UNKNOWN_VARIANT,
+        );
+    )
+}
+
+/// Returns expression with tag of the variant
+/// specified with `variant` argument.
+///
+/// Tag is looked up by name when formula can be used in constant items,
+/// so that misspelled variant is reported only by [`variant_check`].
+fn variant_tag(variant: &syn::Ident, formula: &syn::Path) -> proc_macro2::TokenStream {
+    let has_arguments = formula
+        .segments
+        .iter()
+        .any(|seg| !matches!(seg.arguments, syn::PathArguments::None));
+
+    if has_arguments {
+        let variant_name_idx = quote::format_ident!("__ALKAHEST_FORMULA_VARIANT_{}_IDX", variant);
+        return quote::quote! { #formula::#variant_name_idx };
+    }
+
+    let name = variant.to_string();
+    quote::quote! {{
+        const __ALKAHEST_VARIANT_IDX: ::alkahest::private::usize = ::alkahest::private::variant_index(#formula::__ALKAHEST_FORMULA_VARIANT_NAMES, #name);
+        ::alkahest::private::variant_tag(#formula::__ALKAHEST_FORMULA_VARIANT_TAGS, __ALKAHEST_VARIANT_IDX)
+    }}
+}
+
 fn struct_field_order_checks(
     data: &syn::DataStruct,
     variant: Option<&syn::Ident>,
@@ -188,7 +267,14 @@ fn struct_field_order_checks(
 ) -> proc_macro2::TokenStream {
     let no_named_fields = syn::punctuated::Punctuated::<syn::Field, syn::Token![,]>::new();
 
-    match &data.fields {
+    let count_const = match variant {
+        None => quote::format_ident!("__ALKAHEST_FORMULA_FIELD_COUNT"),
+        Some(v) => quote::format_ident!("__ALKAHEST_FORMULA_VARIANT_{}_FIELD_COUNT", v),
+    };
+    let count_check =
+        field_count_check(data.fields.len(), &count_const, &this.to_string(), formula);
+
+    let order_checks: proc_macro2::TokenStream = match &data.fields {
         syn::Fields::Named(fields) => fields.named.iter(),
         _ => no_named_fields.iter(),
     }.enumerate()
@@ -208,7 +294,12 @@ fn struct_field_order_checks(
         let error = format!("Field `{this}.{f}` is out of order with formula's");
         quote::quote_spanned!(f.span() => ::alkahest::private::debug_assert_eq!(#idx, #formula::#order, #error);)
     })
-    .collect()
+    .collect();
+
+    quote::quote! {
+        #count_check
+        #order_checks
+    }
 }
 
 fn enum_field_order_checks(
@@ -218,7 +309,18 @@ fn enum_field_order_checks(
 ) -> proc_macro2::TokenStream {
     let no_named_fields = syn::punctuated::Punctuated::<syn::Field, syn::Token![,]>::new();
 
-    data.variants.iter().flat_map(|v| {
+    let count_checks = data.variants.iter().map(|v| {
+        let count_const =
+            quote::format_ident!("__ALKAHEST_FORMULA_VARIANT_{}_FIELD_COUNT", v.ident);
+        field_count_check(
+            v.fields.len(),
+            &count_const,
+            &format!("{this}::{}", v.ident),
+            formula,
+        )
+    });
+
+    let order_checks = data.variants.iter().flat_map(|v| {
         match &v.fields {
             syn::Fields::Named(fields) => fields.named.iter(),
             _ => no_named_fields.iter(),
@@ -234,5 +336,166 @@ fn enum_field_order_checks(
             let error = format!("Field `{this}.{f}` is out of order with formula's");
             quote::quote_spanned!(f.span() => ::alkahest::private::debug_assert_eq!(#idx, #formula::#order, #error);)
         })
-    }).collect()
+    });
+
+    quote::quote! {
+        #(#count_checks)*
+        #(#order_checks)*
+    }
+}
+
+/// Returns formula type of the field.
+/// Formula from `as` attribute if present, otherwise field type itself.
+fn field_formula_ty(field: &syn::Field) -> syn::Result<syn::Type> {
+    let args = FieldArgs::parse(field)?;
+    Ok(args.as_formula.unwrap_or_else(|| field.ty.clone()))
+}
+
+/// Returns types of fields that mention generic parameters,
+/// paired with formula from `as` attribute if present.
+/// Each pair is returned once.
+fn generic_field_types<'a>(
+    fields: impl Iterator<Item = &'a syn::Field>,
+    params: &syn::punctuated::Punctuated<syn::GenericParam, syn::Token![,]>,
+) -> syn::Result<Vec<(syn::Type, Option<syn::Type>)>> {
+    let params = generic_param_idents(params.iter());
+    let mut types = Vec::new();
+    for field in fields {
+        let as_formula = FieldArgs::parse(field)?.as_formula;
+        let generic = is_generic_ty(&field.ty, &params)
+            || as_formula
+                .as_ref()
+                .is_some_and(|ty| is_generic_ty(ty, &params));
+        let pair = (field.ty.clone(), as_formula);
+        if generic && !types.contains(&pair) {
+            types.push(pair);
+        }
+    }
+    Ok(types)
+}
+
+/// Returns size of presence bitmap of the struct in bytes.
+///
+/// Bitmap has one bit per field and is written only if any field
+/// has `skip_serializing_if` attribute, otherwise size is zero.
+fn presence_size(fields: &syn::Fields) -> syn::Result<usize> {
+    for field in fields {
+        if FieldArgs::parse(field)?.skip_serializing_if.is_some() {
+            return Ok(fields.len().div_ceil(8));
+        }
+    }
+    Ok(0)
+}
+
+/// Fails if any field of enum variants has `skip_serializing_if` attribute.
+fn check_no_skip_in_variants(data: &syn::DataEnum) -> syn::Result<()> {
+    for field in data.variants.iter().flat_map(|v| v.fields.iter()) {
+        if let Some(path) = FieldArgs::parse(field)?.skip_serializing_if {
+            return Err(syn::Error::new_spanned(
+                path,
+                "`skip_serializing_if` is supported only for struct fields",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Separates variant marked with `#[alkahest(unknown)]` from the rest.
+///
+/// Unknown variant has no tag of its own and holds raw tag
+/// of any variant the formula does not know,
+/// so it must have single unnamed field.
+/// Returns enum data without it.
+fn split_unknown_variant(data: &syn::DataEnum) -> syn::Result<(syn::DataEnum, Option<syn::Ident>)> {
+    let mut known = data.clone();
+    known.variants.clear();
+
+    let mut unknown = None;
+    for variant in &data.variants {
+        if VariantArgs::parse(variant)?.unknown.is_none() {
+            known.variants.push(variant.clone());
+            continue;
+        }
+        if unknown.is_some() {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "Only one variant can be marked `unknown`",
+            ));
+        }
+        if !matches!(&variant.fields, syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "`unknown` variant must have single unnamed field for the tag",
+            ));
+        }
+        unknown = Some(variant.ident.clone());
+    }
+
+    Ok((known, unknown))
+}
+
+/// Per-field tokens for generated `Serialize` and `Deserialize` impls.
+struct FieldCode {
+    /// Expression that evaluates to `WithFormula` of the field.
+    with_formula: proc_macro2::TokenStream,
+
+    /// Expression that converts bound field into value to serialize.
+    value: proc_macro2::TokenStream,
+
+    /// Expression that converts field bound by reference
+    /// into reference to value for size hint.
+    hint: proc_macro2::TokenStream,
+
+    /// Predicate from `skip_serializing_if` attribute.
+    skip_if: Option<syn::ExprPath>,
+}
+
+/// Generates per-field code.
+///
+/// Field formula is inferred from `pattern` matching formula
+/// unless specified with `as` attribute.
+/// Fields with `as` attribute are serialized through `SerIter`.
+fn field_code(
+    field: &syn::Field,
+    name: &syn::Ident,
+    formula: &impl quote::ToTokens,
+    pattern: &proc_macro2::TokenStream,
+    by_ref: bool,
+) -> syn::Result<FieldCode> {
+    let args = FieldArgs::parse(field)?;
+
+    let skip_if = args.skip_serializing_if;
+
+    let code = match args.as_formula {
+        None => FieldCode {
+            with_formula: quote::quote! {
+                ::alkahest::private::with_formula(|s: &#formula| match *s {
+                    #[allow(unused_variables)]
+                    #pattern => #name,
+                    _ => unreachable!(),
+                })
+            },
+            value: quote::quote! { #name },
+            hint: if by_ref {
+                quote::quote! { &#name }
+            } else {
+                quote::quote! { #name }
+            },
+            skip_if,
+        },
+        Some(as_formula) => FieldCode {
+            with_formula: quote::quote! {
+                ::alkahest::private::WithFormula::<#as_formula>::new()
+            },
+            value: quote::quote! {
+                ::alkahest::private::SerIter(::alkahest::private::IntoIterator::into_iter(#name))
+            },
+            hint: quote::quote! {
+                &::alkahest::private::SerIter(::alkahest::private::IntoIterator::into_iter(#name))
+            },
+            skip_if,
+        },
+    };
+
+    Ok(code)
 }
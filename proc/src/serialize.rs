@@ -1,10 +1,9 @@
-use std::collections::HashSet;
-
 use proc_macro2::TokenStream;
 
 use crate::{
-    attrs::SerializeArgs, enum_field_order_checks, filter_type_param, is_generic_ty,
-    struct_field_order_checks,
+    attrs::SerializeArgs, check_no_skip_in_variants, enum_field_order_checks, field_code,
+    generic_field_types, presence_size, split_unknown_variant, struct_field_order_checks,
+    variant_check, variant_tag, FieldCode,
 };
 
 struct Config {
@@ -18,6 +17,47 @@ struct Config {
     check_fields: bool,
 }
 
+/// Returns predicates that fields are serializable with their formulas.
+///
+/// Fields with `as` attribute are serialized through `SerIter`
+/// over their `IntoIterator` impl.
+/// Size hint always iterates over reference to the field.
+fn field_bounds(
+    types: Vec<(syn::Type, Option<syn::Type>)>,
+    by_ref: bool,
+) -> Vec<syn::WherePredicate> {
+    let mut predicates: Vec<syn::WherePredicate> = Vec::new();
+    for (ty, as_formula) in types {
+        match (as_formula, by_ref) {
+            (None, false) => predicates.push(syn::parse_quote! {
+                #ty: ::alkahest::private::Formula + ::alkahest::private::Serialize<#ty>
+            }),
+            (None, true) => {
+                predicates.push(syn::parse_quote! { #ty: ::alkahest::private::Formula });
+                predicates.push(syn::parse_quote! {
+                    for<'ser> &'ser #ty: ::alkahest::private::Serialize<#ty>
+                });
+            }
+            (Some(formula), by_ref) => {
+                predicates.push(syn::parse_quote! { #formula: ::alkahest::private::Formula });
+                if !by_ref {
+                    predicates.push(syn::parse_quote! { #ty: ::alkahest::private::IntoIterator });
+                    predicates.push(syn::parse_quote! {
+                        ::alkahest::private::SerIter<<#ty as ::alkahest::private::IntoIterator>::IntoIter>: ::alkahest::private::Serialize<#formula>
+                    });
+                }
+                predicates.push(syn::parse_quote! {
+                    for<'ser> &'ser #ty: ::alkahest::private::IntoIterator
+                });
+                predicates.push(syn::parse_quote! {
+                    for<'ser> ::alkahest::private::SerIter<<&'ser #ty as ::alkahest::private::IntoIterator>::IntoIter>: ::alkahest::private::Serialize<#formula>
+                });
+            }
+        }
+    }
+    predicates
+}
+
 impl Config {
     #[allow(clippy::too_many_lines)]
     fn for_type(
@@ -25,10 +65,10 @@ impl Config {
         data: &syn::Data,
         generics: &syn::Generics,
         by_ref: bool,
-    ) -> Self {
+    ) -> syn::Result<Self> {
         let params = &generics.params;
 
-        match (args.formula, args.generics) {
+        let mut cfg = match (args.formula, args.generics) {
             (None, None) if params.is_empty() => Config {
                 formula: syn::parse_quote! { Self },
                 generics: syn::Generics::default(),
@@ -46,55 +86,23 @@ impl Config {
                 // Add predicates that fields implement
                 // `T: Formula + Serialize<T>`
                 // for fields where generics are involved.
+                // Fields with `as = F` attribute get `F: Formula`
+                // and `SerIter<T::IntoIter>: Serialize<F>` instead.
+                // Unless predicates are specified with `bound` attribute.
 
-                match data {
+                let fields: Vec<&syn::Field> = match data {
                     syn::Data::Union(_) => unreachable!(),
-                    syn::Data::Struct(data) => {
-                        let mut all_generic_field_types: HashSet<_> =
-                            data.fields.iter().map(|f| &f.ty).collect();
-                        all_generic_field_types
-                            .retain(|ty| is_generic_ty(ty, &filter_type_param(params.iter())));
-
-                        if !all_generic_field_types.is_empty() {
-                            if by_ref {
-                                let predicates = all_generic_field_types.iter().map(|ty| -> syn::WherePredicate {
-                                    syn::parse_quote! { #ty: ::alkahest::private::Formula }
-                                }).chain(all_generic_field_types.iter().map(|ty| -> syn::WherePredicate {
-                                    syn::parse_quote! { for<'ser> &'ser #ty: ::alkahest::private::Serialize<#ty> }
-                                }));
-                                generics.make_where_clause().predicates.extend(predicates);
-                            } else {
-                                let predicates = all_generic_field_types.iter().map(|ty| -> syn::WherePredicate {
-                                    syn::parse_quote! { #ty: ::alkahest::private::Formula + ::alkahest::private::Serialize<#ty> }
-                                });
-                                generics.make_where_clause().predicates.extend(predicates);
-                            }
-                        }
-                    }
+                    syn::Data::Struct(data) => data.fields.iter().collect(),
                     syn::Data::Enum(data) => {
-                        let all_fields = data.variants.iter().flat_map(|v| v.fields.iter());
-
-                        let mut all_generic_field_types: HashSet<_> =
-                            all_fields.map(|f| &f.ty).collect();
-                        all_generic_field_types.retain(|ty| {
-                            is_generic_ty(ty, &filter_type_param(generics.params.iter()))
-                        });
-
-                        if !all_generic_field_types.is_empty() {
-                            if by_ref {
-                                let predicates = all_generic_field_types.iter().map(|ty| -> syn::WherePredicate {
-                                    syn::parse_quote! { #ty: ::alkahest::private::Formula }
-                                }).chain(all_generic_field_types.iter().map(|ty| -> syn::WherePredicate {
-                                    syn::parse_quote! { for<'ser> &'ser #ty: ::alkahest::private::Serialize<#ty> }
-                                }));
-                                generics.make_where_clause().predicates.extend(predicates);
-                            } else {
-                                let predicates = all_generic_field_types.iter().map(|ty| -> syn::WherePredicate {
-                                    syn::parse_quote! { #ty: ::alkahest::private::Formula + ::alkahest::private::Serialize<#ty> }
-                                });
-                                generics.make_where_clause().predicates.extend(predicates);
-                            }
-                        }
+                        data.variants.iter().flat_map(|v| v.fields.iter()).collect()
+                    }
+                };
+
+                if args.bound.is_none() {
+                    let predicates =
+                        field_bounds(generic_field_types(fields.into_iter(), params)?, by_ref);
+                    if !predicates.is_empty() {
+                        generics.make_where_clause().predicates.extend(predicates);
                     }
                 }
 
@@ -123,7 +131,35 @@ impl Config {
                 variant: args.variant,
                 check_fields: true,
             },
+        };
+
+        if let Some(variant) = &cfg.variant {
+            if let syn::Data::Enum(_) = data {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "Variant can be specified only for structs",
+                ));
+            }
+            if cfg.formula.is_ident("Self") {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "Variant requires enum formula to be specified",
+                ));
+            }
+        }
+
+        if let Some(bound) = args.bound {
+            cfg.generics.make_where_clause().predicates.extend(bound);
+        }
+
+        if !args.extra_bound.is_empty() {
+            cfg.generics
+                .make_where_clause()
+                .predicates
+                .extend(args.extra_bound);
         }
+
+        Ok(cfg)
     }
 }
 
@@ -137,7 +173,7 @@ pub fn derive(
     let generics = &input.generics;
     let (_impl_generics, type_generics, _where_clause) = generics.split_for_impl();
 
-    let cfg = Config::for_type(args, &input.data, generics, by_ref);
+    let cfg = Config::for_type(args, &input.data, generics, by_ref)?;
 
     match &input.data {
         syn::Data::Union(_) => Err(syn::Error::new_spanned(
@@ -151,10 +187,6 @@ pub fn derive(
                 TokenStream::new()
             };
 
-            let field_count = data.fields.len();
-
-            let field_ids: Vec<_> = (0..data.fields.len()).collect();
-
             let bound_names = data
                 .fields
                 .iter()
@@ -220,19 +252,41 @@ pub fn derive(
                 Some(v) => quote::quote! { :: #v },
             };
 
+            let formula_path = &cfg.formula;
+
             let start_stack_size = match &cfg.variant {
                 None => quote::quote! { 0usize },
-                Some(_) => quote::quote! { ::alkahest::private::VARIANT_SIZE },
+                Some(_) => {
+                    quote::quote! { ::alkahest::private::variant_tag_size(#formula_path::__ALKAHEST_FORMULA_TAG, #formula_path::__ALKAHEST_FORMULA_UNTAGGED) }
+                }
             };
 
-            let formula_path = &cfg.formula;
+            let pattern = quote::quote! { #formula_path #with_variant #bind_ref_names };
+            let field_codes = data
+                .fields
+                .iter()
+                .zip(&bound_names)
+                .map(|(field, name)| field_code(field, name, formula_path, &pattern, by_ref))
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            let presence_size = presence_size(&data.fields)?;
+            if presence_size > 0 && (cfg.variant.is_some() || !formula_path.is_ident("Self")) {
+                let skip_if = field_codes.iter().find_map(|c| c.skip_if.as_ref());
+                return Err(syn::Error::new_spanned(
+                    skip_if,
+                    "`skip_serializing_if` requires struct to be its own formula",
+                ));
+            }
+
+            let write_fields =
+                struct_write_fields(&field_codes, &bound_names, presence_size, by_ref);
+            let hint_fields = struct_hint_fields(&field_codes, &bound_names);
 
             let write_variant = match &cfg.variant {
                 None => quote::quote! {},
                 Some(v) => {
-                    let variant_name_idx =
-                        quote::format_ident!("__ALKAHEST_FORMULA_VARIANT_{}_IDX", v);
-                    quote::quote! { ::alkahest::private::write_exact_size_field::<u32, u32, _>(#formula_path::#variant_name_idx, __sizes, __buffer.reborrow())?; }
+                    let tag = variant_tag(v, formula_path);
+                    quote::quote! { ::alkahest::private::write_variant_tag(#tag, #formula_path::__ALKAHEST_FORMULA_UNTAGGED, __sizes, __buffer.reborrow())?; }
                 }
             };
 
@@ -264,13 +318,7 @@ pub fn derive(
 
                             let #ident #bind_ref_names = *self;
                             #write_variant
-                            #(
-                                let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                    #formula_path #with_variant #bind_ref_names => #bound_names,
-                                    _ => unreachable!(),
-                                });
-                                with_formula.write_field(#bound_names, __sizes, __buffer.reborrow(), #field_count == 1 + #field_ids)?;
-                            )*
+                            #write_fields
                             Ok(())
                         }
 
@@ -282,14 +330,8 @@ pub fn derive(
                                 return Some(sizes);
                             }
                             let #ident #bind_ref_names = *self;
-                            let mut __total = ::alkahest::private::Sizes::with_stack(#start_stack_size);
-                            #(
-                                let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                    #formula_path #with_variant #bind_ref_names => #bound_names,
-                                    _ => unreachable!(),
-                                });
-                                __total += with_formula.size_hint(&#bound_names, #field_count == 1 + #field_ids)?;
-                            )*
+                            let mut __total = ::alkahest::private::Sizes::with_stack(#start_stack_size + #presence_size);
+                            #hint_fields
                             Some(__total)
                         }
                     }
@@ -307,13 +349,7 @@ pub fn derive(
 
                             let #ident #bind_names = self;
                             #write_variant
-                            #(
-                                let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                    #formula_path #with_variant #bind_ref_names => #bound_names,
-                                    _ => unreachable!(),
-                                });
-                                with_formula.write_field(#bound_names, __sizes, __buffer.reborrow(), #field_count == 1 + #field_ids)?;
-                            )*
+                            #write_fields
                             Ok(())
                         }
 
@@ -325,36 +361,36 @@ pub fn derive(
                                 return Some(sizes);
                             }
                             let #ident #bind_ref_names = *self;
-                            let mut __total = ::alkahest::private::Sizes::with_stack(#start_stack_size);
-                            #(
-                                let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                    #formula_path #with_variant #bind_ref_names => #bound_names,
-                                    _ => unreachable!(),
-                                });
-                                __total += with_formula.size_hint(#bound_names, #field_count == 1 + #field_ids)?;
-                            )*
+                            let mut __total = ::alkahest::private::Sizes::with_stack(#start_stack_size + #presence_size);
+                            #hint_fields
                             Some(__total)
                         }
                     }
                 }
             };
 
-            Ok(tokens)
+            let variant_check = match &cfg.variant {
+                None => TokenStream::new(),
+                Some(v) => variant_check(v, formula_path),
+            };
+
+            Ok(quote::quote! {
+                #variant_check
+                #tokens
+            })
         }
         syn::Data::Enum(data) => {
+            check_no_skip_in_variants(data)?;
+
+            let (known, unknown) = split_unknown_variant(data)?;
+            let data = &known;
+
             let field_checks = if cfg.check_fields {
                 enum_field_order_checks(data, &input.ident, &cfg.formula)
             } else {
                 TokenStream::new()
             };
 
-            if let Some(variant) = &cfg.variant {
-                return Err(syn::Error::new_spanned(
-                    variant,
-                    "Variant can be specified only for structs",
-                ));
-            }
-
             let field_ids: Vec<Vec<_>> = data
                 .variants
                 .iter()
@@ -448,6 +484,37 @@ pub fn derive(
 
             let formula_path = &cfg.formula;
 
+            let field_codes = data
+                .variants
+                .iter()
+                .zip(&bound_names)
+                .zip(&bind_ref_names)
+                .map(|((v, names), bind_ref_names)| {
+                    let variant_name = &v.ident;
+                    let pattern = quote::quote! { #formula_path::#variant_name #bind_ref_names };
+                    v.fields
+                        .iter()
+                        .zip(names)
+                        .map(|(field, name)| {
+                            field_code(field, name, formula_path, &pattern, by_ref)
+                        })
+                        .collect::<syn::Result<Vec<_>>>()
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            let with_formulas: Vec<Vec<_>> = field_codes
+                .iter()
+                .map(|codes| codes.iter().map(|c| &c.with_formula).collect())
+                .collect();
+            let values: Vec<Vec<_>> = field_codes
+                .iter()
+                .map(|codes| codes.iter().map(|c| &c.value).collect())
+                .collect();
+            let hints: Vec<Vec<_>> = field_codes
+                .iter()
+                .map(|codes| codes.iter().map(|c| &c.hint).collect())
+                .collect();
+
             let mut generics = input.generics.clone();
 
             generics.lt_token = generics.lt_token.or(cfg.generics.lt_token);
@@ -463,6 +530,30 @@ pub fn derive(
 
             let (impl_generics, _type_generics, where_clause) = generics.split_for_impl();
 
+            // `unknown` variant writes back the raw tag it holds.
+            let (write_unknown, hint_unknown) = match &unknown {
+                None => (TokenStream::new(), TokenStream::new()),
+                Some(variant) => {
+                    let (bind_tag, tag) = if by_ref {
+                        (quote::quote! { ref __tag }, quote::quote! { *__tag })
+                    } else {
+                        (quote::quote! { __tag }, quote::quote! { __tag })
+                    };
+                    (
+                        quote::quote! {
+                            #ident::#variant(#bind_tag) => {
+                                ::alkahest::private::write_unknown_variant_tag::<#formula_path, _, _>(#formula_path::__ALKAHEST_FORMULA_VARIANT_TAGS, #tag, __sizes, __buffer.reborrow())
+                            }
+                        },
+                        quote::quote! {
+                            #ident::#variant(_) => {
+                                Some(::alkahest::private::Sizes::with_stack(::alkahest::private::variant_tag_size(#formula_path::__ALKAHEST_FORMULA_TAG, #formula_path::__ALKAHEST_FORMULA_UNTAGGED)))
+                            }
+                        },
+                    )
+                }
+            };
+
             let tokens = if by_ref {
                 quote::quote! {
                     impl #impl_generics ::alkahest::private::SerializeRef<#formula_path> for #ident #type_generics #where_clause {
@@ -476,17 +567,15 @@ pub fn derive(
                             match *self {
                                 #(
                                     #ident::#variant_names #bind_ref_names => {
-                                        ::alkahest::private::write_exact_size_field::<u32, u32, _>(#formula_path::#variant_name_ids, __sizes, __buffer.reborrow())?;
+                                        ::alkahest::private::write_variant_tag(#formula_path::#variant_name_ids, #formula_path::__ALKAHEST_FORMULA_UNTAGGED, __sizes, __buffer.reborrow())?;
                                         #(
-                                            let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                                #formula_path::#variant_names #bind_ref_names => #bound_names,
-                                                _ => unreachable!(),
-                                            });
-                                            with_formula.write_field(#bound_names, __sizes, __buffer.reborrow(), #field_counts == 1 + #field_ids)?;
+                                            let with_formula = #with_formulas;
+                                            with_formula.write_field(#values, __sizes, __buffer.reborrow(), #field_counts == 1 + #field_ids)?;
                                         )*
                                         Ok(())
                                     }
                                 )*
+                                #write_unknown
                             }
                         }
 
@@ -500,17 +589,15 @@ pub fn derive(
                             match *self {
                                 #(
                                     #ident::#variant_names #bind_ref_names => {
-                                        let mut __total = ::alkahest::private::Sizes::with_stack(::alkahest::private::VARIANT_SIZE);
+                                        let mut __total = ::alkahest::private::Sizes::with_stack(::alkahest::private::variant_tag_size(#formula_path::__ALKAHEST_FORMULA_TAG, #formula_path::__ALKAHEST_FORMULA_UNTAGGED));
                                         #(
-                                            let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                                #formula_path::#variant_names #bind_ref_names => #bound_names,
-                                                _ => unreachable!(),
-                                            });
-                                            __total += with_formula.size_hint(&#bound_names, #field_counts == 1 + #field_ids)?;
+                                            let with_formula = #with_formulas;
+                                            __total += with_formula.size_hint(#hints, #field_counts == 1 + #field_ids)?;
                                         )*
                                         Some(__total)
                                     }
                                 )*
+                                #hint_unknown
                             }
                         }
                     }
@@ -528,17 +615,15 @@ pub fn derive(
                             match self {
                                 #(
                                     #ident::#variant_names #bind_names => {
-                                        ::alkahest::private::write_exact_size_field::<u32, u32, _>(#formula_path::#variant_name_ids, __sizes, __buffer.reborrow())?;
+                                        ::alkahest::private::write_variant_tag(#formula_path::#variant_name_ids, #formula_path::__ALKAHEST_FORMULA_UNTAGGED, __sizes, __buffer.reborrow())?;
                                         #(
-                                            let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                                #formula_path::#variant_names #bind_ref_names => #bound_names,
-                                                _ => unreachable!(),
-                                            });
-                                            with_formula.write_field(#bound_names, __sizes, __buffer.reborrow(), #field_counts == 1 + #field_ids)?;
+                                            let with_formula = #with_formulas;
+                                            with_formula.write_field(#values, __sizes, __buffer.reborrow(), #field_counts == 1 + #field_ids)?;
                                         )*
                                         Ok(())
                                     }
                                 )*
+                                #write_unknown
                             }
                         }
 
@@ -552,17 +637,15 @@ pub fn derive(
                             match *self {
                                 #(
                                     #ident::#variant_names #bind_ref_names => {
-                                        let mut __total = ::alkahest::private::Sizes::with_stack(::alkahest::private::VARIANT_SIZE);
+                                        let mut __total = ::alkahest::private::Sizes::with_stack(::alkahest::private::variant_tag_size(#formula_path::__ALKAHEST_FORMULA_TAG, #formula_path::__ALKAHEST_FORMULA_UNTAGGED));
                                         #(
-                                            let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                                #formula_path::#variant_names #bind_ref_names => #bound_names,
-                                                _ => unreachable!(),
-                                            });
-                                            __total += with_formula.size_hint(#bound_names, #field_counts == 1 + #field_ids)?;
+                                            let with_formula = #with_formulas;
+                                            __total += with_formula.size_hint(#hints, #field_counts == 1 + #field_ids)?;
                                         )*
                                         Some(__total)
                                     }
                                 )*
+                                #hint_unknown
                             }
                         }
                     }
@@ -573,3 +656,93 @@ pub fn derive(
         }
     }
 }
+
+/// Generates code that writes struct fields.
+///
+/// Writes presence bitmap first if `presence_size` is not zero
+/// and omits fields for which `skip_serializing_if` predicate holds.
+fn struct_write_fields(
+    field_codes: &[FieldCode],
+    names: &[syn::Ident],
+    presence_size: usize,
+    by_ref: bool,
+) -> TokenStream {
+    let field_count = field_codes.len();
+    let mut tokens = TokenStream::new();
+
+    if presence_size > 0 {
+        let mut set_bits = TokenStream::new();
+        for (idx, (code, name)) in field_codes.iter().zip(names).enumerate() {
+            let byte = idx / 8;
+            let bit = 1u8 << (idx % 8);
+            set_bits.extend(match &code.skip_if {
+                None => quote::quote! { __presence[#byte] |= #bit; },
+                Some(skip_if) => {
+                    let present = quote::format_ident!("__present_{}", idx);
+                    let arg = if by_ref {
+                        quote::quote! { #name }
+                    } else {
+                        quote::quote! { &#name }
+                    };
+                    quote::quote! {
+                        let #present = !#skip_if(#arg);
+                        if #present {
+                            __presence[#byte] |= #bit;
+                        }
+                    }
+                }
+            });
+        }
+
+        tokens.extend(quote::quote! {
+            let mut __presence = [0u8; #presence_size];
+            #set_bits
+            ::alkahest::private::WithFormula::<[::alkahest::private::u8; #presence_size]>::new().write_field(__presence, __sizes, __buffer.reborrow(), false)?;
+        });
+    }
+
+    for (idx, code) in field_codes.iter().enumerate() {
+        let with_formula = &code.with_formula;
+        let value = &code.value;
+        let last = field_count == idx + 1;
+        let write = quote::quote! {
+            let with_formula = #with_formula;
+            with_formula.write_field(#value, __sizes, __buffer.reborrow(), #last)?;
+        };
+        tokens.extend(match &code.skip_if {
+            None => write,
+            Some(_) => {
+                let present = quote::format_ident!("__present_{}", idx);
+                quote::quote! { if #present { #write } }
+            }
+        });
+    }
+
+    tokens
+}
+
+/// Generates code that sums size hints of struct fields into `__total`.
+///
+/// Fields for which `skip_serializing_if` predicate holds are not counted.
+fn struct_hint_fields(field_codes: &[FieldCode], names: &[syn::Ident]) -> TokenStream {
+    let field_count = field_codes.len();
+
+    field_codes
+        .iter()
+        .zip(names)
+        .enumerate()
+        .map(|(idx, (code, name))| {
+            let with_formula = &code.with_formula;
+            let hint = &code.hint;
+            let last = field_count == idx + 1;
+            let add = quote::quote! {
+                let with_formula = #with_formula;
+                __total += with_formula.size_hint(#hint, #last)?;
+            };
+            match &code.skip_if {
+                None => add,
+                Some(skip_if) => quote::quote! { if !#skip_if(#name) { #add } },
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,118 @@
+//! Deserialization into `Box<dyn Any>`.
+//!
+//! Values are serialized with formula `(u32, F)`,
+//! where the first element is an id assigned to the type in [`AnyRegistry`].
+
+use core::any::Any;
+
+use alloc::{boxed::Box, collections::BTreeMap};
+
+use crate::{
+    deserialize::{deserialize, Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+};
+
+type DeserializeAnyFn = fn(&[u8]) -> Result<Box<dyn Any>, DeserializeError>;
+
+/// Registry of types that can be deserialized into `Box<dyn Any>`.
+///
+/// Each type is registered with a unique `u32` id.
+/// Value of registered type `T` with formula `F` is serialized
+/// with formula `(u32, F)` where the first element is the id.
+/// [`AnyRegistry::deserialize_any`] reads the id and deserializes the value
+/// of the type registered with it.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut registry = AnyRegistry::new();
+/// registry.register::<u32>(1).register::<String>(2);
+///
+/// let mut buffer = [0u8; 64];
+/// let (size, _) = serialize::<(u32, String), _>((2u32, "hello"), &mut buffer).unwrap();
+///
+/// let value = registry.deserialize_any(&buffer[..size]).unwrap();
+/// assert_eq!(value.downcast_ref::<String>().unwrap(), "hello");
+/// ```
+#[derive(Clone, Default)]
+pub struct AnyRegistry {
+    entries: BTreeMap<u32, DeserializeAnyFn>,
+}
+
+impl AnyRegistry {
+    /// Creates empty registry.
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        AnyRegistry {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Registers type `T` that is its own formula with specified id.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the id is already registered.
+    #[inline(always)]
+    pub fn register<T>(&mut self, id: u32) -> &mut Self
+    where
+        T: Formula + for<'de> Deserialize<'de, T> + 'static,
+    {
+        self.register_with_formula::<T, T>(id)
+    }
+
+    /// Registers type `T` deserialized with formula `F` with specified id.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the id is already registered.
+    #[inline]
+    pub fn register_with_formula<F, T>(&mut self, id: u32) -> &mut Self
+    where
+        F: Formula + ?Sized,
+        T: for<'de> Deserialize<'de, F> + 'static,
+    {
+        let old = self.entries.insert(id, deserialize_any_value::<F, T>);
+        assert!(old.is_none(), "Type id {id} is already registered");
+        self
+    }
+
+    /// Returns `true` if a type is registered with the id.
+    #[must_use]
+    #[inline(always)]
+    pub fn contains(&self, id: u32) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    /// Deserializes value prefixed with id of registered type.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::WrongVariant` if no type is registered
+    /// with the id.
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn deserialize_any(&self, input: &[u8]) -> Result<Box<dyn Any>, DeserializeError> {
+        let mut de = Deserializer::new(input.len(), input)?;
+        let id = de.read_value::<u32, u32>(false)?;
+
+        match self.entries.get(&id) {
+            None => Err(DeserializeError::WrongVariant {
+                got: id,
+                count: self.entries.len() as u32,
+            }),
+            Some(deserialize_any) => deserialize_any(input),
+        }
+    }
+}
+
+fn deserialize_any_value<F, T>(input: &[u8]) -> Result<Box<dyn Any>, DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: for<'de> Deserialize<'de, F> + 'static,
+{
+    let (_, value) = deserialize::<(u32, F), (u32, T)>(input)?;
+    Ok(Box::new(value))
+}
@@ -36,6 +36,12 @@ where
     }
 }
 
+/// Also makes `&[T; N]` serializable with `[F; N]` formula.
+///
+/// Generic code that can only prove `&'a T: Serialize<F>`
+/// for a particular lifetime may serialize `&'a [T; N]` with `[F]` formula.
+/// Implementing `Serialize<[F; N]>` for `&[T; N]` directly
+/// would overlap with this impl.
 impl<F, T, const N: usize> SerializeRef<[F; N]> for [T; N]
 where
     F: Formula,
@@ -109,6 +115,13 @@ where
         Ok(value)
     }
 
+    /// Deserializes each element in-place, reusing existing values.
+    /// For example `String` elements keep their allocations.
+    ///
+    /// If deserialization of an element fails, the error is returned
+    /// immediately. Elements before the failed one are already overwritten
+    /// while the failed element and elements after it may be left
+    /// in their previous or partially updated state.
     #[inline(always)]
     fn deserialize_in_place(&mut self, mut de: Deserializer<'de>) -> Result<(), DeserializeError> {
         self.iter_mut()
@@ -117,6 +130,48 @@ where
     }
 }
 
+impl<'de, F, T, const N: usize> Deserialize<'de, [F]> for [T; N]
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    /// Deserializes first `N` elements of the slice.
+    /// Remaining elements are ignored.
+    ///
+    /// Returns `DeserializeError::WrongLength` if slice
+    /// has fewer than `N` elements.
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let mut iter = de.into_unsized_iter::<F, T>();
+        let mut opts = [(); N].map(|_| None);
+        opts.iter_mut().try_for_each(|slot| match iter.next() {
+            None => Err(DeserializeError::WrongLength),
+            Some(elem) => {
+                *slot = Some(elem?);
+                Ok(())
+            }
+        })?;
+        let value = opts.map(Option::unwrap);
+        Ok(value)
+    }
+
+    /// Deserializes each element in-place from first `N` elements of the slice.
+    /// Remaining elements are ignored.
+    ///
+    /// Returns `DeserializeError::WrongLength` if slice
+    /// has fewer than `N` elements.
+    /// Elements before the failed one are already overwritten.
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let mut iter = de.into_unsized_iter::<F, T>();
+        self.iter_mut()
+            .try_for_each(|elem| match iter.next_in_place(elem) {
+                None => Err(DeserializeError::WrongLength),
+                Some(result) => result,
+            })
+    }
+}
+
 /// Returns the size of the serialized data if it can be determined fast.
 #[inline(always)]
 pub fn owned_array_fast_sizes<F, I, T>(iter: I) -> Option<Sizes>
@@ -133,11 +188,11 @@ where
 
 /// Returns the size of the serialized data if it can be determined fast.
 #[inline(always)]
-pub fn ref_array_fast_sizes<'a, F, I, T: 'a>(iter: I) -> Option<Sizes>
+pub fn ref_array_fast_sizes<'a, F, I, T>(iter: I) -> Option<Sizes>
 where
     F: Formula + ?Sized,
     I: Iterator<Item = &'a T>,
-    T: Serialize<F>,
+    T: Serialize<F> + 'a,
 {
     match (F::HEAPLESS, F::MAX_STACK_SIZE) {
         (true, Some(0)) => Some(Sizes::ZERO),
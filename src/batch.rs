@@ -0,0 +1,228 @@
+//! Serialization of multiple messages into one buffer.
+//!
+//! Messages are appended one after another.
+//! Addresses are absolute, so a message may reference heap payloads
+//! written by earlier messages of the same batch.
+//! [`BatchHeap`] uses this to store repeated payloads only once.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{
+    buffer::{Buffer, VecBuffer},
+    bytes::Bytes,
+    ctx::SerializeCtx,
+    deserialize::{deserialize_with_size, Deserialize, DeserializeError},
+    formula::{reference_size, BareFormula, Formula},
+    reference::Ref,
+    serialize::{write_ref, write_reference, Serialize, Sizes},
+};
+
+/// Context for messages serialized with [`BatchSerializer::push_ctx`].
+///
+/// Remembers heap payloads written in the batch.
+/// Serializing `&str` with `Ref<str>` or `&[u8]` with `Ref<Bytes>`
+/// writes the payload once and references it from all messages
+/// that contain the same bytes.
+pub struct BatchHeap {
+    interned: BTreeMap<Vec<u8>, usize>,
+}
+
+impl BatchHeap {
+    /// Returns number of distinct payloads written so far.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.interned.len()
+    }
+
+    /// Returns `true` if no payloads were written yet.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.interned.is_empty()
+    }
+
+    /// Writes reference to the payload,
+    /// writing payload to the heap only if it is not there yet.
+    fn write_interned<F, B>(
+        &mut self,
+        bytes: &[u8],
+        sizes: &mut Sizes,
+        mut buffer: B,
+    ) -> Result<(), B::Error>
+    where
+        F: BareFormula + ?Sized,
+        B: Buffer,
+    {
+        let address = match self.interned.get(bytes) {
+            Some(&address) => address,
+            None => {
+                write_ref::<Bytes, _, _>(bytes, sizes, buffer.reborrow())?;
+                self.interned.insert(bytes.to_vec(), sizes.heap);
+                sizes.heap
+            }
+        };
+
+        write_reference::<F, B>(bytes.len(), address, sizes.heap, sizes.stack, buffer)?;
+        sizes.stack += reference_size::<F>();
+        Ok(())
+    }
+}
+
+impl SerializeCtx<Ref<str>, BatchHeap> for &str {
+    #[inline(always)]
+    fn serialize_ctx<B>(
+        self,
+        ctx: &mut BatchHeap,
+        sizes: &mut Sizes,
+        buffer: B,
+    ) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        ctx.write_interned::<str, B>(self.as_bytes(), sizes, buffer)
+    }
+}
+
+impl SerializeCtx<Ref<Bytes>, BatchHeap> for &[u8] {
+    #[inline(always)]
+    fn serialize_ctx<B>(
+        self,
+        ctx: &mut BatchHeap,
+        sizes: &mut Sizes,
+        buffer: B,
+    ) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        ctx.write_interned::<Bytes, B>(self, sizes, buffer)
+    }
+}
+
+/// Builder for buffers with multiple messages.
+///
+/// Each push returns the end of the message in the batch and its stack size.
+/// A message is deserialized from the batch prefix that ends with it,
+/// see [`BatchSerializer::deserialize`].
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut batch = BatchSerializer::new();
+/// let a = batch.push_ctx::<Ref<str>, _>("repeated");
+/// let b = batch.push_ctx::<Ref<str>, _>("repeated");
+/// assert_eq!(batch.heap().len(), 1);
+///
+/// assert_eq!(batch.deserialize::<Ref<str>, &str>(a).unwrap(), "repeated");
+/// assert_eq!(batch.deserialize::<Ref<str>, &str>(b).unwrap(), "repeated");
+/// ```
+pub struct BatchSerializer {
+    buf: Vec<u8>,
+    heap: BatchHeap,
+}
+
+impl Default for BatchSerializer {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchSerializer {
+    /// Creates empty batch.
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        BatchSerializer {
+            buf: Vec::new(),
+            heap: BatchHeap {
+                interned: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Appends message to the batch.
+    /// Returns the end of the message in the batch and its stack size.
+    #[inline]
+    pub fn push<F, T>(&mut self, value: T) -> (usize, usize)
+    where
+        F: Formula + ?Sized,
+        T: Serialize<F>,
+    {
+        let mut sizes = Sizes {
+            heap: self.buf.len(),
+            stack: 0,
+        };
+        let stack = match write_ref::<F, T, _>(value, &mut sizes, VecBuffer::new(&mut self.buf)) {
+            Ok(stack) => stack,
+            Err(never) => match never {},
+        };
+        self.buf.truncate(sizes.heap);
+        (sizes.heap, stack)
+    }
+
+    /// Appends message to the batch,
+    /// sharing heap payloads with previous messages.
+    /// Returns the end of the message in the batch and its stack size.
+    #[inline]
+    pub fn push_ctx<F, T>(&mut self, value: T) -> (usize, usize)
+    where
+        F: Formula + ?Sized,
+        T: SerializeCtx<F, BatchHeap>,
+    {
+        let mut sizes = Sizes {
+            heap: self.buf.len(),
+            stack: 0,
+        };
+        let mut buffer = VecBuffer::new(&mut self.buf);
+        match value.serialize_ctx(&mut self.heap, &mut sizes, buffer.reborrow()) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+
+        // Same as `write_ref` without size hint.
+        let stack = sizes.to_heap(0);
+        buffer.move_to_heap(sizes.heap - stack, sizes.stack + stack, stack);
+        self.buf.truncate(sizes.heap);
+        (sizes.heap, stack)
+    }
+
+    /// Deserializes message returned by [`push`](BatchSerializer::push)
+    /// or [`push_ctx`](BatchSerializer::push_ctx).
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline(always)]
+    pub fn deserialize<'de, F, T>(&'de self, message: (usize, usize)) -> Result<T, DeserializeError>
+    where
+        F: Formula + ?Sized,
+        T: Deserialize<'de, F>,
+    {
+        let (end, stack) = message;
+        deserialize_with_size::<F, T>(&self.buf[..end], stack)
+    }
+
+    /// Returns context with payloads written so far.
+    #[must_use]
+    #[inline(always)]
+    pub fn heap(&self) -> &BatchHeap {
+        &self.heap
+    }
+
+    /// Returns bytes of all messages in the batch.
+    #[must_use]
+    #[inline(always)]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Consumes the batch and returns the owned buffer
+    /// with bytes of all messages.
+    #[must_use]
+    #[inline(always)]
+    pub fn into_vec(self) -> Vec<u8> {
+        self.buf
+    }
+}
@@ -0,0 +1,80 @@
+//! Serialization of `BinaryHeap`.
+//!
+//! Heap is serialized as a slice of its elements in arbitrary order.
+//! Deserialization rebuilds the heap from elements.
+//! Therefore order of elements in the underlying storage is not preserved,
+//! while the set of elements is.
+
+use alloc::collections::BinaryHeap;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+    iter::{
+        deserialize_extend_iter, deserialize_from_iter, owned_iter_fast_sizes, ref_iter_fast_sizes,
+    },
+    serialize::{write_slice, Serialize, SerializeRef, Sizes},
+};
+
+impl<F, T> Serialize<[F]> for BinaryHeap<T>
+where
+    F: Formula,
+    T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice(self.into_iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        ref_iter_fast_sizes::<F, _, _>(self.iter())
+    }
+}
+
+impl<F, T> SerializeRef<[F]> for BinaryHeap<T>
+where
+    F: Formula,
+    for<'ser> &'ser T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice(self.iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        owned_iter_fast_sizes::<F, _, _>(self.iter())
+    }
+}
+
+impl<'de, F, T> Deserialize<'de, [F]> for BinaryHeap<T>
+where
+    F: Formula,
+    T: Deserialize<'de, F> + Ord,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        deserialize_from_iter(de.into_unsized_iter())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        // Rebuilding heap at once is faster than pushing elements one by one.
+        let mut vec = core::mem::take(self).into_vec();
+        vec.clear();
+        let iter = de.into_unsized_iter();
+        let (lower, _) = Iterator::size_hint(&iter);
+        vec.reserve(lower);
+        let result = deserialize_extend_iter(&mut vec, iter);
+        *self = BinaryHeap::from(vec);
+        result
+    }
+}
@@ -54,6 +54,72 @@ pub trait Buffer {
         stack: usize,
         len: usize,
     ) -> Result<&mut [u8], Self::Error>;
+
+    /// Reserves `len` bytes on the stack to be filled later.
+    /// Caller should advance stack size by `len` bytes.
+    ///
+    /// Useful to write a header, e.g. length prefix,
+    /// that is known only after the rest of the value is written.
+    /// Reservation must be filled before the value is moved to heap.
+    ///
+    /// # Errors
+    ///
+    /// If buffer cannot add padding bytes, it should return `Err`.
+    #[inline(always)]
+    fn reserve_stack(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        len: usize,
+    ) -> Result<Reservation, Self::Error> {
+        self.pad_stack(heap, stack, len)?;
+        Ok(Reservation { heap, stack, len })
+    }
+}
+
+/// Reserved bytes on the stack.
+/// Created by [`Buffer::reserve_stack`] and filled with [`Reservation::fill`].
+#[must_use = "Reservation should be filled"]
+#[derive(Debug)]
+pub struct Reservation {
+    heap: usize,
+    stack: usize,
+    len: usize,
+}
+
+impl Reservation {
+    /// Returns number of reserved bytes.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no bytes are reserved.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Writes `bytes` into reserved space of the `buffer`.
+    /// Buffer must be the one that created the reservation.
+    ///
+    /// # Errors
+    ///
+    /// If buffer cannot write bytes, it should return `Err`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if length of `bytes` does not match reserved length.
+    #[inline(always)]
+    pub fn fill<B>(self, mut buffer: B, bytes: &[u8]) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        assert_eq!(bytes.len(), self.len, "Reserved length mismatch");
+        buffer.write_stack(self.heap, self.stack, bytes)
+    }
 }
 
 /// No-op buffer that does not write anything.
@@ -99,6 +165,87 @@ impl Buffer for DryBuffer {
     }
 }
 
+/// Buffer that does not write anything
+/// and only counts the number of bytes that would be written.
+///
+/// Unlike [`serialized_size`](crate::serialized_size) that relies on
+/// sizes tracked by `Serialize` implementation,
+/// this buffer measures the offsets actually touched by the writes.
+/// Useful to size values with manual `Serialize` implementations
+/// and to check that they report sizes correctly.
+///
+/// Pass `&mut CountingBuffer` as the buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountingBuffer {
+    len: usize,
+}
+
+impl CountingBuffer {
+    /// Returns new counting buffer.
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        CountingBuffer { len: 0 }
+    }
+
+    /// Returns number of bytes required to fit all writes so far.
+    #[must_use]
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if nothing was written.
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline(always)]
+    fn touch(&mut self, end: usize) {
+        self.len = self.len.max(end);
+    }
+}
+
+impl Buffer for &mut CountingBuffer {
+    type Error = Infallible;
+    type Reborrow<'a> = &'a mut CountingBuffer where Self: 'a;
+
+    #[inline(always)]
+    fn reborrow(&mut self) -> &'_ mut CountingBuffer {
+        self
+    }
+
+    #[inline(always)]
+    fn write_stack(&mut self, heap: usize, stack: usize, bytes: &[u8]) -> Result<(), Infallible> {
+        self.touch(heap + stack + bytes.len());
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn pad_stack(&mut self, heap: usize, stack: usize, len: usize) -> Result<(), Infallible> {
+        self.touch(heap + stack + len);
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn move_to_heap(&mut self, heap: usize, stack: usize, _len: usize) {
+        self.touch(heap + stack);
+    }
+
+    #[inline(always)]
+    fn reserve_heap(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], Infallible> {
+        self.touch(heap + stack + len);
+        Ok(&mut [])
+    }
+}
+
 /// Error that may occur during serialization,
 /// if buffer is too small to fit serialized data.
 ///
@@ -260,6 +407,18 @@ impl<'a> MaybeFixedBuffer<'a> {
     pub fn new(buf: &'a mut [u8], exhausted: &'a mut bool) -> Self {
         MaybeFixedBuffer { buf, exhausted }
     }
+
+    /// Returns `true` if serialized data did not fit into the slice.
+    pub fn is_exhausted(&self) -> bool {
+        *self.exhausted
+    }
+
+    /// Clears exhausted flag so that the buffer can be reused
+    /// for another serialization.
+    /// Offsets are passed to each write, so the buffer keeps no other state.
+    pub fn reset(&mut self) {
+        *self.exhausted = false;
+    }
 }
 
 impl<'a> Buffer for MaybeFixedBuffer<'a> {
@@ -350,6 +509,13 @@ impl<'a> VecBuffer<'a> {
     pub fn new(buf: &'a mut Vec<u8>) -> Self {
         VecBuffer { buf }
     }
+
+    /// Clears the vector keeping its capacity,
+    /// so that the buffer can be reused for another serialization
+    /// without reallocation.
+    pub fn reset(&mut self) {
+        self.buf.clear();
+    }
 }
 
 #[cfg(feature = "alloc")]
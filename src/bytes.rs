@@ -7,6 +7,7 @@ use crate::{
 
 /// A formula for a raw byte slices.
 /// Serializable from anything that implements `AsRef<[u8]>`.
+/// Deserializable into `&[u8]` and into `&str` with UTF-8 validation.
 pub struct Bytes;
 
 impl Formula for Bytes {
@@ -44,3 +45,19 @@ impl<'de, 'fe: 'de> Deserialize<'fe, Bytes> for &'de [u8] {
         Ok(())
     }
 }
+
+impl<'de, 'fe: 'de> Deserialize<'fe, Bytes> for &'de str {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        match core::str::from_utf8(de.read_all_bytes()) {
+            Ok(s) => Ok(s),
+            Err(error) => Err(DeserializeError::NonUtf8(error)),
+        }
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<'fe, Bytes>>::deserialize(de)?;
+        Ok(())
+    }
+}
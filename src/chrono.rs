@@ -0,0 +1,101 @@
+//! Serialization of `chrono` date-times.
+//!
+//! `NaiveDateTime` and `DateTime<Utc>` are serialized as seconds since
+//! Unix epoch in `i64` followed by sub-second nanoseconds in `u32`,
+//! both in little-endian.
+//! Leap seconds are kept as nanoseconds above one second.
+//!
+//! Deserialization fails with `DeserializeError::Incompatible`
+//! if the value is out of range for `chrono`.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+};
+
+#[inline(always)]
+fn datetime_to_bytes(datetime: &DateTime<Utc>) -> [u8; 12] {
+    let mut bytes = [0; 12];
+    bytes[..8].copy_from_slice(&datetime.timestamp().to_le_bytes());
+    bytes[8..].copy_from_slice(&datetime.timestamp_subsec_nanos().to_le_bytes());
+    bytes
+}
+
+#[inline(always)]
+fn read_datetime(mut de: Deserializer) -> Result<DateTime<Utc>, DeserializeError> {
+    let bytes = de.read_byte_array::<12>()?;
+    let mut secs = [0; 8];
+    secs.copy_from_slice(&bytes[..8]);
+    let mut nanos = [0; 4];
+    nanos.copy_from_slice(&bytes[8..]);
+
+    DateTime::from_timestamp(i64::from_le_bytes(secs), u32::from_le_bytes(nanos))
+        .ok_or(DeserializeError::Incompatible)
+}
+
+macro_rules! impl_datetime {
+    ($ty:ty, |$to:ident| $to_utc:expr, |$from:ident| $from_utc:expr) => {
+        impl Formula for $ty {
+            const MAX_STACK_SIZE: Option<usize> = Some(12);
+            const EXACT_SIZE: bool = true;
+            const HEAPLESS: bool = true;
+        }
+
+        impl BareFormula for $ty {}
+
+        impl Serialize<$ty> for $ty {
+            #[inline(always)]
+            fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                let $to = &self;
+                write_bytes(&datetime_to_bytes(&$to_utc), sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                Some(Sizes::with_stack(12))
+            }
+        }
+
+        impl SerializeRef<$ty> for $ty {
+            #[inline(always)]
+            fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                let $to = self;
+                write_bytes(&datetime_to_bytes(&$to_utc), sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                Some(Sizes::with_stack(12))
+            }
+        }
+
+        impl Deserialize<'_, $ty> for $ty {
+            #[inline(always)]
+            fn deserialize(de: Deserializer) -> Result<Self, DeserializeError> {
+                let $from = read_datetime(de)?;
+                Ok($from_utc)
+            }
+
+            #[inline(always)]
+            fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+                let $from = read_datetime(de)?;
+                *self = $from_utc;
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_datetime!(NaiveDateTime, |naive| naive.and_utc(), |utc| utc
+    .naive_utc());
+impl_datetime!(DateTime<Utc>, |utc| *utc, |utc| utc);
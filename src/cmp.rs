@@ -0,0 +1,140 @@
+use core::cmp::{Ordering, Reverse};
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+};
+
+impl<T, F> Serialize<F> for Reverse<T>
+where
+    F: BareFormula + ?Sized,
+    T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <T as Serialize<F>>::serialize(self.0, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <T as Serialize<F>>::size_hint(&self.0)
+    }
+}
+
+impl<T, F> SerializeRef<F> for Reverse<T>
+where
+    F: BareFormula + ?Sized,
+    T: SerializeRef<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <T as SerializeRef<F>>::serialize(&self.0, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <T as SerializeRef<F>>::size_hint(&self.0)
+    }
+}
+
+impl<'de, T, F> Deserialize<'de, F> for Reverse<T>
+where
+    F: BareFormula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn deserialize(deserializer: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        Ok(Reverse(<T as Deserialize<F>>::deserialize(deserializer)?))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(
+        &mut self,
+        deserializer: Deserializer<'de>,
+    ) -> Result<(), DeserializeError> {
+        <T as Deserialize<F>>::deserialize_in_place(&mut self.0, deserializer)
+    }
+}
+
+/// `Ordering` is serialized as a single byte tag:
+/// `0` for `Less`, `1` for `Equal` and `2` for `Greater`.
+impl Formula for Ordering {
+    const MAX_STACK_SIZE: Option<usize> = Some(1);
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = true;
+}
+
+impl BareFormula for Ordering {}
+
+#[inline(always)]
+const fn ordering_tag(ordering: Ordering) -> u8 {
+    match ordering {
+        Ordering::Less => 0,
+        Ordering::Equal => 1,
+        Ordering::Greater => 2,
+    }
+}
+
+#[inline(always)]
+fn read_ordering(de: &mut Deserializer) -> Result<Ordering, DeserializeError> {
+    match de.read_byte()? {
+        0 => Ok(Ordering::Less),
+        1 => Ok(Ordering::Equal),
+        2 => Ok(Ordering::Greater),
+        tag => Err(DeserializeError::WrongVariant {
+            got: u32::from(tag),
+            count: 3,
+        }),
+    }
+}
+
+impl Serialize<Ordering> for Ordering {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&[ordering_tag(self)], sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(1))
+    }
+}
+
+impl SerializeRef<Ordering> for Ordering {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&[ordering_tag(*self)], sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(1))
+    }
+}
+
+impl Deserialize<'_, Ordering> for Ordering {
+    #[inline(always)]
+    fn deserialize(mut de: Deserializer) -> Result<Self, DeserializeError> {
+        read_ordering(&mut de)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, mut de: Deserializer) -> Result<(), DeserializeError> {
+        *self = read_ordering(&mut de)?;
+        Ok(())
+    }
+}
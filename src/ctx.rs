@@ -0,0 +1,104 @@
+//! Serialization and deserialization with user-provided context.
+//!
+//! [`Serialize`](crate::Serialize) and [`Deserialize`](crate::Deserialize) are context-free.
+//! Types that need shared state, like string interners, registries
+//! or reference clocks, implement [`SerializeCtx`] and [`DeserializeCtx`]
+//! and are serialized with [`serialize_with_ctx`]
+//! and deserialized with [`deserialize_with_ctx`].
+
+use crate::{
+    buffer::{Buffer, BufferExhausted, CheckedFixedBuffer},
+    deserialize::{assert_sized_or_heapless, DeserializeError, Deserializer},
+    formula::Formula,
+    serialize::Sizes,
+};
+
+/// Trait for types that can be serialized with formula `F`
+/// using mutable context `C`.
+///
+/// Context-aware types write their fields with the same functions as
+/// [`Serialize`](crate::Serialize) implementations and pass context to nested
+/// context-aware values explicitly.
+pub trait SerializeCtx<F: Formula + ?Sized, C: ?Sized> {
+    /// Serializes `self` into the given buffer using context.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if buffer write fails.
+    fn serialize_ctx<B>(self, ctx: &mut C, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        Self: Sized,
+        B: Buffer;
+}
+
+/// Trait for types that can be deserialized from formula `F`
+/// using mutable context `C`.
+pub trait DeserializeCtx<'de, F: Formula + ?Sized, C: ?Sized> {
+    /// Deserializes value using context.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    fn deserialize_ctx(
+        ctx: &mut C,
+        deserializer: Deserializer<'de>,
+    ) -> Result<Self, DeserializeError>
+    where
+        Self: Sized;
+}
+
+/// Serialize value into bytes slice using context.
+/// Returns the number of bytes written.
+/// Fails if the buffer is too small.
+///
+/// # Errors
+///
+/// Returns [`BufferExhausted`] if the buffer is too small.
+#[inline(always)]
+pub fn serialize_with_ctx<F, T, C>(
+    value: T,
+    output: &mut [u8],
+    ctx: &mut C,
+) -> Result<(usize, usize), BufferExhausted>
+where
+    F: Formula + ?Sized,
+    T: SerializeCtx<F, C>,
+    C: ?Sized,
+{
+    let mut buffer = CheckedFixedBuffer::new(output);
+    let mut sizes = Sizes { heap: 0, stack: 0 };
+    value.serialize_ctx(ctx, &mut sizes, buffer.reborrow())?;
+
+    // Same as `write_ref` without size hint.
+    let len = sizes.to_heap(0);
+    buffer.move_to_heap(sizes.heap - len, sizes.stack + len, len);
+    Ok((sizes.heap, len))
+}
+
+/// Deserializes value from the input using context.
+/// The value must occupy the whole input slice.
+/// The value must be either sized or heap-less.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[inline(always)]
+pub fn deserialize_with_ctx<'de, F, T, C>(
+    input: &'de [u8],
+    ctx: &mut C,
+) -> Result<T, DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: DeserializeCtx<'de, F, C>,
+    C: ?Sized,
+{
+    assert_sized_or_heapless::<F>();
+
+    let stack = match F::MAX_STACK_SIZE {
+        None => input.len(),
+        Some(max_stack) => max_stack.min(input.len()),
+    };
+
+    let de = Deserializer::new_unchecked(stack, input);
+    <T as DeserializeCtx<'de, F, C>>::deserialize_ctx(ctx, de)
+}
@@ -0,0 +1,98 @@
+use alloc::{collections::BTreeMap, rc::Rc};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError},
+    formula::BareFormula,
+    lazy::Lazy,
+};
+
+/// Cache of deserialized reference targets.
+///
+/// Serialized data may contain several references to the same address,
+/// e.g. when built with [`write_reference`](crate::advanced::write_reference)
+/// to share nodes of a graph.
+/// Deserializing such references into owned values duplicates both
+/// work and allocations.
+///
+/// Deserialize reference fields into [`Lazy`] and resolve them
+/// with [`RefDedup::get`].
+/// Values at the same address are deserialized once
+/// and shared through [`Rc`].
+///
+/// Positions are only meaningful within a single input buffer,
+/// so use a fresh cache for each deserialized buffer.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 64];
+/// let (size, _) = serialize::<(Ref<str>, Ref<str>), _>(("a", "b"), &mut buffer).unwrap();
+///
+/// let (a, b) =
+///     deserialize::<(Ref<str>, Ref<str>), (Lazy<str>, Lazy<str>)>(&buffer[..size]).unwrap();
+///
+/// let mut dedup = RefDedup::<String>::new();
+/// assert_eq!(*dedup.get(&a).unwrap(), "a");
+/// assert_eq!(*dedup.get(&b).unwrap(), "b");
+/// assert_eq!(dedup.len(), 2);
+/// ```
+pub struct RefDedup<T> {
+    cache: BTreeMap<(usize, usize), Rc<T>>,
+}
+
+impl<T> Default for RefDedup<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> RefDedup<T> {
+    /// Creates empty cache.
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        RefDedup {
+            cache: BTreeMap::new(),
+        }
+    }
+
+    /// Returns number of cached values.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if no values are cached.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns shared value deserialized from the lazy value.
+    /// Value is deserialized only if value at the same position
+    /// was not deserialized with this cache before.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    /// Failed values are not cached.
+    #[inline]
+    pub fn get<'de, F>(&mut self, lazy: &Lazy<'de, F>) -> Result<Rc<T>, DeserializeError>
+    where
+        F: BareFormula + ?Sized,
+        T: Deserialize<'de, F>,
+    {
+        let position = lazy.position();
+        if let Some(value) = self.cache.get(&position) {
+            return Ok(value.clone());
+        }
+
+        let value = Rc::new(lazy.get::<T>()?);
+        self.cache.insert(position, value.clone());
+        Ok(value)
+    }
+}
@@ -1,8 +1,10 @@
 use core::{any::type_name, iter::FusedIterator, marker::PhantomData, str::Utf8Error};
 
 use crate::{
-    formula::{reference_size, unwrap_size, Formula},
+    formula::{reference_size, unwrap_size, BareFormula, Formula},
+    reference::Ref,
     size::{deserialize_usize, FixedIsizeType, FixedUsizeType, SIZE_STACK},
+    vlq::read_vlq,
 };
 
 #[inline(never)]
@@ -31,7 +33,13 @@ pub enum DeserializeError {
     InvalidIsize(FixedIsizeType),
 
     /// Enum variant is invalid.
-    WrongVariant(u32),
+    WrongVariant {
+        /// Variant tag read from the input.
+        got: u32,
+
+        /// Number of variants expected by the formula.
+        count: u32,
+    },
 
     /// Bytes slice is not UTF8 where `str` is expected.
     NonUtf8(Utf8Error),
@@ -87,6 +95,7 @@ pub struct Deserializer<'de> {
     /// Input buffer sub-slice usable for deserialization.
     input: &'de [u8],
     stack: usize,
+    max_elements: usize,
 }
 
 impl<'de> Deserializer<'de> {
@@ -108,7 +117,139 @@ impl<'de> Deserializer<'de> {
     #[inline(always)]
     pub const fn new_unchecked(stack: usize, input: &'de [u8]) -> Self {
         debug_assert!(stack <= input.len());
-        Deserializer { input, stack }
+        Deserializer {
+            input,
+            stack,
+            max_elements: usize::MAX,
+        }
+    }
+
+    /// Limits number of elements in sequences
+    /// read by this deserializer and deserializers derived from it.
+    ///
+    /// Sequences of sized formulas have length known
+    /// before elements are read and are checked before any element is read,
+    /// so that untrusted length can't trigger huge allocation.
+    /// Iterator over such sequence exceeding the limit yields single
+    /// [`DeserializeError::Incompatible`] error.
+    ///
+    /// Sequences of unsized formulas are checked as elements are read.
+    /// Iterator over such sequence yields up to the limit elements
+    /// and then [`DeserializeError::Incompatible`] error
+    /// if more elements remain.
+    #[inline(always)]
+    pub const fn with_max_elements(mut self, max_elements: usize) -> Self {
+        self.max_elements = max_elements;
+        self
+    }
+
+    /// Returns offset of the unread part of the value's stack
+    /// from the start of the input buffer.
+    ///
+    /// Fields are read from the end of the stack,
+    /// so the deserializer passed to [`Deserialize::deserialize`]
+    /// reports offset of the value being deserialized,
+    /// and before the last field is read it reports offset of that field.
+    /// Use it to report where deserialization failed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::{*, advanced::*};
+    /// # use core::cmp::Ordering;
+    /// let mut buffer = [0u8; 16];
+    /// let (size, stack) = serialize::<(u32, (Ordering, u32)), _>((1u32, (Ordering::Equal, 2u32)), &mut buffer).unwrap();
+    /// // Tuple elements are laid out from the end.
+    /// assert_eq!(buffer[4], 1);
+    /// buffer[4] = 3; // Invalid `Ordering`.
+    ///
+    /// let mut de = Deserializer::new(stack, &buffer[..size]).unwrap();
+    /// assert_eq!(de.read_value::<u32, u32>(false).unwrap(), 1);
+    /// let offset = de.offset();
+    /// assert!(de.read_value::<(Ordering, u32), (Ordering, u32)>(true).is_err());
+    /// assert_eq!(offset, 0);
+    /// ```
+    #[must_use]
+    #[inline(always)]
+    pub const fn offset(&self) -> usize {
+        self.input.len() - self.stack
+    }
+
+    /// Creates deserializer for part of the input,
+    /// keeping configuration of this one.
+    #[inline(always)]
+    const fn derive(&self, stack: usize, input: &'de [u8]) -> Self {
+        debug_assert!(stack <= input.len());
+        Deserializer {
+            input,
+            stack,
+            max_elements: self.max_elements,
+        }
+    }
+
+    #[inline(always)]
+    fn iter<F, T, M>(self, upper: usize) -> DeIter<'de, F, T, M>
+    where
+        F: Formula + ?Sized,
+    {
+        assert!(self.stack <= self.input.len());
+        let exceeds = F::MAX_STACK_SIZE.is_some() && upper > self.max_elements;
+        let limited = F::MAX_STACK_SIZE.is_none() && upper > self.max_elements;
+        let max_elements = self.max_elements;
+        let mut iter = DeIter {
+            de: self,
+            marker: PhantomData,
+            upper: if exceeds { 0 } else { upper.min(max_elements) },
+            limited,
+            error: if exceeds {
+                Some(DeserializeError::Incompatible)
+            } else {
+                None
+            },
+        };
+        iter.check_limit();
+        iter
+    }
+
+    /// Creates a copy of this deserializer at the same position.
+    ///
+    /// Reads from the fork do not advance the original deserializer.
+    /// Useful for speculative reads, when parsing may need to backtrack.
+    #[inline(always)]
+    pub fn fork(&self) -> Deserializer<'de> {
+        self.clone()
+    }
+
+    /// Attempts to read using provided closure on a fork of this deserializer.
+    /// If closure succeeds, this deserializer is advanced to the fork's position.
+    /// Otherwise this deserializer is left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns error returned by the closure.
+    #[inline(always)]
+    pub fn try_read<F, T>(&mut self, f: F) -> Result<T, DeserializeError>
+    where
+        F: FnOnce(&mut Deserializer<'de>) -> Result<T, DeserializeError>,
+    {
+        let mut fork = self.fork();
+        let value = f(&mut fork)?;
+        *self = fork;
+        Ok(value)
+    }
+
+    /// Returns `true` if the whole stack is read.
+    #[inline(always)]
+    pub(crate) const fn is_consumed(&self) -> bool {
+        self.stack == 0
+    }
+
+    /// Returns memory address of the end of the value and its stack size.
+    /// Identifies the value within input buffer.
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    pub(crate) fn position(&self) -> (usize, usize) {
+        (self.input.as_ptr() as usize + self.input.len(), self.stack)
     }
 
     #[inline(always)]
@@ -117,7 +258,7 @@ impl<'de> Deserializer<'de> {
             return cold_err(DeserializeError::WrongLength);
         }
 
-        let sub = Deserializer::new_unchecked(stack, self.input);
+        let sub = self.derive(stack, self.input);
 
         self.stack -= stack;
         let end = self.input.len() - stack;
@@ -188,6 +329,25 @@ impl<'de> Deserializer<'de> {
         Ok(array)
     }
 
+    /// Reads specified number of bytes from the input buffer.
+    /// Returns reference to array of bytes without copying.
+    /// Advances the input buffer.
+    ///
+    /// Prefer over [`read_byte_array`](Deserializer::read_byte_array)
+    /// for large arrays, e.g. hashes and keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if not enough bytes on stack.
+    #[inline(always)]
+    pub fn read_fixed_bytes<const N: usize>(&mut self) -> Result<&'de [u8; N], DeserializeError> {
+        let bytes = self.read_bytes(N)?;
+        match bytes.try_into() {
+            Ok(array) => Ok(array),
+            Err(_) => unreachable!("Exactly N bytes are read"),
+        }
+    }
+
     /// Reads the rest of the input buffer as bytes.
     #[must_use]
     #[inline(always)]
@@ -199,6 +359,8 @@ impl<'de> Deserializer<'de> {
     /// Reads and deserializes usize from the input buffer.
     /// Advances the input buffer.
     ///
+    /// To read sequence lengths use [`Deserializer::read_len`].
+    ///
     /// # Errors
     ///
     /// Returns `DeserializeError` if deserialization fails.
@@ -207,6 +369,178 @@ impl<'de> Deserializer<'de> {
         deserialize_usize(self.sub(SIZE_STACK)?)
     }
 
+    /// Reads a sequence length in the crate's canonical encoding.
+    /// Advances the input buffer.
+    ///
+    /// Lengths are element counts and stack sizes of values,
+    /// as opposed to addresses that are read when dereferencing.
+    /// Use this method in manual implementations of containers
+    /// to read lengths written as `usize`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::{*, advanced::*};
+    /// // Formula for sequence of `u32` with explicit element count.
+    /// struct StackFormula;
+    ///
+    /// impl Formula for StackFormula {
+    ///     const MAX_STACK_SIZE: Option<usize> = None;
+    ///     const EXACT_SIZE: bool = false;
+    ///     const HEAPLESS: bool = true;
+    /// }
+    ///
+    /// impl BareFormula for StackFormula {}
+    ///
+    /// struct Stack(Vec<u32>);
+    ///
+    /// impl Serialize<StackFormula> for &Stack {
+    ///     fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    ///     where
+    ///         B: Buffer,
+    ///     {
+    ///         write_exact_size_field::<usize, _, _>(self.0.len(), sizes, buffer.reborrow())?;
+    ///         for &elem in &self.0 {
+    ///             write_exact_size_field::<u32, _, _>(elem, sizes, buffer.reborrow())?;
+    ///         }
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn size_hint(&self) -> Option<Sizes> {
+    ///         Some(Sizes::with_stack(size_of::<FixedUsizeType>() + 4 * self.0.len()))
+    ///     }
+    /// }
+    ///
+    /// impl Deserialize<'_, StackFormula> for Stack {
+    ///     fn deserialize(mut de: Deserializer) -> Result<Self, DeserializeError> {
+    ///         let len = de.read_len()?;
+    ///         let mut elems = Vec::new();
+    ///         for _ in 0..len {
+    ///             elems.push(de.read_value::<u32, u32>(false)?);
+    ///         }
+    ///         Ok(Stack(elems))
+    ///     }
+    ///
+    ///     fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+    ///         *self = <Self as Deserialize<StackFormula>>::deserialize(de)?;
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// # use core::mem::size_of;
+    /// let mut buffer = [0u8; 64];
+    /// let (size, _) = serialize::<StackFormula, _>(&Stack(vec![1, 2, 3]), &mut buffer).unwrap();
+    /// let stack = deserialize::<StackFormula, Stack>(&buffer[..size]).unwrap();
+    /// assert_eq!(stack.0, [1, 2, 3]);
+    /// ```
+    #[inline(always)]
+    pub fn read_len(&mut self) -> Result<usize, DeserializeError> {
+        deserialize_usize(self.sub(SIZE_STACK)?)
+    }
+
+    /// Reads usize encoded with `N` bytes from the input buffer.
+    /// Advances the input buffer.
+    ///
+    /// Allows reading size values written with a different `fixedN` feature
+    /// than the one enabled for this build.
+    /// Values written with fewer bytes are zero-extended.
+    /// Values written with more bytes are accepted as long as they
+    /// fit into [`FixedUsizeType`] and `usize`.
+    /// Byte order is the same as for [`FixedUsizeType`] values.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::IntegerOverflow` if value does not fit.
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn read_fixed_usize<const N: usize>(&mut self) -> Result<usize, DeserializeError> {
+        let mut bytes = self.read_byte_array::<N>()?;
+        if cfg!(feature = "big-endian-sizes") {
+            bytes.reverse();
+        }
+
+        let mut value: u128 = 0;
+        for (idx, &byte) in bytes.iter().enumerate() {
+            if byte == 0 {
+                continue;
+            }
+            if idx >= 16 {
+                return cold_err(DeserializeError::IntegerOverflow);
+            }
+            value |= u128::from(byte) << (idx * 8);
+        }
+
+        match FixedUsizeType::try_from(value) {
+            Ok(value) => match usize::try_from(value) {
+                Ok(value) => Ok(value),
+                Err(_) => cold_err(DeserializeError::IntegerOverflow),
+            },
+            Err(_) => cold_err(DeserializeError::IntegerOverflow),
+        }
+    }
+
+    /// Reads UTF-8 string prefixed with [`Vlq`](crate::Vlq) encoded length.
+    /// Advances the input buffer.
+    ///
+    /// Reads values of [`VlqStr`](crate::VlqStr) formula.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::NonUtf8` if string is not valid UTF-8.
+    /// Returns `DeserializeError::IntegerOverflow` if length does not fit `usize`.
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn read_vlq_str(&mut self) -> Result<&'de str, DeserializeError> {
+        let len: usize = read_vlq(self)?;
+        let bytes = self.read_bytes(len)?;
+        match core::str::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            Err(error) => cold_err(DeserializeError::NonUtf8(error)),
+        }
+    }
+
+    /// Reads and deserializes usize from the input buffer
+    /// and checks that it does not exceed `max`.
+    /// Advances the input buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::Incompatible` if value is greater than `max`.
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline(always)]
+    pub fn read_bounded_usize(&mut self, max: usize) -> Result<usize, DeserializeError> {
+        let value = self.read_usize()?;
+        if value > max {
+            return cold_err(DeserializeError::Incompatible);
+        }
+        Ok(value)
+    }
+
+    /// Reads and deserializes value encoded with [`Vlq`] formula.
+    /// Advances the input buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::IntegerOverflow` if value does not fit into `T`.
+    /// Returns `DeserializeError` if deserialization fails.
+    ///
+    /// [`Vlq`]: crate::Vlq
+    #[inline(always)]
+    pub fn read_vlq<T>(&mut self) -> Result<T, DeserializeError>
+    where
+        T: TryFrom<u128>,
+    {
+        let value: u128 = read_vlq(self)?;
+        match T::try_from(value) {
+            Ok(value) => Ok(value),
+            Err(_) => cold_err(DeserializeError::IntegerOverflow),
+        }
+    }
+
     /// Reads and deserializes field from the input buffer.
     /// Advances the input buffer.
     ///
@@ -220,7 +554,7 @@ impl<'de> Deserializer<'de> {
         T: Deserialize<'de, F>,
     {
         let stack = match (F::MAX_STACK_SIZE, F::EXACT_SIZE, last) {
-            (None, _, false) => self.read_usize()?,
+            (None, _, false) => self.read_len()?,
             (None, _, true) => self.stack,
             (Some(max_stack), false, true) => max_stack.min(self.stack),
             (Some(max_stack), _, _) => max_stack,
@@ -251,8 +585,7 @@ impl<'de> Deserializer<'de> {
         let input_back = &self.input[..self.input.len() - self.stack + stack];
         self.stack -= stack;
 
-        let sub = Deserializer::new_unchecked(stack, input_back);
-        <T as Deserialize<'de, F>>::deserialize(sub)
+        <T as Deserialize<'de, F>>::deserialize(self.derive(stack, input_back))
     }
 
     /// Reads and deserializes field from the input buffer in-place.
@@ -269,7 +602,7 @@ impl<'de> Deserializer<'de> {
         let stack = match (last, F::MAX_STACK_SIZE) {
             (true, _) => self.stack,
             (false, Some(max_stack)) => max_stack,
-            (false, None) => self.read_usize()?,
+            (false, None) => self.read_len()?,
         };
 
         <T as Deserialize<'de, F>>::deserialize_in_place(place, self.sub(stack)?)
@@ -292,7 +625,7 @@ impl<'de> Deserializer<'de> {
         }
 
         let (head, tail) = self.input.split_at(self.input.len() - reference_size);
-        let (address, size) = read_reference::<F>(tail, head.len());
+        let (address, size) = read_reference::<F>(tail, head.len())?;
 
         if address > head.len() {
             return Err(DeserializeError::WrongAddress);
@@ -300,7 +633,80 @@ impl<'de> Deserializer<'de> {
 
         let input = &head[..address];
 
-        Deserializer::new(size, input)
+        if size > input.len() {
+            return cold_err(DeserializeError::OutOfBounds);
+        }
+        Ok(self.derive(size, input))
+    }
+
+    /// Reads reference to a value of formula `F` from the input buffer
+    /// and returns serialized bytes of the referenced value.
+    /// Advances the input buffer.
+    ///
+    /// Counterpart of [`write_raw_reference`](crate::advanced::write_raw_reference).
+    ///
+    /// Use [`read_reference_raw`](Deserializer::read_reference_raw)
+    /// to get address and size without following the reference.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if reference is out of bounds
+    /// or has address larger that self.
+    #[inline]
+    pub fn read_raw_reference<F>(&mut self) -> Result<&'de [u8], DeserializeError>
+    where
+        F: Formula + ?Sized,
+    {
+        let de = self.sub(reference_size::<F>())?.deref::<F>()?;
+        Ok(de.read_all_bytes())
+    }
+
+    /// Reads reference to a value of formula `F` from the input buffer
+    /// and returns its address and size without following it.
+    /// Advances the input buffer.
+    ///
+    /// Address points to the end of referenced value,
+    /// so the value occupies `address - size..address` bytes of the input.
+    /// Neither is checked against input bounds,
+    /// which allows tools to inspect malformed data.
+    ///
+    /// Use [`read_raw_reference`](Deserializer::read_raw_reference)
+    /// to get bytes of the referenced value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if not enough bytes on stack
+    /// or address or size does not fit `usize`.
+    #[inline]
+    pub fn read_reference_raw<F>(&mut self) -> Result<(usize, usize), DeserializeError>
+    where
+        F: Formula + ?Sized,
+    {
+        let reference = self.read_bytes(reference_size::<F>())?;
+        read_reference::<F>(reference, self.input.len())
+    }
+
+    /// Converts deserializer into iterator over values
+    /// referenced by elements of `[Ref<F>]` slice.
+    /// Each reference is followed and the pointee is deserialized.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::{*, advanced::*};
+    /// let mut buffer = [0u8; 64];
+    /// let (size, stack) = serialize::<[Ref<u64>], _>([1u64, 2], &mut buffer).unwrap();
+    /// let de = Deserializer::new(stack, &buffer[..size]).unwrap();
+    /// let values = de.deref_iter::<u64, u64>().collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(values, [1, 2]);
+    /// ```
+    #[inline(always)]
+    pub fn deref_iter<F, T>(self) -> DeIter<'de, Ref<F>, T>
+    where
+        F: BareFormula + ?Sized,
+        T: Deserialize<'de, F>,
+    {
+        self.into_unsized_iter::<Ref<F>, T>()
     }
 
     /// Converts deserializer into iterator over deserialized values with
@@ -318,16 +724,11 @@ impl<'de> Deserializer<'de> {
     {
         let upper = match F::MAX_STACK_SIZE {
             None => panic!("Formula must be sized"),
-            Some(0) => self.read_usize().unwrap_or(0),
+            Some(0) => self.read_len().unwrap_or(0),
             Some(max_stack) => self.stack / max_stack,
         };
 
-        assert!(self.stack <= self.input.len());
-        DeIter {
-            de: self,
-            marker: PhantomData,
-            upper,
-        }
+        self.iter(upper)
     }
 
     /// Converts deserializer into iterator over deserialized values with
@@ -341,16 +742,11 @@ impl<'de> Deserializer<'de> {
     {
         let upper = match F::MAX_STACK_SIZE {
             None => self.stack / SIZE_STACK,
-            Some(0) => self.read_usize().unwrap_or(0),
+            Some(0) => self.read_len().unwrap_or(0),
             Some(max_stack) => self.stack / max_stack,
         };
 
-        assert!(self.stack <= self.input.len());
-        DeIter {
-            de: self,
-            marker: PhantomData,
-            upper,
-        }
+        self.iter(upper)
     }
 
     /// Converts deserializer into iterator over deserialized values with
@@ -368,12 +764,7 @@ impl<'de> Deserializer<'de> {
     {
         assert!(F::MAX_STACK_SIZE.is_some(), "Formula must be sized");
 
-        assert!(self.stack <= self.input.len());
-        DeIter {
-            de: self,
-            marker: PhantomData,
-            upper: len,
-        }
+        self.iter(len)
     }
 
     /// Converts deserializer into iterator over deserialized values with
@@ -385,12 +776,30 @@ impl<'de> Deserializer<'de> {
         F: Formula + ?Sized,
         T: Deserialize<'de, F>,
     {
-        assert!(self.stack <= self.input.len());
-        DeIter {
-            de: self,
-            marker: PhantomData,
-            upper: len,
-        }
+        self.iter(len)
+    }
+
+    /// Deserializes sequence of values with specified formula
+    /// and passes each value to the closure.
+    ///
+    /// Allocation-free alternative to collecting values into a container.
+    /// Stops at the first error.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization of a value fails
+    /// or closure returns an error.
+    #[inline]
+    pub fn for_each<F, T>(
+        self,
+        mut f: impl FnMut(T) -> Result<(), DeserializeError>,
+    ) -> Result<(), DeserializeError>
+    where
+        F: Formula + ?Sized,
+        T: Deserialize<'de, F>,
+    {
+        self.into_unsized_iter::<F, T>()
+            .try_for_each(|value| f(value?))
     }
 
     // /// Finishing check for deserializer.
@@ -416,7 +825,7 @@ impl<'de> Deserializer<'de> {
         match F::MAX_STACK_SIZE {
             None => {
                 for _ in 0..n {
-                    let skip_bytes = self.read_usize()?;
+                    let skip_bytes = self.read_len()?;
                     self.read_bytes(skip_bytes)?;
                 }
             }
@@ -439,6 +848,8 @@ pub type SizedDeIter<'de, F, T> = DeIter<'de, F, T, IterSized>;
 pub struct DeIter<'de, F: ?Sized, T, M = IterMaybeUnsized> {
     de: Deserializer<'de>,
     upper: usize,
+    limited: bool,
+    error: Option<DeserializeError>,
     marker: PhantomData<fn(&F, M) -> T>,
 }
 
@@ -451,9 +862,32 @@ where
     #[must_use]
     #[inline(always)]
     pub fn is_empty(&self) -> bool {
-        self.upper == 0 || self.stack_empty()
+        self.error.is_none() && (self.upper == 0 || self.stack_empty())
     }
 
+    /// Deserializes next element in-place.
+    /// Returns `None` if no items remains in the iterator.
+    ///
+    /// Allows reusing existing values, for example keeping allocations.
+    #[inline(always)]
+    pub fn next_in_place(&mut self, place: &mut T) -> Option<Result<(), DeserializeError>> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+        if self.is_empty() {
+            return None;
+        }
+        let result = self.de.read_in_place::<F, T>(place, false);
+        self.upper -= 1;
+        self.check_limit();
+        Some(result)
+    }
+}
+
+impl<'de, F, T, M> DeIter<'de, F, T, M>
+where
+    F: Formula + ?Sized,
+{
     /// Returns true if no items remains in the iterator.
     #[inline(always)]
     fn stack_empty(&self) -> bool {
@@ -463,6 +897,18 @@ where
             Some(max_stack) => self.de.stack < max_stack,
         }
     }
+
+    /// Sets error if elements remain after limited number of elements
+    /// of unsized formula is read.
+    #[inline(always)]
+    fn check_limit(&mut self) {
+        if self.limited && self.upper == 0 {
+            self.limited = false;
+            if !self.stack_empty() {
+                self.error = Some(DeserializeError::Incompatible);
+            }
+        }
+    }
 }
 
 impl<'de, F, T, M> Clone for DeIter<'de, F, T, M>
@@ -475,12 +921,17 @@ where
             de: self.de.clone(),
             marker: PhantomData,
             upper: self.upper,
+            limited: self.limited,
+            error: self.error,
         }
     }
 
     #[inline(always)]
     fn clone_from(&mut self, source: &Self) {
         self.de = source.de.clone();
+        self.upper = source.upper;
+        self.limited = source.limited;
+        self.error = source.error;
     }
 }
 
@@ -493,19 +944,29 @@ where
 
     #[inline(always)]
     fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.error.is_some() {
+            return (1, Some(1));
+        }
         match F::MAX_STACK_SIZE {
-            None => (usize::from(self.de.stack >= SIZE_STACK), Some(self.upper)),
+            None => (
+                usize::from(self.de.stack >= SIZE_STACK),
+                Some(self.upper + usize::from(self.limited)),
+            ),
             Some(_) => (self.upper, Some(self.upper)),
         }
     }
 
     #[inline(always)]
     fn next(&mut self) -> Option<Result<T, DeserializeError>> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
         if self.is_empty() {
             return None;
         }
         let item = self.de.read_value::<F, T>(false);
         self.upper -= 1;
+        self.check_limit();
         Some(item)
     }
 
@@ -513,16 +974,20 @@ where
     fn count(self) -> usize {
         match F::MAX_STACK_SIZE {
             None => self.fold(0, |acc, _| acc + 1),
-            Some(_) => self.upper,
+            Some(_) => self.upper + usize::from(self.error.is_some()),
         }
     }
 
     #[inline]
     fn nth(&mut self, n: usize) -> Option<Result<T, DeserializeError>> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
         if n > 0 {
             if n >= self.upper {
                 self.upper = 0;
-                return None;
+                self.check_limit();
+                return self.error.take().map(Err);
             }
             if let Err(err) = self.de.skip_values::<F>(n) {
                 self.upper = 0;
@@ -538,30 +1003,17 @@ where
     where
         Fun: FnMut(B, Result<T, DeserializeError>) -> B,
     {
+        if let Some(err) = self.error.take() {
+            return f(init, Err(err));
+        }
         match F::MAX_STACK_SIZE {
-            None => loop {
-                if self.de.stack < SIZE_STACK {
-                    break;
+            None => {
+                for result in self.by_ref() {
+                    init = f(init, result);
                 }
-                let sub = Deserializer::new_unchecked(SIZE_STACK, self.de.input);
-                self.de.input = &self.de.input[..self.de.input.len() - SIZE_STACK];
-
-                let stack = match deserialize_usize(sub) {
-                    Ok(stack) => stack,
-                    Err(err) => {
-                        self.de.stack = 0;
-                        return f(init, cold_err(err));
-                    }
-                };
-                let sub = Deserializer::new_unchecked(stack, self.de.input);
-                self.de.input = &self.de.input[..self.de.input.len() - stack];
-                self.de.stack -= SIZE_STACK * stack;
-
-                let result = <T as Deserialize<'de, F>>::deserialize(sub);
-                init = f(init, result);
-            },
+            }
             Some(0) => {
-                let sub = Deserializer::new_unchecked(0, self.de.input);
+                let sub = self.de.derive(0, self.de.input);
                 for _ in 0..self.upper {
                     let result = <T as Deserialize<'de, F>>::deserialize(sub.clone());
                     init = f(init, result);
@@ -570,7 +1022,7 @@ where
             Some(stack) => {
                 assert_eq!(self.de.stack / stack, self.upper);
                 for _ in 0..self.upper {
-                    let sub = Deserializer::new_unchecked(stack, self.de.input);
+                    let sub = self.de.derive(stack, self.de.input);
                     self.de.input = &self.de.input[..self.de.input.len() - stack];
 
                     let result = <T as Deserialize<'de, F>>::deserialize(sub);
@@ -597,6 +1049,9 @@ where
 {
     #[inline(always)]
     fn next_back(&mut self) -> Option<Result<T, DeserializeError>> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
         if Self::is_empty(self) {
             return None;
         }
@@ -607,6 +1062,9 @@ where
 
     #[inline]
     fn nth_back(&mut self, n: usize) -> Option<Result<T, DeserializeError>> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
         if n > 0 {
             if n >= self.upper {
                 self.upper = 0;
@@ -623,9 +1081,12 @@ where
     where
         Fun: FnMut(B, Result<T, DeserializeError>) -> B,
     {
+        if let Some(err) = self.error {
+            return f(init, Err(err));
+        }
         match Self::ELEMENT_SIZE {
             0 => {
-                let sub = Deserializer::new_unchecked(0, self.de.input);
+                let sub = self.de.derive(0, self.de.input);
                 for _ in 0..self.upper {
                     let result = <T as Deserialize<'de, F>>::deserialize(sub.clone());
                     init = f(init, result);
@@ -636,7 +1097,7 @@ where
                 let mut end = self.de.input.len() - stack * self.upper;
                 for _ in 0..self.upper {
                     end += stack;
-                    let sub = Deserializer::new_unchecked(stack, &self.de.input[..end]);
+                    let sub = self.de.derive(stack, &self.de.input[..end]);
 
                     let result = <T as Deserialize<'de, F>>::deserialize(sub);
                     init = f(init, result);
@@ -665,19 +1126,12 @@ where
 {
 }
 
-/// Deserializes value from the input.
-/// The value must occupy the whole input slice.
-/// The value must be either sized or heap-less.
-/// Returns deserialized value.
-///
-/// # Errors
-///
-/// Returns `DeserializeError` if deserialization fails.
+/// Panics if formula is neither sized nor heap-less,
+/// as such values can't be deserialized from the whole input.
 #[inline(always)]
-pub fn deserialize<'de, F, T>(input: &'de [u8]) -> Result<T, DeserializeError>
+pub(crate) fn assert_sized_or_heapless<F>()
 where
     F: Formula + ?Sized,
-    T: Deserialize<'de, F>,
 {
     assert!(
         F::HEAPLESS || F::MAX_STACK_SIZE.is_some(),
@@ -695,6 +1149,23 @@ where
             "not sized"
         }
     );
+}
+
+/// Deserializes value from the input.
+/// The value must occupy the whole input slice.
+/// The value must be either sized or heap-less.
+/// Returns deserialized value.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[inline(always)]
+pub fn deserialize<'de, F, T>(input: &'de [u8]) -> Result<T, DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    assert_sized_or_heapless::<F>();
 
     let stack = match F::MAX_STACK_SIZE {
         None => input.len(),
@@ -707,6 +1178,41 @@ where
     Ok(value)
 }
 
+/// Deserializes value from the input
+/// limiting number of elements in sequences.
+/// The value must occupy the whole input slice.
+/// The value must be either sized or heap-less.
+/// Returns deserialized value.
+///
+/// Use for untrusted input, where length prefixes may claim
+/// more elements than are reasonable to allocate.
+/// The limit applies to sequences of both sized and unsized formulas,
+/// see [`Deserializer::with_max_elements`] for details.
+///
+/// # Errors
+///
+/// Returns `DeserializeError::Incompatible` if sequence exceeds the limit.
+/// Returns `DeserializeError` if deserialization fails.
+#[inline(always)]
+pub fn deserialize_with_limit<'de, F, T>(
+    input: &'de [u8],
+    max_elements: usize,
+) -> Result<T, DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    assert_sized_or_heapless::<F>();
+
+    let stack = match F::MAX_STACK_SIZE {
+        None => input.len(),
+        Some(max_stack) => max_stack.min(input.len()),
+    };
+
+    let de = Deserializer::new_unchecked(stack, input).with_max_elements(max_elements);
+    <T as Deserialize<'de, F>>::deserialize(de)
+}
+
 /// Deserializes value from the input.
 /// The value must occupy the whole input slice.
 /// Returns deserialized value.
@@ -780,24 +1286,19 @@ where
 }
 
 #[inline(always)]
-pub fn read_reference<F>(input: &[u8], len: usize) -> (usize, usize)
+pub fn read_reference<F>(input: &[u8], len: usize) -> Result<(usize, usize), DeserializeError>
 where
     F: Formula + ?Sized,
 {
     let reference_size = reference_size::<F>();
     debug_assert!(reference_size <= input.len());
 
+    let mut de = Deserializer::new_unchecked(reference_size, &input[..reference_size]);
     if F::EXACT_SIZE {
-        let mut de = Deserializer::new(reference_size, &input[..reference_size]).unwrap();
-        let Ok(address) = de.read_usize() else {
-            unreachable!();
-        };
-        (address, unwrap_size(F::MAX_STACK_SIZE).min(len))
+        let address = de.read_usize()?;
+        Ok((address, unwrap_size(F::MAX_STACK_SIZE).min(len)))
     } else {
-        let mut de = Deserializer::new(reference_size, &input[..reference_size]).unwrap();
-        let Ok([size, address]) = de.read_value::<[usize; 2], [usize; 2]>(true) else {
-            unreachable!();
-        };
-        (address, size)
+        let [size, address] = de.read_value::<[usize; 2], [usize; 2]>(true)?;
+        Ok((address, size))
     }
 }
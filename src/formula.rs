@@ -133,9 +133,11 @@ pub(crate) const fn unwrap_size(a: Option<usize>) -> usize {
 
 /// Function to combine sizes of formulas.
 /// If any of two is `None` then result is `None`.
+///
+/// Useful to calculate `MAX_STACK_SIZE` of a formula
+/// that is a sequence of other formulas.
 #[must_use]
 #[inline(always)]
-#[doc(hidden)]
 pub const fn sum_size(a: Option<usize>, b: Option<usize>) -> Option<usize> {
     match (a, b) {
         (None, _) | (_, None) => None,
@@ -147,9 +149,11 @@ pub const fn sum_size(a: Option<usize>, b: Option<usize>) -> Option<usize> {
 /// Order of arguments is not important.
 /// If any argument is `None` then result is `None`.
 /// If both arguments are `Some` then result is maximum of the two.
+///
+/// Useful to calculate `MAX_STACK_SIZE` of a formula
+/// that is one of several other formulas.
 #[must_use]
 #[inline(always)]
-#[doc(hidden)]
 pub const fn max_size(a: Option<usize>, b: Option<usize>) -> Option<usize> {
     match (a, b) {
         (Some(_), None) | (None, _) => None,
@@ -159,10 +163,14 @@ pub const fn max_size(a: Option<usize>, b: Option<usize>) -> Option<usize> {
 }
 
 /// Function for multiplying size of formula by a constant.
-/// First argument cannot be `None` and will cause an error.
+/// If first argument is `None` then result is `None`.
 /// If first argument is `Some` then product of arguments is returned.
+///
+/// Useful to calculate `MAX_STACK_SIZE` of a formula
+/// that is a repetition of another formula.
+#[must_use]
 #[inline(always)]
-pub(crate) const fn repeat_size(a: Option<usize>, n: usize) -> Option<usize> {
+pub const fn repeat_size(a: Option<usize>, n: usize) -> Option<usize> {
     match a {
         None => None,
         Some(a) => Some(a * n),
@@ -182,3 +190,44 @@ where
         SIZE_STACK * 2
     }
 }
+
+/// Byte layout of a reference to a value with some formula.
+///
+/// Reference is written as [`FixedUsizeType`] words,
+/// little-endian by default and big-endian with `big-endian-sizes` feature.
+/// The `address` word is always present.
+/// It is the offset from the start of the input to the end
+/// of the referenced value, whose stack is located right before the address.
+///
+/// When `has_len` is `true`, the `len` word follows the `address`,
+/// and holds the stack size of the referenced value.
+/// Otherwise formula has exact size and its `MAX_STACK_SIZE` is used.
+///
+/// ```text
+/// has_len == false: [address]
+/// has_len == true:  [address][len]
+/// ```
+///
+/// [`FixedUsizeType`]: crate::advanced::FixedUsizeType
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReferenceLayout {
+    /// Total size of the reference in bytes.
+    pub size_bytes: usize,
+
+    /// Whether reference contains the length of the referenced value.
+    pub has_len: bool,
+}
+
+/// Returns byte layout of formula reference.
+/// See [`ReferenceLayout`] for details.
+#[must_use]
+#[inline(always)]
+pub const fn reference_layout<F>() -> ReferenceLayout
+where
+    F: Formula + ?Sized,
+{
+    ReferenceLayout {
+        size_bytes: reference_size::<F>(),
+        has_len: !F::EXACT_SIZE,
+    }
+}
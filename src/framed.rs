@@ -0,0 +1,100 @@
+use core::marker::PhantomData;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{sum_size, Formula},
+    serialize::{field_size_hint, write_field, Serialize, Sizes},
+    size::{fixed_usize_to_bytes, usize_truncate, SIZE_STACK},
+};
+
+/// Formula for length-delimited frames.
+///
+/// Writes stack size of the value serialized with formula `F`
+/// as `usize` followed by the value.
+/// This makes it possible to skip the frame without knowing `F`,
+/// for example when `F` is versioned independently from enclosing formula.
+/// Heap data of the value is not part of the frame.
+///
+/// Serializable from and deserializable into any type
+/// that is serializable and deserializable with formula `F`.
+/// Deserializing into [`Skip`](crate::Skip) consumes the frame.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// type MyFormula = (Framed<str>, u32);
+///
+/// let mut buffer = [0u8; 64];
+/// let (size, _) = serialize::<MyFormula, _>(("hello", 42u32), &mut buffer).unwrap();
+///
+/// let (_, value) = deserialize::<MyFormula, (Skip, u32)>(&buffer[..size]).unwrap();
+/// assert_eq!(value, 42);
+/// ```
+pub struct Framed<F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Formula for Framed<F>
+where
+    F: Formula + ?Sized,
+{
+    const MAX_STACK_SIZE: Option<usize> = sum_size(Some(SIZE_STACK), F::MAX_STACK_SIZE);
+    const EXACT_SIZE: bool = F::EXACT_SIZE;
+    const HEAPLESS: bool = F::HEAPLESS;
+}
+
+impl<F, T> Serialize<Framed<F>> for T
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        buffer.write_stack(sizes.heap, sizes.stack, &[0; SIZE_STACK])?;
+        sizes.stack += SIZE_STACK;
+
+        let old_stack = sizes.stack;
+        write_field::<F, T, _>(self, sizes, buffer.reborrow(), true)?;
+
+        let size = usize_truncate(sizes.stack - old_stack);
+        let res = buffer.write_stack(
+            sizes.heap,
+            old_stack - SIZE_STACK,
+            &fixed_usize_to_bytes(size),
+        );
+        if res.is_err() {
+            unreachable!("Successfully written before");
+        };
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        let mut sizes = field_size_hint::<F>(self, true)?;
+        sizes.add_stack(SIZE_STACK);
+        Some(sizes)
+    }
+}
+
+impl<'de, F, T> Deserialize<'de, Framed<F>> for T
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let size = de.read_len()?;
+        <T as Deserialize<'de, F>>::deserialize(de.sub(size)?)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, mut de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let size = de.read_len()?;
+        <T as Deserialize<'de, F>>::deserialize_in_place(self, de.sub(size)?)
+    }
+}
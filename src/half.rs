@@ -0,0 +1,72 @@
+//! Serialization of half-precision floats.
+//!
+//! `f16` and `bf16` are serialized as their bit patterns in little-endian.
+//! NaN payloads are preserved.
+
+use half::{bf16, f16};
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+};
+
+macro_rules! impl_half {
+    ($($ty:ident)*) => {$(
+        impl Formula for $ty {
+            const MAX_STACK_SIZE: Option<usize> = Some(2);
+            const EXACT_SIZE: bool = true;
+            const HEAPLESS: bool = true;
+        }
+
+        impl BareFormula for $ty {}
+
+        impl Serialize<$ty> for $ty {
+            #[inline(always)]
+            fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                write_bytes(&self.to_bits().to_le_bytes(), sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                Some(Sizes::with_stack(2))
+            }
+        }
+
+        impl SerializeRef<$ty> for $ty {
+            #[inline(always)]
+            fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                write_bytes(&self.to_bits().to_le_bytes(), sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                Some(Sizes::with_stack(2))
+            }
+        }
+
+        impl Deserialize<'_, $ty> for $ty {
+            #[inline(always)]
+            fn deserialize(mut de: Deserializer) -> Result<Self, DeserializeError> {
+                let bytes = de.read_byte_array::<2>()?;
+                Ok(<$ty>::from_bits(u16::from_le_bytes(bytes)))
+            }
+
+            #[inline(always)]
+            fn deserialize_in_place(&mut self, mut de: Deserializer) -> Result<(), DeserializeError> {
+                let bytes = de.read_byte_array::<2>()?;
+                *self = <$ty>::from_bits(u16::from_le_bytes(bytes));
+                Ok(())
+            }
+        }
+    )*};
+}
+
+impl_half!(f16 bf16);
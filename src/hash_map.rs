@@ -0,0 +1,101 @@
+//! Serialization of `HashMap`.
+//!
+//! Map is serialized as a slice of key-value pairs in arbitrary order.
+//! Deserialization builds the map with hasher created by `S::default()`,
+//! so maps with non-default hashers are supported.
+
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, Hash},
+};
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+    iter::{deserialize_extend_iter, deserialize_from_iter},
+    serialize::{write_slice, Serialize, SerializeRef, Sizes},
+    size::SIZE_STACK,
+};
+
+/// Returns the size of the serialized map if it can be determined fast.
+#[inline(always)]
+fn map_fast_sizes<FK, FV>(len: usize) -> Option<Sizes>
+where
+    FK: Formula,
+    FV: Formula,
+{
+    match (
+        <(FK, FV) as Formula>::HEAPLESS,
+        <(FK, FV) as Formula>::MAX_STACK_SIZE,
+    ) {
+        (true, Some(0)) => Some(Sizes::with_stack(SIZE_STACK)),
+        (true, Some(max_stack)) => Some(Sizes::with_stack(len * max_stack)),
+        _ => None,
+    }
+}
+
+impl<FK, FV, K, V, S> Serialize<[(FK, FV)]> for HashMap<K, V, S>
+where
+    FK: Formula,
+    FV: Formula,
+    K: Serialize<FK>,
+    V: Serialize<FV>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice::<(FK, FV), _, _>(self.into_iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        map_fast_sizes::<FK, FV>(self.len())
+    }
+}
+
+impl<FK, FV, K, V, S> SerializeRef<[(FK, FV)]> for HashMap<K, V, S>
+where
+    FK: Formula,
+    FV: Formula,
+    for<'ser> &'ser K: Serialize<FK>,
+    for<'ser> &'ser V: Serialize<FV>,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice::<(FK, FV), _, _>(self.iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        map_fast_sizes::<FK, FV>(self.len())
+    }
+}
+
+impl<'de, FK, FV, K, V, S> Deserialize<'de, [(FK, FV)]> for HashMap<K, V, S>
+where
+    FK: Formula,
+    FV: Formula,
+    K: Deserialize<'de, FK> + Eq + Hash,
+    V: Deserialize<'de, FV>,
+    S: BuildHasher + Default,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        deserialize_from_iter(de.into_unsized_iter::<(FK, FV), _>())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        self.clear();
+        let iter = de.into_unsized_iter::<(FK, FV), _>();
+        let (lower, _) = Iterator::size_hint(&iter);
+        self.reserve(lower);
+        deserialize_extend_iter(self, iter)
+    }
+}
@@ -0,0 +1,165 @@
+//! Serialization of `IndexMap` and `IndexSet`.
+//!
+//! Map is serialized as a slice of key-value pairs and set as a slice of elements,
+//! both in insertion order.
+//! Deserialization inserts entries in the order they appear in the slice,
+//! so insertion order survives the round trip.
+//! Hasher is created by `S::default()`.
+
+use core::hash::{BuildHasher, Hash};
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+    iter::{
+        deserialize_extend_iter, deserialize_from_iter, owned_iter_fast_sizes, ref_iter_fast_sizes,
+    },
+    serialize::{write_slice, Serialize, SerializeRef, Sizes},
+    size::SIZE_STACK,
+};
+
+/// Returns the size of the serialized map if it can be determined fast.
+#[inline(always)]
+fn map_fast_sizes<FK, FV>(len: usize) -> Option<Sizes>
+where
+    FK: Formula,
+    FV: Formula,
+{
+    match (
+        <(FK, FV) as Formula>::HEAPLESS,
+        <(FK, FV) as Formula>::MAX_STACK_SIZE,
+    ) {
+        (true, Some(0)) => Some(Sizes::with_stack(SIZE_STACK)),
+        (true, Some(max_stack)) => Some(Sizes::with_stack(len * max_stack)),
+        _ => None,
+    }
+}
+
+impl<FK, FV, K, V, S> Serialize<[(FK, FV)]> for IndexMap<K, V, S>
+where
+    FK: Formula,
+    FV: Formula,
+    K: Serialize<FK>,
+    V: Serialize<FV>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice::<(FK, FV), _, _>(self.into_iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        map_fast_sizes::<FK, FV>(self.len())
+    }
+}
+
+impl<FK, FV, K, V, S> SerializeRef<[(FK, FV)]> for IndexMap<K, V, S>
+where
+    FK: Formula,
+    FV: Formula,
+    for<'ser> &'ser K: Serialize<FK>,
+    for<'ser> &'ser V: Serialize<FV>,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice::<(FK, FV), _, _>(self.iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        map_fast_sizes::<FK, FV>(self.len())
+    }
+}
+
+/// Later duplicate keys overwrite the value but keep position of the first one.
+impl<'de, FK, FV, K, V, S> Deserialize<'de, [(FK, FV)]> for IndexMap<K, V, S>
+where
+    FK: Formula,
+    FV: Formula,
+    K: Deserialize<'de, FK> + Eq + Hash,
+    V: Deserialize<'de, FV>,
+    S: BuildHasher + Default,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        deserialize_from_iter(de.into_unsized_iter::<(FK, FV), _>())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        self.clear();
+        let iter = de.into_unsized_iter::<(FK, FV), _>();
+        let (lower, _) = Iterator::size_hint(&iter);
+        self.reserve(lower);
+        deserialize_extend_iter(self, iter)
+    }
+}
+
+impl<F, T, S> Serialize<[F]> for IndexSet<T, S>
+where
+    F: Formula,
+    T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice(self.into_iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        ref_iter_fast_sizes::<F, _, _>(self.iter())
+    }
+}
+
+impl<F, T, S> SerializeRef<[F]> for IndexSet<T, S>
+where
+    F: Formula,
+    for<'ser> &'ser T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice(self.iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        owned_iter_fast_sizes::<F, _, _>(self.iter())
+    }
+}
+
+/// Duplicate elements keep position of the first one.
+impl<'de, F, T, S> Deserialize<'de, [F]> for IndexSet<T, S>
+where
+    F: Formula,
+    T: Deserialize<'de, F> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        deserialize_from_iter(de.into_unsized_iter::<F, _>())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        self.clear();
+        let iter = de.into_unsized_iter::<F, _>();
+        let (lower, _) = Iterator::size_hint(&iter);
+        self.reserve(lower);
+        deserialize_extend_iter(self, iter)
+    }
+}
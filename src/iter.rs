@@ -35,11 +35,11 @@ where
 
 /// Returns the size of the serialized data if it can be determined fast.
 #[inline]
-pub fn ref_iter_fast_sizes<'a, F, I, T: 'a>(iter: I) -> Option<Sizes>
+pub fn ref_iter_fast_sizes<'a, F, I, T>(iter: I) -> Option<Sizes>
 where
     F: Formula + ?Sized,
     I: Iterator<Item = &'a T>,
-    T: Serialize<F>,
+    T: Serialize<F> + 'a,
 {
     match (F::HEAPLESS, F::MAX_STACK_SIZE) {
         (true, Some(0)) => Some(Sizes::with_stack(SIZE_STACK)),
@@ -56,7 +56,7 @@ where
         }
         _ => {
             let (_lower, upper) = iter.size_hint();
-            if upper.map_or(false, |upper| upper <= ITER_UPPER) {
+            if upper.is_some_and(|upper| upper <= ITER_UPPER) {
                 let mut sizes = Sizes::ZERO;
                 for elem in iter {
                     sizes += field_size_hint::<F>(elem, false)?;
@@ -91,7 +91,7 @@ where
         }
         _ => {
             let (_lower, upper) = iter.size_hint();
-            if upper.map_or(false, |upper| upper <= ITER_UPPER) {
+            if upper.is_some_and(|upper| upper <= ITER_UPPER) {
                 let mut sizes = Sizes::ZERO;
                 for elem in iter {
                     sizes += field_size_hint::<F>(&elem, false)?;
@@ -112,10 +112,82 @@ macro_rules! serialize_iter_to_slice {
 /// Iterator wrapper serializable with slice formula.
 /// Many standard library iterators implement serialization.
 /// For others this wrapper can be used without performance penalty.
+///
+/// Iterators with exact length, like any `ExactSizeIterator`,
+/// provide exact size hint when element formula is sized and heap-less.
+/// Such iterators are serialized with the same fast path and into the same
+/// bytes as slices.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct SerIter<T>(pub T);
 
+impl<I> SerIter<I>
+where
+    I: Iterator,
+{
+    /// Wraps iterator produced from `iter`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 16];
+    /// let (size, _) = serialize::<[u32], _>(SerIter::new([1u32, 2, 3].iter().map(|x| x * 2)), &mut buffer).unwrap();
+    /// let values = deserialize::<[u32], Vec<u32>>(&buffer[..size]).unwrap();
+    /// assert_eq!(values, [2, 4, 6]);
+    /// ```
+    #[inline(always)]
+    pub fn new<T>(iter: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        SerIter(iter.into_iter())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> SerIter<alloc::vec::IntoIter<T>> {
+    /// Drains iterator produced from `iter` into a staging `Vec`
+    /// and wraps iterator over it.
+    ///
+    /// Iterators that can't report exact length don't provide size hint,
+    /// so serializing them with indirect formulas like `Vec<F>`
+    /// takes slower path that moves serialized data after writing.
+    /// Buffered iterator knows exact number of elements,
+    /// at the cost of extra allocation for staging `Vec`.
+    ///
+    /// Iterator is consumed once, so this works with one-shot iterators.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 32];
+    /// let iter = [1u32, 2, 3, 4].into_iter().filter(|x| x % 2 == 0);
+    /// let (size, _) = serialize::<Vec<u32>, _>(SerIter::buffered(iter), &mut buffer).unwrap();
+    /// let values = deserialize::<Vec<u32>, Vec<u32>>(&buffer[..size]).unwrap();
+    /// assert_eq!(values, [2, 4]);
+    /// ```
+    #[inline]
+    pub fn buffered<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let vec: alloc::vec::Vec<T> = iter.into_iter().collect();
+        SerIter(vec.into_iter())
+    }
+}
+
+impl<T> From<T> for SerIter<T::IntoIter>
+where
+    T: IntoIterator,
+{
+    #[inline(always)]
+    fn from(iter: T) -> Self {
+        SerIter(iter.into_iter())
+    }
+}
+
 impl<F, T, I> Serialize<[F]> for SerIter<I>
 where
     F: Formula,
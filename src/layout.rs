@@ -0,0 +1,92 @@
+/// Asserts layout properties of a formula at compile time.
+///
+/// Fails to compile if formula's associated constants
+/// do not match expectations.
+/// Useful to catch accidental changes of the wire format.
+///
+/// Accepts formula type followed by any combination of
+///
+/// * `stack = N` - `MAX_STACK_SIZE` is `Some(N)`.
+/// * `stack = None` - `MAX_STACK_SIZE` is `None`.
+/// * `exact` or `!exact` - `EXACT_SIZE` is `true` or `false`.
+/// * `heapless` or `!heapless` - `HEAPLESS` is `true` or `false`.
+///
+/// Properties not listed are not checked.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// assert_formula_layout!(u32, stack = 4, exact, heapless);
+/// assert_formula_layout!([u8], stack = None, !exact, heapless);
+/// assert_formula_layout!(Ref<str>, exact, !heapless);
+/// ```
+///
+/// Mismatch fails to compile.
+///
+/// ```compile_fail
+/// # use alkahest::*;
+/// assert_formula_layout!(u32, stack = 8);
+/// ```
+///
+/// ```compile_fail
+/// # use alkahest::*;
+/// assert_formula_layout!([u8], exact);
+/// ```
+#[macro_export]
+macro_rules! assert_formula_layout {
+    (@check $formula:ty;) => {};
+    (@check $formula:ty; stack = None $(, $($rest:tt)*)?) => {
+        const _: () = ::core::assert!(
+            <$formula as $crate::private::Formula>::MAX_STACK_SIZE.is_none(),
+            ::core::concat!("`", ::core::stringify!($formula), "` is expected to be unsized"),
+        );
+        $crate::assert_formula_layout!(@check $formula; $($($rest)*)?);
+    };
+    (@check $formula:ty; stack = $stack:expr $(, $($rest:tt)*)?) => {
+        const _: () = ::core::assert!(
+            ::core::matches!(
+                <$formula as $crate::private::Formula>::MAX_STACK_SIZE,
+                ::core::option::Option::Some(stack) if stack == $stack
+            ),
+            ::core::concat!(
+                "`", ::core::stringify!($formula),
+                "` is expected to have stack size of ", ::core::stringify!($stack)
+            ),
+        );
+        $crate::assert_formula_layout!(@check $formula; $($($rest)*)?);
+    };
+    (@check $formula:ty; exact $(, $($rest:tt)*)?) => {
+        const _: () = ::core::assert!(
+            <$formula as $crate::private::Formula>::EXACT_SIZE,
+            ::core::concat!("`", ::core::stringify!($formula), "` is expected to be exact size"),
+        );
+        $crate::assert_formula_layout!(@check $formula; $($($rest)*)?);
+    };
+    (@check $formula:ty; !exact $(, $($rest:tt)*)?) => {
+        const _: () = ::core::assert!(
+            !<$formula as $crate::private::Formula>::EXACT_SIZE,
+            ::core::concat!("`", ::core::stringify!($formula), "` is expected to not be exact size"),
+        );
+        $crate::assert_formula_layout!(@check $formula; $($($rest)*)?);
+    };
+    (@check $formula:ty; heapless $(, $($rest:tt)*)?) => {
+        const _: () = ::core::assert!(
+            <$formula as $crate::private::Formula>::HEAPLESS,
+            ::core::concat!("`", ::core::stringify!($formula), "` is expected to be heapless"),
+        );
+        $crate::assert_formula_layout!(@check $formula; $($($rest)*)?);
+    };
+    (@check $formula:ty; !heapless $(, $($rest:tt)*)?) => {
+        const _: () = ::core::assert!(
+            !<$formula as $crate::private::Formula>::HEAPLESS,
+            ::core::concat!("`", ::core::stringify!($formula), "` is expected to use heap"),
+        );
+        $crate::assert_formula_layout!(@check $formula; $($($rest)*)?);
+    };
+
+    ($formula:ty $(,)?) => {};
+    ($formula:ty, $($rest:tt)+) => {
+        $crate::assert_formula_layout!(@check $formula; $($rest)+);
+    };
+}
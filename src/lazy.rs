@@ -1,5 +1,6 @@
 use core::{
     any::type_name,
+    borrow::Borrow,
     fmt::{self, Debug},
     marker::PhantomData,
 };
@@ -7,6 +8,7 @@ use core::{
 use crate::{
     deserialize::{DeIter, Deserialize, DeserializeError, Deserializer, SizedDeIter},
     formula::{unwrap_size, BareFormula, Formula},
+    reference::Ref,
 };
 
 /// Wrapper for lazy deserialization.
@@ -57,6 +59,29 @@ where
     {
         <T as Deserialize<'de, F>>::deserialize_in_place(place, self.de.clone())
     }
+
+    /// Deserializes the lazy value and compares it to `value`.
+    ///
+    /// For lazy slices prefer [`Lazy::eq_slice`]
+    /// that stops at the first mismatching element.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn eq_value<T>(&self, value: &T) -> Result<bool, DeserializeError>
+    where
+        T: Deserialize<'de, F> + PartialEq,
+    {
+        Ok(self.get::<T>()? == *value)
+    }
+
+    /// Returns position of the lazy value within input buffer.
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    pub(crate) fn position(&self) -> (usize, usize) {
+        self.de.position()
+    }
 }
 
 trait LazySizedIter<'de, F: ?Sized> {
@@ -165,6 +190,150 @@ where
     {
         self.de.clone().into_unsized_iter()
     }
+
+    /// Compares lazy deserialized elements to `values`.
+    ///
+    /// Elements are deserialized one by one
+    /// and comparison stops at the first mismatch.
+    /// Slices of sized formulas with different length
+    /// are unequal without deserializing any element.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[u32], _>([1u8, 2, 3], &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    /// assert!(lazy.eq_slice::<u32>(&[1, 2, 3]).unwrap());
+    /// assert!(!lazy.eq_slice::<u32>(&[1, 5, 3]).unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization of an element fails.
+    #[inline]
+    pub fn eq_slice<T>(&self, values: &[T]) -> Result<bool, DeserializeError>
+    where
+        T: Deserialize<'de, F> + PartialEq,
+    {
+        let mut iter = self.iter::<T>();
+
+        if let (lower, Some(upper)) = Iterator::size_hint(&iter) {
+            if lower == upper && lower != values.len() {
+                return Ok(false);
+            }
+        }
+
+        for value in values {
+            match iter.next() {
+                None => return Ok(false),
+                Some(elem) => {
+                    if elem? != *value {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        Ok(iter.next().is_none())
+    }
+}
+
+impl<'de, F> Lazy<'de, [Ref<F>]>
+where
+    F: BareFormula + ?Sized,
+{
+    /// Returns number of references in the slice.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.sized_iter::<Lazy<'de, F>>().len()
+    }
+
+    /// Returns `true` if the slice has no references.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Deserializes value referenced by element at `idx`.
+    ///
+    /// Preceding references are skipped without following them,
+    /// so any element is reached in constant time.
+    /// Deserialized values borrow from the shared input.
+    ///
+    /// Returns `None` if `idx` is out of bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[Ref<str>], _>(["qwe", "rty"], &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[Ref<str>], Lazy<[Ref<str>]>>(&buffer[..size], root).unwrap();
+    /// assert_eq!(lazy.len(), 2);
+    /// assert_eq!(lazy.get_at::<&str>(1).unwrap().unwrap(), "rty");
+    /// assert!(lazy.get_at::<&str>(2).is_none());
+    /// ```
+    #[inline]
+    pub fn get_at<T>(&self, idx: usize) -> Option<Result<T, DeserializeError>>
+    where
+        T: Deserialize<'de, F>,
+    {
+        self.sized_iter::<T>().nth(idx)
+    }
+}
+
+impl<'de, FK, FV> Lazy<'de, [(FK, FV)]>
+where
+    FK: Formula,
+    FV: Formula,
+{
+    /// Looks up value by key in lazy deserialized slice of key-value pairs.
+    /// Keys are deserialized one by one and compared to `key`.
+    /// Only value of the first matching pair is deserialized.
+    ///
+    /// This is O(n) scan over the pairs.
+    ///
+    /// Returns `None` if there is no matching key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[(As<str>, u32)], _>([("a", 1u32), ("b", 2)], &mut buffer).unwrap();
+    /// let map = deserialize_with_size::<[(As<str>, u32)], Lazy<[(As<str>, u32)]>>(&buffer[..size], root).unwrap();
+    /// assert_eq!(map.lookup::<&str, u32, str>("b").unwrap().unwrap(), 2);
+    /// assert!(map.lookup::<&str, u32, str>("c").is_none());
+    /// ```
+    pub fn lookup<K, V, Q>(&self, key: &Q) -> Option<Result<V, DeserializeError>>
+    where
+        K: Deserialize<'de, FK> + Borrow<Q>,
+        V: Deserialize<'de, FV>,
+        Q: PartialEq + ?Sized,
+    {
+        for pair in self.iter::<Lazy<'de, (FK, FV)>>() {
+            let mut de = match pair {
+                Ok(pair) => pair.de,
+                Err(err) => return Some(Err(err)),
+            };
+
+            let k = match de.read_value::<FK, K>(false) {
+                Ok(k) => k,
+                Err(err) => return Some(Err(err)),
+            };
+
+            if *k.borrow() == *key {
+                return Some(de.read_value::<FV, V>(true));
+            }
+        }
+        None
+    }
 }
 
 impl<'de, 'fe: 'de, F> Deserialize<'fe, F> for Lazy<'de, F>
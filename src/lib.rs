@@ -27,10 +27,15 @@ mod array;
 mod r#as;
 mod buffer;
 mod bytes;
+mod cmp;
+mod ctx;
 mod deserialize;
 mod formula;
+mod framed;
 mod iter;
+mod layout;
 mod lazy;
+mod net;
 mod option;
 mod packet;
 mod primitive;
@@ -39,13 +44,29 @@ mod serialize;
 mod size;
 mod skip;
 mod slice;
+mod sorted;
 mod str;
+mod time;
 mod tuple;
+mod unknown;
+mod versioned;
 mod vlq;
 
+#[cfg(feature = "alloc")]
+mod any;
+
+#[cfg(feature = "alloc")]
+mod batch;
+
+#[cfg(feature = "alloc")]
+mod binary_heap;
+
 #[cfg(feature = "alloc")]
 mod boxed;
 
+#[cfg(feature = "alloc")]
+mod dedup;
+
 #[cfg(test)]
 mod tests;
 
@@ -58,22 +79,47 @@ mod vec_deque;
 #[cfg(feature = "alloc")]
 mod string;
 
+#[cfg(feature = "std")]
+mod hash_map;
+
+#[cfg(feature = "std")]
+mod path;
+
+#[cfg(feature = "std")]
+mod read;
+
 #[cfg(feature = "bincoded")]
 mod bincoded;
 
+#[cfg(feature = "smallvec")]
+mod smallvec;
+
+#[cfg(feature = "half")]
+mod half;
+
+#[cfg(feature = "chrono")]
+mod chrono;
+
+#[cfg(feature = "indexmap")]
+mod indexmap;
+
 pub use crate::{
     buffer::BufferExhausted,
     bytes::Bytes,
+    ctx::{deserialize_with_ctx, serialize_with_ctx, DeserializeCtx, SerializeCtx},
     deserialize::{
-        deserialize, deserialize_in_place, deserialize_in_place_with_size, deserialize_with_size,
-        DeIter, Deserialize, DeserializeError,
+        deserialize, deserialize_in_place, deserialize_in_place_with_size, deserialize_with_limit,
+        deserialize_with_size, DeIter, Deserialize, DeserializeError,
     },
     formula::Formula,
+    framed::Framed,
     iter::SerIter,
     lazy::Lazy,
+    option::OptionReversed,
     packet::{
-        packet_size, read_packet, read_packet_in_place, read_packet_size, write_packet,
-        write_packet_into, write_packet_unchecked,
+        packet_size, read_packet, read_packet_in_place, read_packet_size, read_packet_with,
+        try_read_packet_size, write_packet, write_packet_into, write_packet_unchecked,
+        write_packet_with, Leb128, PacketPrefix, WritePacketError,
     },
     r#as::As,
     reference::Ref,
@@ -82,11 +128,28 @@ pub use crate::{
         Serialize, SerializeRef,
     },
     skip::Skip,
-    vlq::Vlq,
+    sorted::Sorted,
+    str::{Ascii, Str, VlqStr},
+    unknown::UnknownTag,
+    versioned::Versioned,
+    vlq::{Vlq, VlqSaturating},
 };
 
 #[cfg(feature = "alloc")]
-pub use crate::{packet::write_packet_to_vec, serialize::serialize_to_vec};
+pub use crate::{
+    any::AnyRegistry,
+    batch::{BatchHeap, BatchSerializer},
+    dedup::RefDedup,
+    packet::write_packet_to_vec,
+    serialize::serialize_to_vec,
+};
+
+#[cfg(feature = "std")]
+pub use crate::{
+    path::PathUtf8,
+    read::{read_bytes_to_writer, ReadDeError, ReadDeIter},
+    time::InstantSince,
+};
 
 #[cfg(feature = "derive")]
 pub use alkahest_proc::{alkahest, Deserialize, Formula, Serialize, SerializeRef};
@@ -98,16 +161,22 @@ pub use bincoded::{Bincode, Bincoded};
 /// `Serialize` and `Deserialize` traits.
 pub mod advanced {
     pub use crate::{
-        buffer::{Buffer, CheckedFixedBuffer, MaybeFixedBuffer},
+        buffer::{Buffer, CheckedFixedBuffer, CountingBuffer, MaybeFixedBuffer, Reservation},
         deserialize::Deserializer,
-        formula::{reference_size, BareFormula},
+        formula::{
+            max_size, reference_layout, reference_size, repeat_size, sum_size, BareFormula,
+            ReferenceLayout,
+        },
         iter::{default_iter_fast_sizes, deserialize_extend_iter, deserialize_from_iter},
         serialize::{
-            field_size_hint, formula_fast_sizes, slice_writer, write_array, write_bytes,
-            write_exact_size_field, write_field, write_ref, write_reference, write_slice, Sizes,
-            SliceWriter,
+            element_fast_sizes, field_size_hint, formula_fast_sizes, slice_writer, write_array,
+            write_bytes, write_exact_size_field, write_field, write_raw_reference, write_ref,
+            write_reference, write_slice, Sizes, SliceWriter,
+        },
+        size::{
+            fixed_usize_from_bytes, fixed_usize_to_bytes, reference_width, FixedIsizeType,
+            FixedUsizeType, FIXED_USIZE_BITS,
         },
-        size::{FixedIsizeType, FixedUsizeType},
     };
 
     #[cfg(feature = "alloc")]
@@ -120,14 +189,21 @@ pub mod advanced {
 pub mod private {
     pub use {
         bool,
-        core::{convert::Into, debug_assert_eq, option::Option, result::Result},
-        u32, u8, usize,
+        core::{
+            assert, convert::Into, debug_assert_eq, default::Default, iter::IntoIterator,
+            option::Option, result::Result, unreachable,
+        },
+        str, u32, u8, usize,
     };
 
+    #[cfg(feature = "arbitrary")]
+    pub use arbitrary;
+
     pub use crate::{
         buffer::Buffer,
         deserialize::{Deserialize, DeserializeError, Deserializer},
         formula::{max_size, sum_size, BareFormula, Formula},
+        iter::SerIter,
         serialize::{
             formula_fast_sizes, write_exact_size_field, write_field, Serialize, SerializeRef, Sizes,
         },
@@ -135,8 +211,7 @@ pub mod private {
 
     use core::marker::PhantomData;
 
-    pub const VARIANT_SIZE: usize = core::mem::size_of::<u32>();
-    pub const VARIANT_SIZE_OPT: Option<usize> = Some(VARIANT_SIZE);
+    use crate::unknown::UnknownTag;
 
     pub struct WithFormula<F: Formula + ?Sized> {
         marker: PhantomData<fn(&F) -> &F>,
@@ -146,6 +221,15 @@ pub mod private {
     where
         F: Formula + ?Sized,
     {
+        #[must_use]
+        #[inline(always)]
+        #[allow(clippy::new_without_default)]
+        pub const fn new() -> Self {
+            WithFormula {
+                marker: PhantomData,
+            }
+        }
+
         #[inline(always)]
         pub fn write_field<T, B>(
             self,
@@ -168,7 +252,6 @@ pub mod private {
             last: bool,
         ) -> Result<T, DeserializeError>
         where
-            F: Formula,
             T: Deserialize<'de, F>,
         {
             de.read_value::<F, T>(last)
@@ -182,7 +265,6 @@ pub mod private {
             last: bool,
         ) -> Result<(), DeserializeError>
         where
-            F: Formula,
             T: Deserialize<'de, F>,
         {
             de.read_in_place::<F, T>(place, last)
@@ -197,6 +279,137 @@ pub mod private {
         }
     }
 
+    /// Writes enum variant tag.
+    /// Tag type is inferred from formula's tag constants.
+    /// Nothing is written for untagged enums.
+    #[inline(always)]
+    pub fn write_variant_tag<T, B>(
+        tag: T,
+        untagged: bool,
+        sizes: &mut Sizes,
+        buffer: B,
+    ) -> Result<(), B::Error>
+    where
+        T: Formula + Serialize<T>,
+        B: Buffer,
+    {
+        if untagged {
+            return Ok(());
+        }
+        write_exact_size_field::<T, T, B>(tag, sizes, buffer)
+    }
+
+    /// Writes raw tag held by `unknown` enum variant.
+    /// Tag type is inferred from tags of known variants.
+    #[inline(always)]
+    pub fn write_unknown_variant_tag<F, T, B>(
+        known: &[T],
+        tag: UnknownTag<F>,
+        sizes: &mut Sizes,
+        buffer: B,
+    ) -> Result<(), B::Error>
+    where
+        F: ?Sized,
+        T: Formula + Serialize<T> + TryFrom<u32> + PartialEq,
+        B: Buffer,
+    {
+        match T::try_from(tag.tag()) {
+            Ok(tag_value) => {
+                debug_assert!(!known.contains(&tag_value));
+                write_exact_size_field::<T, T, B>(tag_value, sizes, buffer)
+            }
+            Err(_) => unreachable!("Unknown tag is read with the tag type"),
+        }
+    }
+
+    /// Wraps raw tag read from the input that is unknown to formula `F`.
+    #[must_use]
+    #[inline(always)]
+    pub const fn unknown_tag<F>(tag: u32) -> UnknownTag<F>
+    where
+        F: ?Sized,
+    {
+        UnknownTag::new(tag)
+    }
+
+    /// Returns index of variant with given name.
+    /// Returns number of variants if there is no such variant.
+    #[must_use]
+    pub const fn variant_index(names: &[&str], name: &str) -> usize {
+        let mut i = 0;
+        while i < names.len() {
+            let candidate = names[i].as_bytes();
+            let name = name.as_bytes();
+            if candidate.len() == name.len() {
+                let mut j = 0;
+                while j < name.len() && candidate[j] == name[j] {
+                    j += 1;
+                }
+                if j == name.len() {
+                    return i;
+                }
+            }
+            i += 1;
+        }
+        names.len()
+    }
+
+    /// Checks that formula has variant with given name.
+    /// Used by derived impls to validate `variant` argument at compile time.
+    #[must_use]
+    pub const fn has_variant(names: &[&str], name: &str) -> bool {
+        variant_index(names, name) < names.len()
+    }
+
+    /// Returns tag of variant found with [`variant_index`].
+    #[inline(always)]
+    #[must_use]
+    pub fn variant_tag<T: Copy>(tags: &[T], idx: usize) -> T {
+        tags[idx]
+    }
+
+    /// Returns size of enum variant tag.
+    /// Tag type is inferred from the witness.
+    #[must_use]
+    #[inline(always)]
+    pub fn variant_tag_size<T>(_witness: T, untagged: bool) -> usize
+    where
+        T: Formula,
+    {
+        if untagged {
+            return 0;
+        }
+        crate::formula::unwrap_size(T::MAX_STACK_SIZE)
+    }
+
+    /// Reads enum variant tag.
+    /// Tag type is inferred from the witness.
+    #[inline(always)]
+    pub fn read_variant_tag<'de, T>(
+        de: &mut Deserializer<'de>,
+        _witness: T,
+    ) -> Result<T, DeserializeError>
+    where
+        T: Formula + Deserialize<'de, T>,
+    {
+        de.read_value::<T, T>(false)
+    }
+
+    /// Reads variant of untagged enum on a fork of the deserializer.
+    /// Returns `None` if variant fails to deserialize
+    /// or does not consume the whole field.
+    #[inline(always)]
+    pub fn read_untagged_variant<'de, T>(
+        de: &Deserializer<'de>,
+        f: impl FnOnce(&mut Deserializer<'de>) -> Result<T, DeserializeError>,
+    ) -> Option<T> {
+        let mut fork = de.fork();
+        match f(&mut fork) {
+            Ok(value) if fork.is_consumed() => Some(value),
+            _ => None,
+        }
+    }
+
     #[must_use]
     #[inline(always)]
     pub fn with_formula<F: Formula + ?Sized, L: Formula + ?Sized>(
@@ -0,0 +1,210 @@
+//! Serialization of IP addresses.
+//!
+//! Implemented for `core::net` types, so no `std` is required.
+//! `std::net` re-exports the same types.
+
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+};
+
+/// Discriminant of `IpAddr::V4` in `IpAddr` formula.
+/// `IpAddr::V6` uses `1`.
+const V4_TAG: u8 = 0;
+
+/// Discriminant of `IpAddr::V6` in `IpAddr` formula.
+const V6_TAG: u8 = 1;
+
+impl Formula for Ipv4Addr {
+    const MAX_STACK_SIZE: Option<usize> = Some(4);
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = true;
+}
+
+impl BareFormula for Ipv4Addr {}
+
+impl Serialize<Ipv4Addr> for Ipv4Addr {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&self.octets(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(4))
+    }
+}
+
+impl SerializeRef<Ipv4Addr> for Ipv4Addr {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&self.octets(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(4))
+    }
+}
+
+impl Deserialize<'_, Ipv4Addr> for Ipv4Addr {
+    #[inline(always)]
+    fn deserialize(mut de: Deserializer) -> Result<Self, DeserializeError> {
+        let octets = de.read_byte_array::<4>()?;
+        Ok(Ipv4Addr::from(octets))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<Ipv4Addr>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+impl Formula for Ipv6Addr {
+    const MAX_STACK_SIZE: Option<usize> = Some(16);
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = true;
+}
+
+impl BareFormula for Ipv6Addr {}
+
+impl Serialize<Ipv6Addr> for Ipv6Addr {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&self.octets(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(16))
+    }
+}
+
+impl SerializeRef<Ipv6Addr> for Ipv6Addr {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&self.octets(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(16))
+    }
+}
+
+impl Deserialize<'_, Ipv6Addr> for Ipv6Addr {
+    #[inline(always)]
+    fn deserialize(mut de: Deserializer) -> Result<Self, DeserializeError> {
+        let octets = de.read_byte_array::<16>()?;
+        Ok(Ipv6Addr::from(octets))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<Ipv6Addr>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+/// `IpAddr` is serialized as one byte discriminant
+/// followed by the address octets.
+/// `V4` uses `0` and `V6` uses `1`.
+impl Formula for IpAddr {
+    const MAX_STACK_SIZE: Option<usize> = Some(17);
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+impl BareFormula for IpAddr {}
+
+#[inline(always)]
+fn write_ip_addr<B>(addr: &IpAddr, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    match addr {
+        IpAddr::V4(addr) => {
+            write_bytes(&[V4_TAG], sizes, buffer.reborrow())?;
+            write_bytes(&addr.octets(), sizes, buffer)
+        }
+        IpAddr::V6(addr) => {
+            write_bytes(&[V6_TAG], sizes, buffer.reborrow())?;
+            write_bytes(&addr.octets(), sizes, buffer)
+        }
+    }
+}
+
+#[inline(always)]
+fn ip_addr_size_hint(addr: &IpAddr) -> Sizes {
+    match addr {
+        IpAddr::V4(_) => Sizes::with_stack(5),
+        IpAddr::V6(_) => Sizes::with_stack(17),
+    }
+}
+
+impl Serialize<IpAddr> for IpAddr {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_ip_addr(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(ip_addr_size_hint(self))
+    }
+}
+
+impl SerializeRef<IpAddr> for IpAddr {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_ip_addr(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(ip_addr_size_hint(self))
+    }
+}
+
+impl Deserialize<'_, IpAddr> for IpAddr {
+    #[inline(always)]
+    fn deserialize(mut de: Deserializer) -> Result<Self, DeserializeError> {
+        match de.read_byte()? {
+            V4_TAG => Ok(IpAddr::V4(Ipv4Addr::from(de.read_byte_array::<4>()?))),
+            V6_TAG => Ok(IpAddr::V6(Ipv6Addr::from(de.read_byte_array::<16>()?))),
+            tag => Err(DeserializeError::WrongVariant {
+                got: u32::from(tag),
+                count: 2,
+            }),
+        }
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<IpAddr>>::deserialize(de)?;
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use core::marker::PhantomData;
+
 use crate::{
     buffer::Buffer,
     deserialize::{Deserialize, DeserializeError, Deserializer},
@@ -5,6 +7,14 @@ use crate::{
     serialize::{field_size_hint, write_bytes, write_field, Serialize, SerializeRef, Sizes},
 };
 
+/// Discriminant of `None` in `Option<F>` formula.
+/// `Some` uses `1`.
+const NONE_TAG: u8 = 0;
+
+/// Discriminant of `None` in `OptionReversed<F>` formula.
+/// `Some` uses `0`.
+const NONE_TAG_REVERSED: u8 = 1;
+
 impl<F> Formula for Option<F>
 where
     F: Formula,
@@ -16,38 +26,125 @@ where
 
 impl<F> BareFormula for Option<F> where F: Formula {}
 
+/// Formula for optional values with reversed discriminant.
+///
+/// `Option<F>` formula writes `None` as `0` and `Some` as `1`.
+/// This formula writes `Some` as `0` and `None` as `1`,
+/// for interoperability with formats that put `Some` first.
+/// Otherwise layout is the same.
+///
+/// Serializable from and deserializable into `Option<T>`.
+pub struct OptionReversed<F> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Formula for OptionReversed<F>
+where
+    F: Formula,
+{
+    const MAX_STACK_SIZE: Option<usize> = <Option<F> as Formula>::MAX_STACK_SIZE;
+    const EXACT_SIZE: bool = <Option<F> as Formula>::EXACT_SIZE;
+    const HEAPLESS: bool = <Option<F> as Formula>::HEAPLESS;
+}
+
+impl<F> BareFormula for OptionReversed<F> where F: Formula {}
+
+#[inline(always)]
+fn write_option<F, T, B>(
+    value: Option<T>,
+    none_tag: u8,
+    sizes: &mut Sizes,
+    mut buffer: B,
+) -> Result<(), B::Error>
+where
+    F: Formula,
+    T: Serialize<F>,
+    B: Buffer,
+{
+    match value {
+        None => write_bytes(&[none_tag], sizes, buffer),
+        Some(value) => {
+            write_bytes(&[none_tag ^ 1], sizes, buffer.reborrow())?;
+            write_field::<F, T, _>(value, sizes, buffer, true)
+        }
+    }
+}
+
+#[inline(always)]
+fn option_size_hint<F, T>(value: Option<&T>) -> Option<Sizes>
+where
+    F: Formula,
+    T: Serialize<F>,
+{
+    match value {
+        None => Some(Sizes::with_stack(1)),
+        Some(value) => {
+            let mut sizes = field_size_hint::<F>(value, true)?;
+            sizes.add_stack(1);
+            Some(sizes)
+        }
+    }
+}
+
+#[inline(always)]
+fn read_option<'de, F, T>(
+    mut de: Deserializer<'de>,
+    none_tag: u8,
+) -> Result<Option<T>, DeserializeError>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    let tag: u8 = de.read_byte()?;
+    if tag == none_tag {
+        Ok(None)
+    } else {
+        Ok(Some(de.read_value::<F, T>(true)?))
+    }
+}
+
+#[inline(always)]
+fn read_option_in_place<'de, F, T>(
+    place: &mut Option<T>,
+    mut de: Deserializer<'de>,
+    none_tag: u8,
+) -> Result<(), DeserializeError>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    let tag: u8 = de.read_byte()?;
+    if tag == none_tag {
+        *place = None;
+    } else {
+        match place {
+            Some(value) => {
+                de.read_in_place::<F, T>(value, true)?;
+            }
+            None => {
+                *place = Some(de.read_value::<F, T>(true)?);
+            }
+        }
+    }
+    Ok(())
+}
+
 impl<F, T> Serialize<Option<F>> for Option<T>
 where
     F: Formula,
     T: Serialize<F>,
 {
     #[inline(always)]
-    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
     where
         B: Buffer,
     {
-        match self {
-            None => write_bytes(&[0u8], sizes, buffer),
-            Some(value) => {
-                write_bytes(&[1u8], sizes, buffer.reborrow())?;
-                write_field::<F, T, _>(value, sizes, buffer, true)
-            }
-        }
+        write_option::<F, T, B>(self, NONE_TAG, sizes, buffer)
     }
 
     #[inline(always)]
     fn size_hint(&self) -> Option<Sizes> {
-        match self {
-            None => {
-                let stack = <Option<F>>::MAX_STACK_SIZE?;
-                Some(Sizes::with_stack(stack))
-            }
-            Some(value) => {
-                let mut sizes = field_size_hint::<F>(value, true)?;
-                sizes.add_stack(1);
-                Some(sizes)
-            }
-        }
+        option_size_hint::<F, T>(self.as_ref())
     }
 }
 
@@ -57,32 +154,16 @@ where
     for<'ser> &'ser T: Serialize<F>,
 {
     #[inline(always)]
-    fn serialize<B>(&self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
     where
         B: Buffer,
     {
-        match self {
-            None => write_bytes(&[0u8], sizes, buffer),
-            Some(value) => {
-                write_bytes(&[1u8], sizes, buffer.reborrow())?;
-                write_field::<F, &T, _>(value, sizes, buffer, true)
-            }
-        }
+        write_option::<F, &T, B>(self.as_ref(), NONE_TAG, sizes, buffer)
     }
 
     #[inline(always)]
     fn size_hint(&self) -> Option<Sizes> {
-        match self {
-            None => {
-                let stack = <Option<F>>::MAX_STACK_SIZE?;
-                Some(Sizes::with_stack(stack))
-            }
-            Some(value) => {
-                let mut sizes = field_size_hint::<F>(&value, true)?;
-                sizes.add_stack(1);
-                Some(sizes)
-            }
-        }
+        option_size_hint::<F, &T>(self.as_ref().as_ref())
     }
 }
 
@@ -92,30 +173,66 @@ where
     T: Deserialize<'de, F>,
 {
     #[inline(always)]
-    fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
-        let is_some: u8 = de.read_byte()?;
-        if is_some == 0 {
-            Ok(None)
-        } else {
-            Ok(Some(de.read_value::<F, T>(true)?))
-        }
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        read_option::<F, T>(de, NONE_TAG)
     }
 
     #[inline(always)]
-    fn deserialize_in_place(&mut self, mut de: Deserializer<'de>) -> Result<(), DeserializeError> {
-        let is_some: u8 = de.read_byte()?;
-        if is_some == 0 {
-            *self = None;
-        } else {
-            match self {
-                Some(value) => {
-                    de.read_in_place::<F, T>(value, true)?;
-                }
-                None => {
-                    *self = Some(de.read_value::<F, T>(true)?);
-                }
-            }
-        }
-        Ok(())
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        read_option_in_place::<F, T>(self, de, NONE_TAG)
+    }
+}
+
+impl<F, T> Serialize<OptionReversed<F>> for Option<T>
+where
+    F: Formula,
+    T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_option::<F, T, B>(self, NONE_TAG_REVERSED, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        option_size_hint::<F, T>(self.as_ref())
+    }
+}
+
+impl<F, T> SerializeRef<OptionReversed<F>> for Option<T>
+where
+    F: Formula,
+    for<'ser> &'ser T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_option::<F, &T, B>(self.as_ref(), NONE_TAG_REVERSED, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        option_size_hint::<F, &T>(self.as_ref().as_ref())
+    }
+}
+
+impl<'de, F, T> Deserialize<'de, OptionReversed<F>> for Option<T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        read_option::<F, T>(de, NONE_TAG_REVERSED)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        read_option_in_place::<F, T>(self, de, NONE_TAG_REVERSED)
     }
 }
@@ -1,12 +1,18 @@
+use core::{fmt, mem::size_of};
+
 use crate::{
-    advanced::FixedUsizeType,
-    buffer::{Buffer, BufferExhausted, CheckedFixedBuffer, DryBuffer, VecBuffer},
-    deserialize::{read_reference, Deserialize, DeserializeError, Deserializer},
+    buffer::{Buffer, BufferExhausted, CheckedFixedBuffer, DryBuffer},
+    deserialize::{
+        deserialize_with_size, read_reference, Deserialize, DeserializeError, Deserializer,
+    },
     formula::{reference_size, Formula},
-    serialize::{write_ref, write_reference, Serialize, Sizes},
-    size::SIZE_STACK,
+    serialize::{serialize, write_ref, write_reference, Serialize, Sizes},
+    size::{fixed_usize_from_bytes, SIZE_STACK},
 };
 
+#[cfg(feature = "alloc")]
+use crate::buffer::VecBuffer;
+
 /// Returns the number of bytes required to write packet with the value.
 /// Note that value is consumed.
 ///
@@ -116,24 +122,44 @@ where
 /// # Panics
 ///
 /// This function may panic if the value size is too big to fit `usize`.
+/// Use [`try_read_packet_size`] to get an error instead.
 #[must_use]
 #[inline]
 pub fn read_packet_size<F>(input: &[u8]) -> Option<usize>
+where
+    F: Formula + ?Sized,
+{
+    match try_read_packet_size::<F>(input) {
+        Ok(size) => Some(size),
+        Err(DeserializeError::OutOfBounds) => None,
+        Err(_) => panic!("Value size can't fit `usize`"),
+    }
+}
+
+/// Reads size of the packet with value from the input.
+/// Never panics.
+///
+/// # Errors
+///
+/// Returns [`DeserializeError::OutOfBounds`] if the input is too short
+/// to determine the size.
+/// Returns [`DeserializeError::InvalidUsize`] if the value size
+/// is too big to fit `usize`.
+#[inline]
+pub fn try_read_packet_size<F>(input: &[u8]) -> Result<usize, DeserializeError>
 where
     F: Formula + ?Sized,
 {
     match F::MAX_STACK_SIZE {
-        Some(0) => Some(0),
+        Some(0) => Ok(0),
         _ => {
             if input.len() < SIZE_STACK {
-                None
+                Err(DeserializeError::OutOfBounds)
             } else {
                 let mut bytes = [0u8; SIZE_STACK];
                 bytes.copy_from_slice(&input[..SIZE_STACK]);
-                let address = FixedUsizeType::from_le_bytes(bytes)
-                    .try_into()
-                    .expect("Value size can't fit `usize`");
-                Some(address)
+                let size = fixed_usize_from_bytes(bytes);
+                usize::try_from(size).map_err(|_| DeserializeError::InvalidUsize(size))
             }
         }
     }
@@ -157,7 +183,7 @@ where
         return Err(DeserializeError::OutOfBounds);
     }
 
-    let (address, size) = read_reference::<F>(input, input.len() - reference_size);
+    let (address, size) = read_reference::<F>(input, input.len() - reference_size)?;
 
     if size > address {
         return Err(DeserializeError::WrongAddress);
@@ -195,7 +221,7 @@ where
         return Err(DeserializeError::OutOfBounds);
     }
 
-    let (address, size) = read_reference::<F>(input, input.len() - reference_size);
+    let (address, size) = read_reference::<F>(input, input.len() - reference_size)?;
 
     if size > address {
         return Err(DeserializeError::WrongAddress);
@@ -210,3 +236,216 @@ where
 
     Ok(address)
 }
+
+/// Encoding of sizes in the header of packets written with
+/// [`write_packet_with`] and read with [`read_packet_with`].
+///
+/// Implemented for `u8`, `u16`, `u32` and `u64`
+/// that encode sizes as little-endian integers of that width,
+/// and for [`Leb128`].
+pub trait PacketPrefix {
+    /// Returns number of bytes required to encode the size.
+    /// Returns `None` if the size does not fit the encoding.
+    fn prefix_len(size: usize) -> Option<usize>;
+
+    /// Encodes the size at the beginning of the output.
+    /// Size must be accepted by [`prefix_len`](PacketPrefix::prefix_len)
+    /// and output must be at least that many bytes long.
+    fn write_prefix(size: usize, output: &mut [u8]);
+
+    /// Decodes the size from the beginning of the input.
+    /// Returns the size and number of bytes consumed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DeserializeError::OutOfBounds`] if the input is too short.
+    /// Returns [`DeserializeError::IntegerOverflow`] if the size
+    /// does not fit `usize`.
+    fn read_prefix(input: &[u8]) -> Result<(usize, usize), DeserializeError>;
+}
+
+macro_rules! impl_packet_prefix {
+    ($($ty:ident)*) => {$(
+        impl PacketPrefix for $ty {
+            #[inline(always)]
+            fn prefix_len(size: usize) -> Option<usize> {
+                match $ty::try_from(size) {
+                    Ok(_) => Some(size_of::<$ty>()),
+                    Err(_) => None,
+                }
+            }
+
+            #[inline(always)]
+            fn write_prefix(size: usize, output: &mut [u8]) {
+                let size = $ty::try_from(size).expect("Size checked by `prefix_len`");
+                output[..size_of::<$ty>()].copy_from_slice(&size.to_le_bytes());
+            }
+
+            #[inline(always)]
+            fn read_prefix(input: &[u8]) -> Result<(usize, usize), DeserializeError> {
+                if input.len() < size_of::<$ty>() {
+                    return Err(DeserializeError::OutOfBounds);
+                }
+                let mut bytes = [0u8; size_of::<$ty>()];
+                bytes.copy_from_slice(&input[..size_of::<$ty>()]);
+                match usize::try_from($ty::from_le_bytes(bytes)) {
+                    Ok(size) => Ok((size, size_of::<$ty>())),
+                    Err(_) => Err(DeserializeError::IntegerOverflow),
+                }
+            }
+        }
+    )*};
+}
+
+impl_packet_prefix!(u8 u16 u32 u64);
+
+/// Encoding of packet sizes as unsigned LEB128.
+///
+/// Each byte holds 7 bits of the size starting from the least significant ones
+/// and has the high bit set if more bytes follow.
+/// Sizes below 128 take a single byte.
+///
+/// This is not the encoding of [`Vlq`](crate::Vlq) formula,
+/// which stores length of the value in the header byte
+/// and can't be decoded from the front.
+pub struct Leb128;
+
+impl PacketPrefix for Leb128 {
+    #[inline(always)]
+    fn prefix_len(size: usize) -> Option<usize> {
+        let bits = usize::BITS - size.leading_zeros();
+        Some((bits.max(1) as usize).div_ceil(7))
+    }
+
+    #[inline(always)]
+    fn write_prefix(mut size: usize, output: &mut [u8]) {
+        let mut at = 0;
+        while size >= 0x80 {
+            output[at] = (size as u8) | 0x80;
+            size >>= 7;
+            at += 1;
+        }
+        output[at] = size as u8;
+    }
+
+    #[inline(always)]
+    fn read_prefix(input: &[u8]) -> Result<(usize, usize), DeserializeError> {
+        let mut size = 0usize;
+        for (idx, &byte) in input.iter().enumerate() {
+            let shift = idx as u32 * 7;
+            let bits = usize::from(byte & 0x7F);
+            if shift >= usize::BITS || (bits << shift) >> shift != bits {
+                return Err(DeserializeError::IntegerOverflow);
+            }
+            size |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok((size, idx + 1));
+            }
+        }
+        Err(DeserializeError::OutOfBounds)
+    }
+}
+
+/// Error of [`write_packet_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WritePacketError {
+    /// Output buffer is too small to fit the packet.
+    BufferExhausted,
+
+    /// Packet is too large for the size prefix encoding.
+    TooLarge,
+}
+
+impl From<BufferExhausted> for WritePacketError {
+    #[inline(always)]
+    fn from(BufferExhausted: BufferExhausted) -> Self {
+        WritePacketError::BufferExhausted
+    }
+}
+
+impl fmt::Display for WritePacketError {
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WritePacketError::BufferExhausted => write!(f, "buffer exhausted"),
+            WritePacketError::TooLarge => write!(f, "packet is too large for size prefix"),
+        }
+    }
+}
+
+/// Writes packet with the value into bytes slice,
+/// encoding packet header with `W`.
+/// Returns the number of bytes written.
+///
+/// Unlike [`write_packet`], the header contains size of the serialized value
+/// and size of its root encoded with `W`, e.g. `u8` or [`Leb128`],
+/// which is more compact for small packets.
+/// Read such packets with [`read_packet_with`] using the same `W`.
+///
+/// The format is deliberately different from [`write_packet`]
+/// and no `W` reproduces its header.
+/// [`write_packet`] header is a reference to the value,
+/// and addresses in the value are counted from the start of the packet.
+/// Here the value is serialized on its own, so its addresses
+/// are counted from the end of the header,
+/// and the header can be resized after the value is written.
+/// Packets of one format can't be read by functions of the other.
+///
+/// # Errors
+///
+/// Returns [`WritePacketError::BufferExhausted`] if the buffer is too small.
+/// Returns [`WritePacketError::TooLarge`] if the packet is too large
+/// for the `W` encoding.
+#[inline]
+pub fn write_packet_with<W, F, T>(value: T, output: &mut [u8]) -> Result<usize, WritePacketError>
+where
+    W: PacketPrefix + ?Sized,
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    let (size, stack) = serialize::<F, T>(value, output)?;
+    let (Some(size_len), Some(stack_len)) = (W::prefix_len(size), W::prefix_len(stack)) else {
+        return Err(WritePacketError::TooLarge);
+    };
+    let header = size_len + stack_len;
+
+    if output.len() - size < header {
+        return Err(WritePacketError::BufferExhausted);
+    }
+
+    output.copy_within(..size, header);
+    W::write_prefix(size, output);
+    W::write_prefix(stack, &mut output[size_len..]);
+    Ok(header + size)
+}
+
+/// Reads packet written with [`write_packet_with`] from the input.
+/// Returns deserialized value and number of bytes consumed.
+///
+/// Packets written with [`write_packet`] use different format
+/// and must be read with [`read_packet`].
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[inline]
+pub fn read_packet_with<'de, W, F, T>(input: &'de [u8]) -> Result<(T, usize), DeserializeError>
+where
+    W: PacketPrefix + ?Sized,
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    let (size, size_len) = W::read_prefix(input)?;
+    let (stack, stack_len) = W::read_prefix(&input[size_len..])?;
+    let header = size_len + stack_len;
+
+    if input.len() - header < size {
+        return Err(DeserializeError::OutOfBounds);
+    }
+    if stack > size {
+        return Err(DeserializeError::WrongLength);
+    }
+
+    let value = deserialize_with_size::<F, T>(&input[header..][..size], stack)?;
+    Ok((value, header + size))
+}
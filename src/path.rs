@@ -0,0 +1,222 @@
+//! Serialization of file system paths.
+//!
+//! Paths are serialized with [`Bytes`] formula as encoded bytes of the `OsStr`.
+//! Encoding of `OsStr` is platform-specific.
+//! On Unix paths are arbitrary bytes and are restored as-is.
+//! On other platforms only UTF-8 paths can be deserialized,
+//! other paths fail with [`DeserializeError::NonUtf8`].
+//!
+//! Use [`PathUtf8`] formula to require UTF-8 on all platforms.
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    buffer::Buffer,
+    bytes::Bytes,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+};
+
+/// A formula for file system paths encoded as UTF-8 string.
+///
+/// Same layout as `str`.
+/// Use this formula when paths are exchanged between platforms.
+///
+/// Serializable from `Path` and `PathBuf`.
+/// Deserializable into `&Path` and `PathBuf`.
+///
+/// # Panics
+///
+/// Serialization panics if path is not valid UTF-8.
+/// Size hint is `None` for such paths.
+/// Use [`Path::to_str`] to check beforehand.
+pub struct PathUtf8;
+
+impl Formula for PathUtf8 {
+    const MAX_STACK_SIZE: Option<usize> = <str as Formula>::MAX_STACK_SIZE;
+    const EXACT_SIZE: bool = <str as Formula>::EXACT_SIZE;
+    const HEAPLESS: bool = <str as Formula>::HEAPLESS;
+}
+
+impl BareFormula for PathUtf8 {}
+
+#[inline(always)]
+fn path_str(path: &Path) -> &str {
+    match path.to_str() {
+        Some(s) => s,
+        None => panic!("Path {} is not valid UTF-8", path.display()),
+    }
+}
+
+#[inline(always)]
+fn path_from_bytes(bytes: &[u8]) -> Result<&Path, DeserializeError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(Path::new(OsStr::from_bytes(bytes)))
+    }
+
+    #[cfg(not(unix))]
+    {
+        path_from_utf8(bytes)
+    }
+}
+
+#[inline(always)]
+fn path_from_utf8(bytes: &[u8]) -> Result<&Path, DeserializeError> {
+    match core::str::from_utf8(bytes) {
+        Ok(s) => Ok(Path::new(s)),
+        Err(error) => Err(DeserializeError::NonUtf8(error)),
+    }
+}
+
+impl SerializeRef<Bytes> for Path {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(self.as_os_str().as_encoded_bytes(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.as_os_str().len()))
+    }
+}
+
+impl SerializeRef<Bytes> for PathBuf {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <Path as SerializeRef<Bytes>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <Path as SerializeRef<Bytes>>::size_hint(self)
+    }
+}
+
+impl Serialize<Bytes> for PathBuf {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <Path as SerializeRef<Bytes>>::serialize(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <Path as SerializeRef<Bytes>>::size_hint(self)
+    }
+}
+
+impl<'de, 'fe: 'de> Deserialize<'fe, Bytes> for &'de Path {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        path_from_bytes(de.read_all_bytes())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = path_from_bytes(de.read_all_bytes())?;
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de, Bytes> for PathBuf {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        Ok(path_from_bytes(de.read_all_bytes())?.to_path_buf())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let path = path_from_bytes(de.read_all_bytes())?;
+        self.clear();
+        self.push(path);
+        Ok(())
+    }
+}
+
+impl SerializeRef<PathUtf8> for Path {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(path_str(self).as_bytes(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        // Non-UTF-8 path panics in `serialize`, not here.
+        Some(Sizes::with_stack(self.to_str()?.len()))
+    }
+}
+
+impl SerializeRef<PathUtf8> for PathBuf {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <Path as SerializeRef<PathUtf8>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <Path as SerializeRef<PathUtf8>>::size_hint(self)
+    }
+}
+
+impl Serialize<PathUtf8> for PathBuf {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <Path as SerializeRef<PathUtf8>>::serialize(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <Path as SerializeRef<PathUtf8>>::size_hint(self)
+    }
+}
+
+impl<'de, 'fe: 'de> Deserialize<'fe, PathUtf8> for &'de Path {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        path_from_utf8(de.read_all_bytes())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = path_from_utf8(de.read_all_bytes())?;
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de, PathUtf8> for PathBuf {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        Ok(path_from_utf8(de.read_all_bytes())?.to_path_buf())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let path = path_from_utf8(de.read_all_bytes())?;
+        self.clear();
+        self.push(path);
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+use core::{fmt, marker::PhantomData};
+use std::io::{self, Read, Write};
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    buffer::BufferExhausted,
+    bytes::Bytes,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+    size::{deserialize_usize, SIZE_STACK},
+};
+
+/// Error that can occur when deserializing values with I/O.
+#[derive(Debug)]
+pub enum ReadDeError {
+    /// Reading from the reader or writing to the writer failed.
+    Io(io::Error),
+
+    /// Deserialization of the read bytes failed.
+    Deserialize(DeserializeError),
+}
+
+impl From<io::Error> for ReadDeError {
+    #[inline(always)]
+    fn from(err: io::Error) -> Self {
+        ReadDeError::Io(err)
+    }
+}
+
+impl From<DeserializeError> for ReadDeError {
+    #[inline(always)]
+    fn from(err: DeserializeError) -> Self {
+        ReadDeError::Deserialize(err)
+    }
+}
+
+impl fmt::Display for ReadDeError {
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadDeError::Io(err) => write!(f, "I/O failed: {err}"),
+            ReadDeError::Deserialize(err) => write!(f, "failed to deserialize: {err:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadDeError {
+    #[inline(always)]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadDeError::Io(err) => Some(err),
+            ReadDeError::Deserialize(_) => None,
+        }
+    }
+}
+
+impl From<DeserializeError> for io::Error {
+    #[inline(always)]
+    fn from(err: DeserializeError) -> Self {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("failed to deserialize: {err:?}"),
+        )
+    }
+}
+
+impl From<BufferExhausted> for io::Error {
+    #[inline(always)]
+    fn from(err: BufferExhausted) -> Self {
+        io::Error::new(io::ErrorKind::WriteZero, err.to_string())
+    }
+}
+
+/// Iterator over values deserialized from a reader.
+///
+/// Reads serialized `[F]` slice incrementally, one element at a time,
+/// keeping only a single element in memory.
+///
+/// Only sized and heapless element formulas are supported,
+/// since references require random access to the input.
+///
+/// Elements of a slice are laid out from the end of the input,
+/// so reading the input forward yields elements in reverse order,
+/// last element first.
+#[must_use]
+pub struct ReadDeIter<F: ?Sized, T, R> {
+    reader: R,
+    chunk: Vec<u8>,
+    zero_sized: Option<usize>,
+    marker: PhantomData<fn(&F) -> T>,
+}
+
+impl<F, T, R> ReadDeIter<F, T, R>
+where
+    F: Formula + ?Sized,
+    T: for<'de> Deserialize<'de, F>,
+    R: Read,
+{
+    /// Creates iterator that reads elements from the reader.
+    /// Reader must contain serialized `[F]` value and nothing else.
+    ///
+    /// # Panics
+    ///
+    /// Panics if formula is not sized or not heapless.
+    #[inline]
+    pub fn new(reader: R) -> Self {
+        assert!(F::HEAPLESS, "Formula must be heapless");
+        let Some(max_stack) = F::MAX_STACK_SIZE else {
+            panic!("Formula must be sized");
+        };
+
+        ReadDeIter {
+            reader,
+            chunk: vec![0; max_stack],
+            zero_sized: None,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the underlying reader.
+    #[must_use]
+    #[inline(always)]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Fills the chunk from the reader.
+    /// Returns `false` if the reader is exhausted before the first byte.
+    fn fill_chunk(&mut self) -> Result<bool, io::Error> {
+        let mut filled = 0;
+        while filled < self.chunk.len() {
+            match self.reader.read(&mut self.chunk[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(true)
+    }
+
+    fn next_zero_sized(&mut self) -> Result<Option<T>, ReadDeError> {
+        let count = match self.zero_sized {
+            Some(count) => count,
+            None => {
+                let mut bytes = [0u8; SIZE_STACK];
+                self.reader.read_exact(&mut bytes)?;
+                deserialize_usize(Deserializer::new_unchecked(SIZE_STACK, &bytes))?
+            }
+        };
+
+        if count == 0 {
+            self.zero_sized = Some(0);
+            return Ok(None);
+        }
+        self.zero_sized = Some(count - 1);
+
+        let value = Deserializer::new_unchecked(0, &[]).read_value::<F, T>(false)?;
+        Ok(Some(value))
+    }
+
+    fn next_sized(&mut self) -> Result<Option<T>, ReadDeError> {
+        if !self.fill_chunk()? {
+            return Ok(None);
+        }
+
+        let mut de = Deserializer::new_unchecked(self.chunk.len(), &self.chunk);
+        let value = de.read_value::<F, T>(false)?;
+        Ok(Some(value))
+    }
+}
+
+impl<F, T, R> Iterator for ReadDeIter<F, T, R>
+where
+    F: Formula + ?Sized,
+    T: for<'de> Deserialize<'de, F>,
+    R: Read,
+{
+    type Item = Result<T, ReadDeError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Result<T, ReadDeError>> {
+        let result = if self.chunk.is_empty() {
+            self.next_zero_sized()
+        } else {
+            self.next_sized()
+        };
+        result.transpose()
+    }
+}
+
+/// Size of chunks written by [`read_bytes_to_writer`].
+const WRITE_CHUNK: usize = 64 * 1024;
+
+/// Reads value of [`Bytes`] formula and writes it into the writer
+/// in chunks, without collecting bytes into owned buffer.
+/// Returns number of bytes written.
+///
+/// Use with deserializer positioned at `Bytes` value,
+/// e.g. one produced by [`Deserializer::deref`] for `Ref<Bytes>`.
+///
+/// # Errors
+///
+/// Returns `ReadDeError::Io` if writing fails.
+#[inline]
+pub fn read_bytes_to_writer<W>(de: Deserializer<'_>, mut writer: W) -> Result<usize, ReadDeError>
+where
+    W: Write,
+{
+    let bytes = <&[u8] as Deserialize<Bytes>>::deserialize(de)?;
+    for chunk in bytes.chunks(WRITE_CHUNK) {
+        writer.write_all(chunk)?;
+    }
+    Ok(bytes.len())
+}
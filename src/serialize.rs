@@ -2,8 +2,8 @@ use core::{fmt, marker::PhantomData, ops};
 
 use crate::{
     buffer::{Buffer, BufferExhausted, CheckedFixedBuffer, DryBuffer, MaybeFixedBuffer},
-    formula::{unwrap_size, BareFormula, Formula},
-    size::{usize_truncate_unchecked, SIZE_STACK},
+    formula::{reference_size, unwrap_size, BareFormula, Formula},
+    size::{fixed_usize_to_bytes, usize_truncate, SIZE_STACK},
 };
 
 #[cfg(feature = "alloc")]
@@ -23,6 +23,13 @@ impl Sizes {
     /// Zero sizes.
     pub const ZERO: Self = Sizes { heap: 0, stack: 0 };
 
+    /// Create new `Sizes` with specified heap and stack sizes.
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(heap: usize, stack: usize) -> Self {
+        Sizes { heap, stack }
+    }
+
     /// Create new `Sizes` with specified heap size.
     #[must_use]
     #[inline(always)]
@@ -37,21 +44,46 @@ impl Sizes {
         Sizes { heap: 0, stack }
     }
 
+    /// Returns heap size.
+    #[must_use]
+    #[inline(always)]
+    pub const fn heap(&self) -> usize {
+        self.heap
+    }
+
+    /// Returns stack size.
+    #[must_use]
+    #[inline(always)]
+    pub const fn stack(&self) -> usize {
+        self.stack
+    }
+
     /// Adds to the heap size.
     #[inline(always)]
-    pub fn add_heap(&mut self, heap: usize) {
+    pub const fn add_heap(&mut self, heap: usize) {
         self.heap += heap;
     }
 
     /// Adds to the stack size.
     #[inline(always)]
-    pub fn add_stack(&mut self, stack: usize) {
+    pub const fn add_stack(&mut self, stack: usize) {
         self.stack += stack;
     }
 
+    /// Returns sum of two sizes.
+    /// Same as `+` operator, usable in const context.
+    #[must_use]
+    #[inline(always)]
+    pub const fn sum(self, rhs: Self) -> Self {
+        Sizes {
+            heap: self.heap + rhs.heap,
+            stack: self.stack + rhs.stack,
+        }
+    }
+
     /// Moves stack size to heap size.
     #[inline(always)]
-    pub fn to_heap(&mut self, until: usize) -> usize {
+    pub const fn to_heap(&mut self, until: usize) -> usize {
         let len = self.stack - until;
         self.heap += len;
         self.stack = until;
@@ -59,8 +91,9 @@ impl Sizes {
     }
 
     /// Returns total size.
+    #[must_use]
     #[inline(always)]
-    pub fn total(&self) -> usize {
+    pub const fn total(&self) -> usize {
         self.heap + self.stack
     }
 }
@@ -70,10 +103,7 @@ impl ops::Add for Sizes {
 
     #[inline(always)]
     fn add(self, rhs: Self) -> Self {
-        Self {
-            heap: self.heap + rhs.heap,
-            stack: self.stack + rhs.stack,
-        }
+        self.sum(rhs)
     }
 }
 
@@ -306,6 +336,28 @@ where
     }
 }
 
+/// Serializes through shared reference.
+impl<F, T> Serialize<F> for &mut T
+where
+    F: BareFormula + ?Sized,
+    T: ?Sized,
+    for<'a> &'a T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        Self: Sized,
+        B: Buffer,
+    {
+        <&T as Serialize<F>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <&T as Serialize<F>>::size_hint(&&**self)
+    }
+}
+
 /// Serialize value into buffer.
 /// Returns total number of bytes written and size of the root value.
 /// The buffer type controls bytes writing and failing strategy.
@@ -480,6 +532,11 @@ pub fn field_size_hint<F: Formula + ?Sized>(
 /// # Errors
 ///
 /// Returns error if buffer write fails.
+///
+/// # Panics
+///
+/// Panics if `size` or `address` does not fit into `FixedUsizeType`,
+/// that is value is too large for the width selected by `fixed*` features.
 #[inline]
 pub fn write_reference<F, B>(
     size: usize,
@@ -492,19 +549,56 @@ where
     F: Formula + ?Sized,
     B: Buffer,
 {
-    let address = usize_truncate_unchecked(address);
-    let size = usize_truncate_unchecked(size);
+    let address = usize_truncate(address);
+    let size = usize_truncate(size);
 
     if F::EXACT_SIZE {
-        debug_assert_eq!(size, usize_truncate_unchecked(F::MAX_STACK_SIZE.unwrap()));
-        buffer.write_stack(heap, stack, &address.to_le_bytes())?;
+        debug_assert_eq!(size, usize_truncate(F::MAX_STACK_SIZE.unwrap()));
+        buffer.write_stack(heap, stack, &fixed_usize_to_bytes(address))?;
     } else {
-        buffer.write_stack(heap, stack, &size.to_le_bytes())?;
-        buffer.write_stack(heap, stack + SIZE_STACK, &address.to_le_bytes())?;
+        buffer.write_stack(heap, stack, &fixed_usize_to_bytes(size))?;
+        buffer.write_stack(heap, stack + SIZE_STACK, &fixed_usize_to_bytes(address))?;
     }
     Ok(())
 }
 
+/// Writes pre-serialized value to the heap and reference to it into the stack.
+/// Allows embedding cached values without re-serializing them.
+///
+/// `bytes` must be serialized value of formula `F`,
+/// e.g. produced by [`serialize`] with the same formula.
+/// Addresses in serialized data are absolute, so `F` must be heapless,
+/// otherwise references inside the copied value would be broken.
+///
+/// Written reference is read as [`Ref<F>`](crate::Ref) field.
+/// Use [`Deserializer::read_raw_reference`](crate::advanced::Deserializer::read_raw_reference)
+/// to read bytes back.
+///
+/// # Errors
+///
+/// Returns error if buffer write fails.
+#[inline]
+pub fn write_raw_reference<F, B>(
+    bytes: &[u8],
+    sizes: &mut Sizes,
+    mut buffer: B,
+) -> Result<(), B::Error>
+where
+    F: Formula + ?Sized,
+    B: Buffer,
+{
+    debug_assert!(F::HEAPLESS, "Only heapless values can be copied as-is");
+
+    let old_stack = sizes.stack;
+    write_bytes(bytes, sizes, buffer.reborrow())?;
+    let len = sizes.to_heap(old_stack);
+    buffer.move_to_heap(sizes.heap - len, sizes.stack + len, len);
+
+    write_reference::<F, B>(len, sizes.heap, sizes.heap, sizes.stack, buffer)?;
+    sizes.stack += reference_size::<F>();
+    Ok(())
+}
+
 /// Writes field value into the buffer.
 ///
 /// Use in [`Serialize::serialize`](Serialize::serialize) implementation.
@@ -512,6 +606,10 @@ where
 /// # Errors
 ///
 /// Returns error if buffer write fails.
+///
+/// # Panics
+///
+/// Panics if size of unsized field does not fit into `FixedUsizeType`.
 #[inline]
 pub fn write_field<F, T, B>(
     value: T,
@@ -534,8 +632,12 @@ where
 
     match (F::MAX_STACK_SIZE, F::EXACT_SIZE, last) {
         (None, _, false) => {
-            let size = usize_truncate_unchecked(sizes.stack - old_stack);
-            let res = buffer.write_stack(sizes.heap, old_stack - SIZE_STACK, &size.to_le_bytes());
+            let size = usize_truncate(sizes.stack - old_stack);
+            let res = buffer.write_stack(
+                sizes.heap,
+                old_stack - SIZE_STACK,
+                &fixed_usize_to_bytes(size),
+            );
             if res.is_err() {
                 unreachable!("Successfully written before");
             };
@@ -814,3 +916,77 @@ where
         _ => None,
     }
 }
+
+/// Returns size of a single sequence element of the formula
+/// if it is known at compile time.
+///
+/// Elements of sequences are padded to the formula's maximum stack size,
+/// so every heapless sized formula has fixed element size,
+/// even if it is not exact size.
+///
+/// Use in [`Serialize::size_hint`](Serialize::size_hint) implementation
+/// of container formulas to short-circuit sizing.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::{*, advanced::*};
+/// # use core::marker::PhantomData;
+/// /// Formula for ring buffer of `N` elements, oldest first.
+/// /// Has the same layout as `[F; N]`.
+/// struct RingFormula<F, const N: usize>(PhantomData<F>);
+///
+/// impl<F: Formula, const N: usize> Formula for RingFormula<F, N> {
+///     const MAX_STACK_SIZE: Option<usize> = <[F; N] as Formula>::MAX_STACK_SIZE;
+///     const EXACT_SIZE: bool = F::EXACT_SIZE;
+///     const HEAPLESS: bool = F::HEAPLESS;
+/// }
+///
+/// impl<F: Formula, const N: usize> BareFormula for RingFormula<F, N> {}
+///
+/// struct Ring<T, const N: usize> {
+///     items: [T; N],
+///     head: usize,
+/// }
+///
+/// impl<'a, F, T, const N: usize> Serialize<RingFormula<F, N>> for &'a Ring<T, N>
+/// where
+///     F: Formula,
+///     &'a T: Serialize<F>,
+/// {
+///     fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+///     where
+///         B: Buffer,
+///     {
+///         let (newer, older) = self.items.split_at(self.head);
+///         write_array::<F, _, _>(older.iter().chain(newer), sizes, buffer)
+///     }
+///
+///     fn size_hint(&self) -> Option<Sizes> {
+///         let elem = element_fast_sizes::<F>()?;
+///         Some(Sizes::with_stack(elem.stack * N))
+///     }
+/// }
+///
+/// let ring = Ring { items: [4u32, 5, 1, 2, 3], head: 2 };
+/// assert_eq!(
+///     <&Ring<u32, 5> as Serialize<RingFormula<u32, 5>>>::size_hint(&&ring),
+///     Some(Sizes::with_stack(20)),
+/// );
+///
+/// let mut buffer = [0u8; 20];
+/// let (size, root) = serialize::<RingFormula<u32, 5>, _>(&ring, &mut buffer).unwrap();
+/// let items = deserialize_with_size::<[u32; 5], [u32; 5]>(&buffer[..size], root).unwrap();
+/// assert_eq!(items, [1, 2, 3, 4, 5]);
+/// ```
+#[must_use]
+#[inline(always)]
+pub const fn element_fast_sizes<F>() -> Option<Sizes>
+where
+    F: Formula + ?Sized,
+{
+    match (F::HEAPLESS, F::MAX_STACK_SIZE) {
+        (true, Some(max_stack_size)) => Some(Sizes::with_stack(max_stack_size)),
+        _ => None,
+    }
+}
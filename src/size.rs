@@ -45,6 +45,112 @@ cfg_if::cfg_if! {
 
 pub const SIZE_STACK: usize = size_of::<FixedUsizeType>();
 
+cfg_if::cfg_if! {
+    if #[cfg(feature = "big-endian-sizes")] {
+        /// Encodes size or address in the byte order used for serialized data.
+        ///
+        /// Sizes and addresses are little-endian by default
+        /// and big-endian with `big-endian-sizes` feature.
+        #[must_use]
+        #[inline(always)]
+        pub const fn fixed_usize_to_bytes(value: FixedUsizeType) -> [u8; SIZE_STACK] {
+            value.to_be_bytes()
+        }
+
+        /// Decodes size or address in the byte order used for serialized data.
+        ///
+        /// Sizes and addresses are little-endian by default
+        /// and big-endian with `big-endian-sizes` feature.
+        #[must_use]
+        #[inline(always)]
+        pub const fn fixed_usize_from_bytes(bytes: [u8; SIZE_STACK]) -> FixedUsizeType {
+            FixedUsizeType::from_be_bytes(bytes)
+        }
+
+        const fn fixed_isize_to_bytes(value: FixedIsizeType) -> [u8; SIZE_STACK] {
+            value.to_be_bytes()
+        }
+
+        const fn fixed_isize_from_bytes(bytes: [u8; SIZE_STACK]) -> FixedIsizeType {
+            FixedIsizeType::from_be_bytes(bytes)
+        }
+    } else {
+        /// Encodes size or address in the byte order used for serialized data.
+        ///
+        /// Sizes and addresses are little-endian by default
+        /// and big-endian with `big-endian-sizes` feature.
+        #[must_use]
+        #[inline(always)]
+        pub const fn fixed_usize_to_bytes(value: FixedUsizeType) -> [u8; SIZE_STACK] {
+            value.to_le_bytes()
+        }
+
+        /// Decodes size or address in the byte order used for serialized data.
+        ///
+        /// Sizes and addresses are little-endian by default
+        /// and big-endian with `big-endian-sizes` feature.
+        #[must_use]
+        #[inline(always)]
+        pub const fn fixed_usize_from_bytes(bytes: [u8; SIZE_STACK]) -> FixedUsizeType {
+            FixedUsizeType::from_le_bytes(bytes)
+        }
+
+        const fn fixed_isize_to_bytes(value: FixedIsizeType) -> [u8; SIZE_STACK] {
+            value.to_le_bytes()
+        }
+
+        const fn fixed_isize_from_bytes(bytes: [u8; SIZE_STACK]) -> FixedIsizeType {
+            FixedIsizeType::from_le_bytes(bytes)
+        }
+    }
+}
+
+/// Number of bits in [`FixedUsizeType`].
+///
+/// Controlled by `fixed8`, `fixed16`, `fixed32` and `fixed64` features.
+pub const FIXED_USIZE_BITS: u32 = FixedUsizeType::BITS;
+
+/// Returns number of bytes used to serialize sizes and addresses.
+///
+/// Data serialized with one width cannot be deserialized
+/// by a build that uses another width.
+/// Compare widths when debugging mismatches between builds.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::advanced::*;
+/// println!("Reference width is {} bytes", reference_width());
+/// assert_eq!(reference_width() * 8, FIXED_USIZE_BITS as usize);
+/// ```
+#[must_use]
+#[inline(always)]
+pub const fn reference_width() -> usize {
+    SIZE_STACK
+}
+
+/// Converts size or address to `FixedUsizeType`.
+///
+/// # Panics
+///
+/// Panics if value does not fit into `FixedUsizeType`.
+#[inline(always)]
+pub fn usize_truncate(value: usize) -> FixedUsizeType {
+    match FixedUsizeType::try_from(value) {
+        Ok(value) => value,
+        Err(_) => size_overflow(value),
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn size_overflow(value: usize) -> ! {
+    panic!(
+        "Size {} does not fit into {} bits, enable wider `fixed*` feature",
+        value, FIXED_USIZE_BITS
+    )
+}
+
 pub fn usize_truncate_unchecked(value: usize) -> FixedUsizeType {
     debug_assert!(FixedUsizeType::try_from(value).is_ok());
     value as FixedUsizeType
@@ -163,7 +269,7 @@ where
     B: Buffer,
 {
     write_bytes(
-        &usize_truncate_unchecked(value).to_le_bytes(),
+        &fixed_usize_to_bytes(usize_truncate_unchecked(value)),
         sizes,
         buffer,
     )
@@ -175,7 +281,7 @@ where
     B: Buffer,
 {
     write_bytes(
-        &isize_truncate_unchecked(value).to_le_bytes(),
+        &fixed_isize_to_bytes(isize_truncate_unchecked(value)),
         sizes,
         buffer,
     )
@@ -185,7 +291,7 @@ where
 pub fn deserialize_usize(mut de: Deserializer) -> Result<usize, DeserializeError> {
     let input = de.read_byte_array::<{ size_of::<FixedUsizeType>() }>()?;
     // de.finish()?;
-    let value = <FixedUsizeType>::from_le_bytes(input);
+    let value = fixed_usize_from_bytes(input);
 
     #[cfg(debug_assertions)]
     if usize::try_from(value).is_err() {
@@ -199,7 +305,7 @@ pub fn deserialize_usize(mut de: Deserializer) -> Result<usize, DeserializeError
 pub fn deserialize_isize(mut de: Deserializer) -> Result<isize, DeserializeError> {
     let input = de.read_byte_array::<{ size_of::<FixedIsizeType>() }>()?;
     // de.finish()?;
-    let value = <FixedIsizeType>::from_le_bytes(input);
+    let value = fixed_isize_from_bytes(input);
 
     #[cfg(debug_assertions)]
     if usize::try_from(value).is_err() {
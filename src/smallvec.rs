@@ -0,0 +1,72 @@
+use smallvec::SmallVec;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+    iter::{deserialize_extend_iter, owned_iter_fast_sizes, ref_iter_fast_sizes},
+    serialize::{write_slice, Serialize, SerializeRef, Sizes},
+};
+
+impl<F, T, const N: usize> Serialize<[F]> for SmallVec<[T; N]>
+where
+    F: Formula,
+    T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice(self.into_iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        ref_iter_fast_sizes::<F, _, _>(self.iter())
+    }
+}
+
+impl<F, T, const N: usize> SerializeRef<[F]> for SmallVec<[T; N]>
+where
+    F: Formula,
+    for<'ser> &'ser T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice(self.iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        owned_iter_fast_sizes::<F, _, _>(self.iter())
+    }
+}
+
+/// Elements are stored inline when there are no more than `N` of them.
+impl<'de, F, T, const N: usize> Deserialize<'de, [F]> for SmallVec<[T; N]>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let iter = de.into_unsized_iter();
+        let (lower, _) = Iterator::size_hint(&iter);
+        let mut vec = SmallVec::with_capacity(lower);
+        deserialize_extend_iter(&mut vec, iter)?;
+        Ok(vec)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        self.clear();
+        let iter = de.into_unsized_iter();
+        let (lower, _) = Iterator::size_hint(&iter);
+        self.reserve(lower);
+        deserialize_extend_iter(self, iter)
+    }
+}
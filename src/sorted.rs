@@ -0,0 +1,114 @@
+use core::marker::PhantomData;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{
+    buffer::Buffer,
+    formula::{BareFormula, Formula},
+    serialize::{Serialize, Sizes},
+};
+
+#[cfg(feature = "alloc")]
+use crate::deserialize::{Deserialize, DeserializeError, Deserializer};
+
+/// Formula for slices with elements in ascending order.
+///
+/// Same layout as `[F]`.
+/// Useful for canonical encodings, e.g. for content-addressing
+/// or sets represented as slices.
+///
+/// Serializable from `&[T]`, asserting in debug that it is sorted,
+/// and from `Vec<T>` that is sorted before serialization.
+/// Deserializable into `Vec<T>`, validating that elements are sorted.
+/// Out-of-order elements fail deserialization with
+/// [`DeserializeError::Incompatible`](crate::DeserializeError::Incompatible).
+///
+/// The formula is sized the same as `[F]`.
+/// Use `Ref<Sorted<F>>` to store the slice behind a fixed size reference.
+pub struct Sorted<F> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Formula for Sorted<F>
+where
+    F: Formula,
+{
+    const MAX_STACK_SIZE: Option<usize> = <[F] as Formula>::MAX_STACK_SIZE;
+    const EXACT_SIZE: bool = <[F] as Formula>::EXACT_SIZE;
+    const HEAPLESS: bool = <[F] as Formula>::HEAPLESS;
+}
+
+impl<F> BareFormula for Sorted<F> where F: Formula {}
+
+#[inline(always)]
+fn is_sorted<T: PartialOrd>(slice: &[T]) -> bool {
+    slice.windows(2).all(|pair| pair[0] <= pair[1])
+}
+
+impl<'ser, F, T> Serialize<Sorted<F>> for &'ser [T]
+where
+    F: Formula,
+    T: PartialOrd,
+    &'ser T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        debug_assert!(is_sorted(self), "Slice must be sorted");
+        <Self as Serialize<[F]>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <Self as Serialize<[F]>>::size_hint(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<F, T> Serialize<Sorted<F>> for Vec<T>
+where
+    F: Formula,
+    T: Ord + Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(mut self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        self.sort();
+        <Self as Serialize<[F]>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <Self as Serialize<[F]>>::size_hint(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, F, T> Deserialize<'de, Sorted<F>> for Vec<T>
+where
+    F: Formula,
+    T: Deserialize<'de, F> + PartialOrd,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let vec = <Self as Deserialize<'de, [F]>>::deserialize(de)?;
+        if !is_sorted(&vec) {
+            return Err(DeserializeError::Incompatible);
+        }
+        Ok(vec)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        <Self as Deserialize<'de, [F]>>::deserialize_in_place(self, de)?;
+        if !is_sorted(self) {
+            return Err(DeserializeError::Incompatible);
+        }
+        Ok(())
+    }
+}
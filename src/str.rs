@@ -2,7 +2,8 @@ use crate::{
     buffer::Buffer,
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{BareFormula, Formula},
-    serialize::{write_bytes, SerializeRef, Sizes},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+    vlq::Vlq,
 };
 
 impl Formula for str {
@@ -56,3 +57,290 @@ impl<'de, 'fe: 'de> Deserialize<'fe, str> for &'de str {
         }
     }
 }
+
+/// Formula for UTF-8 string prefixed with [`Vlq`] encoded length.
+///
+/// Unlike `str` formula, the value is self-delimiting.
+/// Short strings spend a single byte on the length,
+/// where fixed-size prefix would take `SIZE_STACK` bytes.
+///
+/// Serializable from `str` and `String`.
+/// Deserializable into `&str` and `String`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 16];
+/// let (size, _) = serialize::<VlqStr, _>("hello", &mut buffer).unwrap();
+/// assert_eq!(size, 6);
+/// let value = deserialize::<VlqStr, &str>(&buffer[..size]).unwrap();
+/// assert_eq!(value, "hello");
+/// ```
+pub struct VlqStr;
+
+impl Formula for VlqStr {
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+impl BareFormula for VlqStr {}
+
+#[inline(always)]
+pub(crate) fn write_vlq_str<B>(s: &str, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    <usize as Serialize<Vlq>>::serialize(s.len(), sizes, buffer.reborrow())?;
+    write_bytes(s.as_bytes(), sizes, buffer)
+}
+
+#[inline(always)]
+pub(crate) fn vlq_str_size_hint(s: &str) -> Option<Sizes> {
+    let mut sizes = <usize as Serialize<Vlq>>::size_hint(&s.len())?;
+    sizes.add_stack(s.len());
+    Some(sizes)
+}
+
+impl SerializeRef<VlqStr> for str {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_vlq_str(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        vlq_str_size_hint(self)
+    }
+}
+
+impl<'de, 'fe: 'de> Deserialize<'fe, VlqStr> for &'de str {
+    #[inline(always)]
+    fn deserialize(mut deserializer: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        deserializer.read_vlq_str()
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(
+        &mut self,
+        mut deserializer: Deserializer<'fe>,
+    ) -> Result<(), DeserializeError> {
+        *self = deserializer.read_vlq_str()?;
+        Ok(())
+    }
+}
+
+/// Formula for strings that consist of ASCII characters only.
+///
+/// Same layout as `str`.
+/// Serialization debug-asserts that the string is ASCII.
+/// Deserialization fails with [`DeserializeError::Incompatible`]
+/// if any byte is not ASCII.
+///
+/// Serializable from `str` and `String`.
+/// Deserializable into `&str` and `String`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 16];
+/// let (size, _) = serialize::<Ascii, _>("GET", &mut buffer).unwrap();
+/// let value = deserialize::<Ascii, &str>(&buffer[..size]).unwrap();
+/// assert_eq!(value, "GET");
+/// ```
+pub struct Ascii;
+
+impl Formula for Ascii {
+    const MAX_STACK_SIZE: Option<usize> = <str as Formula>::MAX_STACK_SIZE;
+    const EXACT_SIZE: bool = <str as Formula>::EXACT_SIZE;
+    const HEAPLESS: bool = <str as Formula>::HEAPLESS;
+}
+
+impl BareFormula for Ascii {}
+
+#[inline(always)]
+pub(crate) fn write_ascii<B>(s: &str, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    debug_assert!(s.is_ascii(), "String must be ASCII");
+    write_bytes(s.as_bytes(), sizes, buffer)
+}
+
+#[inline(always)]
+pub(crate) fn read_ascii<'de>(
+    deserializer: Deserializer<'de>,
+) -> Result<&'de str, DeserializeError> {
+    let bytes = deserializer.read_all_bytes();
+    if !bytes.is_ascii() {
+        return Err(DeserializeError::Incompatible);
+    }
+    match core::str::from_utf8(bytes) {
+        Ok(s) => Ok(s),
+        Err(error) => Err(DeserializeError::NonUtf8(error)),
+    }
+}
+
+impl SerializeRef<Ascii> for str {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_ascii(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len()))
+    }
+}
+
+impl<'de, 'fe: 'de> Deserialize<'fe, Ascii> for &'de str {
+    #[inline(always)]
+    fn deserialize(deserializer: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        read_ascii(deserializer)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(
+        &mut self,
+        deserializer: Deserializer<'fe>,
+    ) -> Result<(), DeserializeError> {
+        *self = read_ascii(deserializer)?;
+        Ok(())
+    }
+}
+
+/// Formula for UTF-8 string prefixed with `W`-byte length.
+///
+/// `W` must be 1, 2 or 4.
+/// Length is encoded in little-endian order.
+/// Unlike [`VlqStr`] the overhead is fixed and known in advance,
+/// unlike `str` the value is self-delimiting.
+///
+/// Deserialization fails with [`DeserializeError::WrongLength`]
+/// if input is shorter than the encoded length.
+///
+/// Serializable from `str` and `String`.
+/// Deserializable into `&str` and `String`.
+///
+/// The formula is unsized, so fields and elements holding it
+/// are prefixed with their size.
+/// Use `Ref<Str<W>>` to store the string behind a fixed size reference.
+///
+/// # Panics
+///
+/// Serialization panics if string length does not fit into `W` bytes.
+/// Size hint is `None` for such strings.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 16];
+/// let (size, _) = serialize::<Str<1>, _>("hello", &mut buffer).unwrap();
+/// assert_eq!(size, 6);
+/// let value = deserialize::<Str<1>, &str>(&buffer[..size]).unwrap();
+/// assert_eq!(value, "hello");
+/// ```
+pub struct Str<const W: usize>;
+
+impl<const W: usize> Formula for Str<W> {
+    const MAX_STACK_SIZE: Option<usize> = {
+        assert!(
+            W == 1 || W == 2 || W == 4,
+            "Str length prefix must be 1, 2 or 4 bytes"
+        );
+        None
+    };
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+impl<const W: usize> BareFormula for Str<W> {}
+
+#[inline(always)]
+fn prefixed_str_fits<const W: usize>(s: &str) -> bool {
+    (s.len() as u64) >> (W * 8) == 0
+}
+
+#[inline(always)]
+pub(crate) fn prefixed_str_size_hint<const W: usize>(s: &str) -> Option<Sizes> {
+    if !prefixed_str_fits::<W>(s) {
+        // Leave the panic to `write_prefixed_str`.
+        return None;
+    }
+    Some(Sizes::with_stack(W + s.len()))
+}
+
+#[inline(always)]
+pub(crate) fn write_prefixed_str<B, const W: usize>(
+    s: &str,
+    sizes: &mut Sizes,
+    mut buffer: B,
+) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    assert!(
+        prefixed_str_fits::<W>(s),
+        "String is too long for {} byte length prefix",
+        W
+    );
+    let len = s.len() as u64;
+    write_bytes(&len.to_le_bytes()[..W], sizes, buffer.reborrow())?;
+    write_bytes(s.as_bytes(), sizes, buffer)
+}
+
+#[inline(always)]
+pub(crate) fn read_prefixed_str<'de, const W: usize>(
+    mut deserializer: Deserializer<'de>,
+) -> Result<&'de str, DeserializeError> {
+    let mut len = [0; 8];
+    len[..W].copy_from_slice(deserializer.read_bytes(W)?);
+    let Ok(len) = usize::try_from(u64::from_le_bytes(len)) else {
+        return Err(DeserializeError::IntegerOverflow);
+    };
+    let bytes = deserializer.read_bytes(len)?;
+    match core::str::from_utf8(bytes) {
+        Ok(s) => Ok(s),
+        Err(error) => Err(DeserializeError::NonUtf8(error)),
+    }
+}
+
+impl<const W: usize> SerializeRef<Str<W>> for str {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_prefixed_str::<B, W>(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        prefixed_str_size_hint::<W>(self)
+    }
+}
+
+impl<'de, 'fe: 'de, const W: usize> Deserialize<'fe, Str<W>> for &'de str {
+    #[inline(always)]
+    fn deserialize(deserializer: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        read_prefixed_str::<W>(deserializer)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(
+        &mut self,
+        deserializer: Deserializer<'fe>,
+    ) -> Result<(), DeserializeError> {
+        *self = read_prefixed_str::<W>(deserializer)?;
+        Ok(())
+    }
+}
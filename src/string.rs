@@ -1,4 +1,8 @@
-use alloc::{borrow::ToOwned, string::String};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    boxed::Box,
+    string::String,
+};
 
 use crate::{
     buffer::Buffer,
@@ -6,6 +10,10 @@ use crate::{
     formula::{reference_size, Formula},
     reference::Ref,
     serialize::{write_bytes, write_ref, write_reference, Serialize, Sizes},
+    str::{
+        prefixed_str_size_hint, read_ascii, read_prefixed_str, vlq_str_size_hint, write_ascii,
+        write_prefixed_str, write_vlq_str, Ascii, Str, VlqStr,
+    },
 };
 
 impl Formula for String {
@@ -85,6 +93,36 @@ impl Serialize<str> for &String {
     }
 }
 
+impl Serialize<str> for Cow<'_, str> {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(self.as_bytes(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len()))
+    }
+}
+
+impl Serialize<str> for &Cow<'_, str> {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(self.as_bytes(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len()))
+    }
+}
+
 impl<'de> Deserialize<'de, str> for String {
     #[inline(always)]
     fn deserialize(deserializer: Deserializer<'de>) -> Result<Self, DeserializeError> {
@@ -103,3 +141,188 @@ impl<'de> Deserialize<'de, str> for String {
         Ok(())
     }
 }
+
+impl<'de, 'fe: 'de> Deserialize<'fe, str> for Cow<'de, str> {
+    #[inline(always)]
+    fn deserialize(deserializer: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        let string = <&str as Deserialize<'fe, str>>::deserialize(deserializer)?;
+        Ok(Cow::Borrowed(string))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(
+        &mut self,
+        deserializer: Deserializer<'fe>,
+    ) -> Result<(), DeserializeError> {
+        match self {
+            Cow::Borrowed(string) => {
+                <&str as Deserialize<'fe, str>>::deserialize_in_place(string, deserializer)
+            }
+            Cow::Owned(string) => {
+                <String as Deserialize<'fe, str>>::deserialize_in_place(string, deserializer)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de, str> for Box<str> {
+    #[inline(always)]
+    fn deserialize(deserializer: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let string = <&str as Deserialize<'de, str>>::deserialize(deserializer)?;
+        Ok(Box::from(string))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(
+        &mut self,
+        deserializer: Deserializer<'de>,
+    ) -> Result<(), DeserializeError> {
+        let string = <&str as Deserialize<'de, str>>::deserialize(deserializer)?;
+        *self = Box::from(string);
+        Ok(())
+    }
+}
+
+impl Serialize<VlqStr> for String {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_vlq_str(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        vlq_str_size_hint(self)
+    }
+}
+
+impl Serialize<VlqStr> for &String {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_vlq_str(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        vlq_str_size_hint(self)
+    }
+}
+
+impl<'de> Deserialize<'de, VlqStr> for String {
+    #[inline(always)]
+    fn deserialize(mut deserializer: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        Ok(deserializer.read_vlq_str()?.to_owned())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(
+        &mut self,
+        mut deserializer: Deserializer<'de>,
+    ) -> Result<(), DeserializeError> {
+        let string = deserializer.read_vlq_str()?;
+        self.clear();
+        self.push_str(string);
+        Ok(())
+    }
+}
+
+impl Serialize<Ascii> for String {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_ascii(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len()))
+    }
+}
+
+impl Serialize<Ascii> for &String {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_ascii(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len()))
+    }
+}
+
+impl<'de> Deserialize<'de, Ascii> for String {
+    #[inline(always)]
+    fn deserialize(deserializer: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        Ok(read_ascii(deserializer)?.to_owned())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(
+        &mut self,
+        deserializer: Deserializer<'de>,
+    ) -> Result<(), DeserializeError> {
+        let string = read_ascii(deserializer)?;
+        self.clear();
+        self.push_str(string);
+        Ok(())
+    }
+}
+
+impl<const W: usize> Serialize<Str<W>> for String {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_prefixed_str::<B, W>(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        prefixed_str_size_hint::<W>(self)
+    }
+}
+
+impl<const W: usize> Serialize<Str<W>> for &String {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_prefixed_str::<B, W>(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        prefixed_str_size_hint::<W>(self)
+    }
+}
+
+impl<'de, const W: usize> Deserialize<'de, Str<W>> for String {
+    #[inline(always)]
+    fn deserialize(deserializer: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        Ok(read_prefixed_str::<W>(deserializer)?.to_owned())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(
+        &mut self,
+        deserializer: Deserializer<'de>,
+    ) -> Result<(), DeserializeError> {
+        let string = read_prefixed_str::<W>(deserializer)?;
+        self.clear();
+        self.push_str(string);
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+use alkahest::alkahest;
+
+#[alkahest(Formula)]
+enum E {
+    A(u8),
+    #[alkahest(tag_value = 0)]
+    B(u16),
+}
+
+fn main() {}
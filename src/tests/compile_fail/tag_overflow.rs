@@ -0,0 +1,10 @@
+use alkahest::alkahest;
+
+#[alkahest(Formula, tag = u8)]
+enum E {
+    A(u8),
+    #[alkahest(tag_value = 256)]
+    B(u16),
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use alkahest::alkahest;
+
+#[alkahest(Formula, bonud = "u32: Copy")]
+struct S {
+    a: u32,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use alkahest::alkahest;
+
+#[alkahest(Formula)]
+enum Message {
+    Ping,
+    Data { id: u32, payload: u64 },
+}
+
+#[alkahest(Serialize<Message @Pnig>)]
+struct PingMessage;
+
+fn main() {}
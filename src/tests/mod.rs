@@ -1,23 +1,34 @@
 #[cfg(all(feature = "alloc", feature = "derive"))]
 mod net;
 
+#[cfg(feature = "alloc")]
+mod roundtrip;
+
 #[cfg(feature = "alloc")]
 use alloc::{collections::VecDeque, vec, vec::Vec};
 
+#[cfg(feature = "alloc")]
+use crate::{iter::SerIter, str::VlqStr};
+
 use crate::{
-    buffer::BufferExhausted,
+    buffer::{Buffer, BufferExhausted, CheckedFixedBuffer},
     bytes::Bytes,
     deserialize::{
-        deserialize, deserialize_in_place_with_size, deserialize_with_size, Deserialize,
+        deserialize, deserialize_in_place, deserialize_in_place_with_size, deserialize_with_size,
+        Deserialize, DeserializeError, Deserializer,
     },
-    formula::Formula,
+    formula::{max_size, reference_layout, repeat_size, sum_size, Formula, ReferenceLayout},
     lazy::Lazy,
+    option::OptionReversed,
+    packet::{read_packet_size, try_read_packet_size, write_packet},
     r#as::As,
     reference::Ref,
-    serialize::{serialize, serialize_or_size, serialized_size, Serialize},
-    vlq::Vlq,
+    serialize::{serialize, serialize_or_size, serialized_size, write_bytes, Serialize, Sizes},
+    size::{fixed_usize_to_bytes, reference_width, FixedUsizeType, SIZE_STACK},
+    vlq::{Vlq, VlqSaturating},
 };
 
+#[allow(clippy::single_match)]
 fn test_type<'a, F, T, D>(value: &T, buffer: &'a mut [u8], eq: impl Fn(&T, &D) -> bool)
 where
     F: Formula + ?Sized,
@@ -164,8 +175,8 @@ fn test_slice() {
 #[test]
 fn test_ref() {
     let mut buffer = [0u8; 256];
-    // test_type::<Ref<()>, (), ()>(&(), &mut buffer, |x, y| x == y);
-    // test_type::<Ref<u32>, u32, u32>(&1, &mut buffer, |x, y| x == y);
+    test_type::<Ref<()>, (), ()>(&(), &mut buffer, |x, y| x == y);
+    test_type::<Ref<u32>, u32, u32>(&1, &mut buffer, |x, y| x == y);
     test_type::<Ref<str>, str, &str>("qwe", &mut buffer, |x, y| x == *y);
 }
 
@@ -188,6 +199,99 @@ fn test_complex_tuple() {
     );
 }
 
+#[test]
+fn test_indirect_tuple() {
+    type Formula = (Ref<u32>, u64, Ref<str>);
+
+    let mut buffer = [0u8; 256];
+    test_type::<Formula, (u32, u64, &str), (u32, u64, &str)>(
+        &(1, 2, "three"),
+        &mut buffer,
+        |x, y| x == y,
+    );
+
+    // Indirect tuples nested behind references and in slices.
+    type Nested = (Ref<Formula>, u8, [Formula]);
+    type Value<'a> = ((u32, u64, &'a str), u8, [(u32, u64, &'a str); 2]);
+
+    let mut buffer = [0u8; 512];
+    test_type::<Nested, Value, Value>(
+        &((4, 5, "six"), 7, [(8, 9, "ten"), (11, 12, "thirteen")]),
+        &mut buffer,
+        |x, y| x == y,
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_array_ref() {
+    fn generic<F, T>(value: T, buffer: &mut [u8]) -> (usize, usize)
+    where
+        F: Formula + ?Sized,
+        T: Serialize<F>,
+    {
+        serialize::<F, T>(value, buffer).unwrap()
+    }
+
+    let mut buffer = [0u8; 64];
+    let array = [1u32, 2, 3];
+
+    let (size, _) = serialize::<[u32; 3], _>(&array, &mut buffer).unwrap();
+    assert_eq!(size, 12);
+    assert_eq!(
+        deserialize::<[u32; 3], [u32; 3]>(&buffer[..size]).unwrap(),
+        array
+    );
+    assert_eq!(generic::<[u32; 3], _>(&array, &mut buffer), (size, size));
+
+    let (size, stack) = serialize::<[u32], _>(&array, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize_with_size::<[u32], [u32; 3]>(&buffer[..size], stack).unwrap(),
+        array
+    );
+    assert_eq!(generic::<[u32], _>(&array, &mut buffer), (size, stack));
+
+    // Elements that borrow.
+    let strings = ["a", "bc"];
+    let (size, _) = generic::<[As<str>; 2], _>(&strings, &mut buffer);
+    assert_eq!(
+        deserialize::<[As<str>; 2], [&str; 2]>(&buffer[..size]).unwrap(),
+        strings
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_array_of_vecs() {
+    let value = [vec![1u32, 2, 3], vec![], vec![4u32]];
+
+    let mut buffer = [0u8; 128];
+    let (size, _) = serialize::<[Vec<u32>; 3], _>(value.clone(), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<[Vec<u32>; 3], [Vec<u32>; 3]>(&buffer[..size]).unwrap(),
+        value
+    );
+
+    // `Vec<F>` formula is laid out as `Ref<[F]>`.
+    let lazy = deserialize::<[Ref<[u32]>; 3], [Lazy<[u32]>; 3]>(&buffer[..size]).unwrap();
+    for (lazy, expected) in lazy.iter().zip(&value) {
+        assert_eq!(lazy.get::<Vec<u32>>().unwrap(), *expected);
+    }
+
+    // Elements with unsized stack are size-prefixed.
+    let (size, _) = serialize::<[As<[u32]>; 3], _>(value.clone(), &mut buffer).unwrap();
+    assert_eq!(<[As<[u32]>; 3] as Formula>::MAX_STACK_SIZE, None);
+    assert_eq!(
+        deserialize::<[As<[u32]>; 3], [Vec<u32>; 3]>(&buffer[..size]).unwrap(),
+        value
+    );
+
+    let lazy = deserialize::<[As<[u32]>; 3], [Lazy<[u32]>; 3]>(&buffer[..size]).unwrap();
+    for (lazy, expected) in lazy.iter().zip(&value) {
+        assert_eq!(lazy.get::<Vec<u32>>().unwrap(), *expected);
+    }
+}
+
 #[cfg(feature = "alloc")]
 #[test]
 fn test_vec() {
@@ -216,6 +320,7 @@ fn test_enums() {
         Bar { c: Vec<u32>, d: Vec<Vec<u32>> },
     }
 
+    #[allow(dead_code)]
     #[alkahest(Deserialize<'a, TestFormula>)]
     enum TestDataLazy<'a> {
         Foo {
@@ -263,10 +368,7 @@ fn test_slice_of_slice() {
 #[test]
 fn test_size() {
     const REFS: usize = 4;
-    const REF_SIZE: usize = cfg!(feature = "fixed8") as usize
-        + cfg!(feature = "fixed16") as usize * 2
-        + cfg!(feature = "fixed32") as usize * 4
-        + cfg!(feature = "fixed64") as usize * 8;
+    const REF_SIZE: usize = reference_width();
 
     const PAYLOAD: usize = 6;
     const SIZE: usize = REFS * REF_SIZE + PAYLOAD;
@@ -278,6 +380,7 @@ fn test_size() {
 
 #[cfg(all(feature = "derive", feature = "alloc"))]
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_packet() {
     use alkahest_proc::alkahest;
     use alloc::{string::String, vec, vec::Vec};
@@ -485,7 +588,7 @@ fn test_zero_sized_arrays() {
     }
 }
 
-#[cfg(feature = "derive")]
+#[cfg(all(feature = "alloc", feature = "derive"))]
 #[test]
 fn test_recursive_types() {
     use alkahest_proc::alkahest;
@@ -559,3 +662,2859 @@ fn test_recursive_types() {
     let c = crate::deserialize_with_size::<A<i32>, C<i32>>(&buffer[..size], root).unwrap();
     assert_eq!(b, c);
 }
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_array_in_place_reuse() {
+    use alloc::string::String;
+
+    let mut buffer = [0u8; 256];
+    let (size, root) = serialize::<[String; 3], _>(["a", "bb", "ccc"], &mut buffer).unwrap();
+
+    let mut place = [
+        String::with_capacity(16),
+        String::with_capacity(16),
+        String::with_capacity(16),
+    ];
+    let ptrs = place.each_ref().map(|s| s.as_ptr());
+
+    deserialize_in_place_with_size::<[String; 3], _>(&mut place, &buffer[..size], root).unwrap();
+
+    assert_eq!(place, ["a", "bb", "ccc"]);
+    assert_eq!(place.each_ref().map(|s| s.as_ptr()), ptrs);
+    assert!(place.iter().all(|s| s.capacity() == 16));
+}
+
+#[test]
+fn test_try_read_trailing() {
+    fn read_trailing(input: &[u8], stack: usize) -> (u32, Option<u32>) {
+        let mut de = Deserializer::new(stack, input).unwrap();
+        let head = de.read_value::<u32, u32>(false).unwrap();
+        let tail = de.try_read(|de| de.read_value::<u32, u32>(false)).ok();
+        (head, tail)
+    }
+
+    let mut buffer = [0u8; 16];
+
+    let (size, root) = serialize::<(u32, u32), _>((1u32, 2u32), &mut buffer).unwrap();
+    assert_eq!(read_trailing(&buffer[..size], root), (1, Some(2)));
+
+    let (size, root) = serialize::<u32, _>(1u32, &mut buffer).unwrap();
+    assert_eq!(read_trailing(&buffer[..size], root), (1, None));
+
+    // Failed speculative read does not advance the deserializer.
+    let (size, root) = serialize::<u32, _>(3u32, &mut buffer).unwrap();
+    let mut de = Deserializer::new(root, &buffer[..size]).unwrap();
+    let err = de.try_read(|de| {
+        de.read_value::<u32, u32>(false)?;
+        de.read_value::<u32, u32>(false)
+    });
+    assert!(matches!(err, Err(DeserializeError::WrongLength)));
+    assert_eq!(de.fork().read_value::<u32, u32>(true).unwrap(), 3);
+    assert_eq!(de.read_value::<u32, u32>(true).unwrap(), 3);
+}
+
+#[test]
+fn test_reference_layout() {
+    assert_eq!(
+        reference_layout::<u32>(),
+        ReferenceLayout {
+            size_bytes: SIZE_STACK,
+            has_len: false,
+        }
+    );
+
+    assert_eq!(
+        reference_layout::<[u32]>(),
+        ReferenceLayout {
+            size_bytes: SIZE_STACK * 2,
+            has_len: true,
+        }
+    );
+
+    // Reference to `[u32]` of 2 elements preceded by 4 bytes of padding.
+    // Stack is written backwards, so the first element is the last one.
+    let mut input = [0u8; 12 + SIZE_STACK * 2];
+    input[4..8].copy_from_slice(&2u32.to_le_bytes());
+    input[8..12].copy_from_slice(&1u32.to_le_bytes());
+    input[12..][..SIZE_STACK].copy_from_slice(&fixed_usize_to_bytes(12));
+    input[12 + SIZE_STACK..].copy_from_slice(&fixed_usize_to_bytes(8));
+
+    let lazy = deserialize::<Ref<[u32]>, Lazy<[u32]>>(&input).unwrap();
+    let mut iter = lazy.iter::<u32>();
+    assert_eq!(iter.next().unwrap().unwrap(), 1);
+    assert_eq!(iter.next().unwrap().unwrap(), 2);
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_size_arithmetic() {
+    const EXACT: Option<usize> = sum_size(Some(4), Some(8));
+    assert_eq!(EXACT, Some(12));
+    assert_eq!(sum_size(Some(4), None), None);
+    assert_eq!(sum_size(None, Some(4)), None);
+
+    assert_eq!(max_size(Some(4), Some(8)), Some(8));
+    assert_eq!(max_size(Some(8), Some(4)), Some(8));
+    assert_eq!(max_size(None, Some(4)), None);
+    assert_eq!(max_size(Some(4), None), None);
+
+    assert_eq!(repeat_size(Some(4), 3), Some(12));
+    assert_eq!(repeat_size(None, 3), None);
+}
+
+crate::assert_formula_layout!(u32, stack = 4, exact, heapless);
+crate::assert_formula_layout!((u8, u16), stack = 3, exact, heapless);
+crate::assert_formula_layout!([u32], stack = None, !exact, heapless);
+crate::assert_formula_layout!(Ref<[u32]>, stack = SIZE_STACK * 2, exact, !heapless);
+crate::assert_formula_layout!(Option<u32>, stack = 5, !exact);
+crate::assert_formula_layout!(Bytes);
+crate::assert_formula_layout!((), stack = 0, exact, heapless);
+crate::assert_formula_layout!(Ref<()>, stack = SIZE_STACK, exact, heapless);
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_custom_bounds() {
+    use alkahest_proc::alkahest;
+
+    #[alkahest(Formula)]
+    struct PairFormula {
+        a: u32,
+        b: u32,
+    }
+
+    // Formula is specified explicitly, so no predicates are generated
+    // for `T` and bounds must be provided.
+    #[derive(Debug, PartialEq)]
+    #[alkahest(
+        serialize_bound = "T: Serialize<u32>",
+        deserialize_bound = "T: Deserialize<'de, u32>",
+        Serialize<PairFormula>,
+        for<'de> Deserialize<'de, PairFormula>
+    )]
+    struct Pair<T> {
+        a: u32,
+        b: T,
+    }
+
+    let mut buffer = [0u8; 16];
+    let size = serialize::<PairFormula, _>(Pair { a: 1, b: 2u32 }, &mut buffer).unwrap();
+    let pair = deserialize::<PairFormula, Pair<u64>>(&buffer[..size.0]).unwrap();
+    assert_eq!(pair, Pair { a: 1, b: 2u64 });
+}
+
+#[test]
+fn test_read_vlq() {
+    let mut buffer = [0u8; 64];
+
+    for value in [0u64, 15, 16, 255, 8573, 70000, u64::from(u32::MAX)] {
+        let (size, root) = serialize::<Vlq, _>(value, &mut buffer).unwrap();
+
+        let mut de = Deserializer::new(root, &buffer[..size]).unwrap();
+        assert_eq!(de.read_vlq::<u64>().unwrap(), value);
+
+        let mut de = Deserializer::new(root, &buffer[..size]).unwrap();
+        assert_eq!(de.read_vlq::<u32>().unwrap(), value as u32);
+    }
+
+    let (size, root) = serialize::<Vlq, _>(u64::MAX, &mut buffer).unwrap();
+    let mut de = Deserializer::new(root, &buffer[..size]).unwrap();
+    assert!(matches!(
+        de.read_vlq::<u32>(),
+        Err(DeserializeError::IntegerOverflow)
+    ));
+
+    let mut de = Deserializer::new(root, &buffer[..size]).unwrap();
+    assert_eq!(de.read_vlq::<u64>().unwrap(), u64::MAX);
+}
+
+#[test]
+fn test_read_bounded_usize() {
+    let mut buffer = [0u8; 16];
+    let (size, root) = serialize::<usize, _>(42usize, &mut buffer).unwrap();
+
+    let mut de = Deserializer::new(root, &buffer[..size]).unwrap();
+    assert_eq!(de.read_bounded_usize(42).unwrap(), 42);
+
+    let mut de = Deserializer::new(root, &buffer[..size]).unwrap();
+    assert!(matches!(
+        de.read_bounded_usize(41),
+        Err(DeserializeError::Incompatible)
+    ));
+}
+
+#[cfg(all(feature = "derive", feature = "alloc"))]
+#[test]
+fn test_bound_override() {
+    use alkahest_proc::alkahest;
+
+    // Inferred predicates for `Vec<Tree<T>>` field would be recursive.
+    #[alkahest(bound = "T: Formula + Serialize<T>", Serialize, Formula where T: Formula)]
+    struct Tree<T> {
+        value: T,
+        children: Vec<Tree<T>>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(for<'de> Deserialize<'de, Tree<u32>>)]
+    struct TreeU32 {
+        value: u32,
+        children: Vec<TreeU32>,
+    }
+
+    let tree = Tree {
+        value: 1u32,
+        children: vec![Tree {
+            value: 2,
+            children: vec![],
+        }],
+    };
+
+    let mut buffer = [0u8; 256];
+    let (size, root) = serialize::<Tree<u32>, _>(tree, &mut buffer).unwrap();
+    let tree = deserialize_with_size::<Tree<u32>, TreeU32>(&buffer[..size], root).unwrap();
+    assert_eq!(
+        tree,
+        TreeU32 {
+            value: 1,
+            children: vec![TreeU32 {
+                value: 2,
+                children: vec![],
+            }],
+        }
+    );
+}
+
+#[test]
+fn test_lazy_lookup() {
+    type Map = [(u32, As<str>)];
+
+    let mut buffer = [0u8; 256];
+    let (size, root) =
+        serialize::<Map, _>([(1u32, "one"), (2, "two"), (3, "three")], &mut buffer).unwrap();
+
+    let map = deserialize_with_size::<Map, Lazy<Map>>(&buffer[..size], root).unwrap();
+    assert_eq!(map.lookup::<u32, &str, u32>(&2).unwrap().unwrap(), "two");
+    assert_eq!(map.lookup::<u32, &str, u32>(&3).unwrap().unwrap(), "three");
+    assert!(map.lookup::<u32, &str, u32>(&4).is_none());
+}
+
+#[test]
+fn test_lazy_ref_slice() {
+    let strings = ["alpha", "", "gamma", "alpha", "epsilon"];
+
+    let mut buffer = [0u8; 256];
+    let (size, root) = serialize::<[Ref<str>], _>(strings, &mut buffer).unwrap();
+
+    let lazy =
+        deserialize_with_size::<[Ref<str>], Lazy<[Ref<str>]>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.len(), strings.len());
+    assert!(!lazy.is_empty());
+
+    for idx in (0..strings.len()).rev() {
+        assert_eq!(lazy.get_at::<&str>(idx).unwrap().unwrap(), strings[idx]);
+    }
+    assert!(lazy.get_at::<&str>(strings.len()).is_none());
+
+    let (size, root) = serialize::<[Ref<str>], _>([""; 0], &mut buffer).unwrap();
+    let lazy =
+        deserialize_with_size::<[Ref<str>], Lazy<[Ref<str>]>>(&buffer[..size], root).unwrap();
+    assert!(lazy.is_empty());
+    assert!(lazy.get_at::<&str>(0).is_none());
+}
+
+#[test]
+fn test_vlq_saturating() {
+    let mut buffer = [0u8; 64];
+
+    let (size, root) = serialize::<VlqSaturating, _>(300u32, &mut buffer).unwrap();
+    let value = deserialize_with_size::<VlqSaturating, u8>(&buffer[..size], root).unwrap();
+    assert_eq!(value, 255);
+
+    let value = deserialize_with_size::<VlqSaturating, u16>(&buffer[..size], root).unwrap();
+    assert_eq!(value, 300);
+
+    // Same encoding as `Vlq`.
+    let err = deserialize_with_size::<Vlq, u8>(&buffer[..size], root).unwrap_err();
+    assert!(matches!(err, DeserializeError::IntegerOverflow));
+
+    let (size, root) = serialize::<VlqSaturating, _>(u128::MAX, &mut buffer).unwrap();
+    let value = deserialize_with_size::<VlqSaturating, u64>(&buffer[..size], root).unwrap();
+    assert_eq!(value, u64::MAX);
+}
+
+#[cfg(all(feature = "derive", feature = "alloc"))]
+#[test]
+fn test_cow_field() {
+    use alkahest_proc::alkahest;
+    use alloc::borrow::Cow;
+
+    #[alkahest(Formula)]
+    struct NameFormula {
+        id: u32,
+        name: Ref<str>,
+    }
+
+    #[alkahest(Serialize<NameFormula>)]
+    struct NameRef<'a> {
+        id: u32,
+        name: &'a str,
+    }
+
+    #[derive(Debug)]
+    #[alkahest(Deserialize<'de, NameFormula>)]
+    struct Name<'de> {
+        id: u32,
+        name: Cow<'de, str>,
+    }
+
+    let mut buffer = [0u8; 64];
+    let (size, root) = serialize::<NameFormula, _>(
+        NameRef {
+            id: 42,
+            name: "alkahest",
+        },
+        &mut buffer,
+    )
+    .unwrap();
+
+    let mut name = deserialize_with_size::<NameFormula, Name>(&buffer[..size], root).unwrap();
+    assert_eq!(name.id, 42);
+    assert!(matches!(name.name, Cow::Borrowed("alkahest")));
+
+    // Owned string is reused in-place.
+    name.name = Cow::Owned(alloc::string::String::with_capacity(16));
+    deserialize_in_place_with_size::<NameFormula, Name>(&mut name, &buffer[..size], root).unwrap();
+    assert!(matches!(&name.name, Cow::Owned(s) if s == "alkahest" && s.capacity() == 16));
+}
+
+#[test]
+fn test_mut_ref() {
+    fn serialize_mut<F, T>(value: &mut T, buffer: &mut [u8]) -> usize
+    where
+        F: Formula + ?Sized,
+        for<'a> &'a mut T: Serialize<F>,
+    {
+        serialize::<F, _>(value, buffer).unwrap().0
+    }
+
+    let mut buffer = [0u8; 16];
+    let mut value = 42u32;
+    let size = serialize_mut::<u32, _>(&mut value, &mut buffer);
+    assert_eq!(deserialize::<u32, u32>(&buffer[..size]).unwrap(), 42);
+
+    let mut pair = (1u8, 2u16);
+    let size = serialize_mut::<(u8, u16), _>(&mut pair, &mut buffer);
+    assert_eq!(
+        deserialize::<(u8, u16), (u8, u16)>(&buffer[..size]).unwrap(),
+        (1, 2)
+    );
+
+    let (size, _) = serialize::<Ref<u32>, _>(&mut value, &mut buffer).unwrap();
+    assert_eq!(deserialize::<Ref<u32>, u32>(&buffer[..size]).unwrap(), 42);
+}
+
+#[test]
+fn test_reverse() {
+    use core::cmp::Reverse;
+
+    let mut buffer = [0u8; 64];
+    test_type::<u32, Reverse<u32>, Reverse<u32>>(&Reverse(5), &mut buffer, |x, y| x == y);
+
+    let (size, root) = serialize::<u32, _>(Reverse(5u32), &mut buffer).unwrap();
+    let value = deserialize_with_size::<u32, u32>(&buffer[..size], root).unwrap();
+    assert_eq!(value, 5);
+}
+
+#[test]
+fn test_ordering() {
+    use core::cmp::Ordering;
+
+    let mut buffer = [0u8; 16];
+    for (ordering, tag) in [
+        (Ordering::Less, 0),
+        (Ordering::Equal, 1),
+        (Ordering::Greater, 2),
+    ] {
+        let (size, _) = serialize::<Ordering, _>(ordering, &mut buffer).unwrap();
+        assert_eq!(&buffer[..size], &[tag]);
+        assert_eq!(
+            deserialize::<Ordering, Ordering>(&buffer[..size]).unwrap(),
+            ordering
+        );
+    }
+
+    let pair = [Ordering::Greater, Ordering::Less];
+    let (size, _) = serialize::<[Ordering; 2], _>(pair, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[0, 2]);
+    assert_eq!(
+        deserialize::<[Ordering; 2], [Ordering; 2]>(&buffer[..size]).unwrap(),
+        pair
+    );
+
+    assert!(matches!(
+        deserialize::<Ordering, Ordering>(&[3]),
+        Err(DeserializeError::WrongVariant { got: 3, count: 3 })
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_tuple_of_refs() {
+    use alloc::string::String;
+
+    let a = 5u32;
+    let b = String::from("qwe");
+
+    let mut buffer = [0u8; 64];
+    let (size, root) = serialize::<(u32, String), _>((&a, &b), &mut buffer).unwrap();
+    let value =
+        deserialize_with_size::<(u32, String), (u32, String)>(&buffer[..size], root).unwrap();
+    assert_eq!(value, (a, b));
+}
+
+#[test]
+fn test_reserve_stack() {
+    let mut buffer = [0u8; 16];
+    let mut sizes = Sizes::ZERO;
+
+    let mut buf = CheckedFixedBuffer::new(&mut buffer);
+    let reservation = buf.reserve_stack(sizes.heap, sizes.stack, 4).unwrap();
+    assert_eq!(reservation.len(), 4);
+    sizes.stack += 4;
+
+    let body = b"hello";
+    write_bytes(body, &mut sizes, buf.reborrow()).unwrap();
+    reservation
+        .fill(buf.reborrow(), &(body.len() as u32).to_le_bytes())
+        .unwrap();
+
+    assert_eq!(sizes.stack, 9);
+    let mut de = Deserializer::new(sizes.stack, &buffer).unwrap();
+    let len = de.read_value::<u32, u32>(false).unwrap();
+    assert_eq!(len, 5);
+    assert_eq!(de.read_bytes(len as usize).unwrap(), body);
+}
+
+#[cfg(all(not(feature = "fixed64"), target_pointer_width = "64"))]
+#[test]
+#[should_panic(expected = "does not fit into")]
+fn test_write_reference_too_large() {
+    use crate::serialize::write_reference;
+
+    let mut buffer = [0u8; 16];
+    let _ = write_reference::<[u8], _>(usize::MAX, 0, 0, 0, &mut buffer[..]);
+}
+
+#[test]
+fn test_try_read_packet_size() {
+    let mut buffer = [0u8; 32];
+    let size = write_packet::<(u32, [u8]), _>((42u32, [1u8, 2, 3]), &mut buffer).unwrap();
+
+    assert_eq!(
+        try_read_packet_size::<(u32, [u8])>(&buffer[..size]).unwrap(),
+        size
+    );
+    assert!(matches!(
+        try_read_packet_size::<(u32, [u8])>(&buffer[..SIZE_STACK - 1]),
+        Err(DeserializeError::OutOfBounds)
+    ));
+    assert_eq!(
+        read_packet_size::<(u32, [u8])>(&buffer[..SIZE_STACK - 1]),
+        None
+    );
+}
+
+#[test]
+fn test_packet_with() {
+    use crate::{read_packet_with, write_packet_with, Leb128};
+
+    let mut buffer = [0u8; 512];
+
+    // Two packets back to back.
+    let first =
+        write_packet_with::<Leb128, (u32, Ref<str>), _>((42u32, "hello"), &mut buffer).unwrap();
+    let second = write_packet_with::<Leb128, [u16], _>([1u16, 2, 3], &mut buffer[first..]).unwrap();
+
+    let (value, consumed) =
+        read_packet_with::<Leb128, (u32, Ref<str>), (u32, &str)>(&buffer[..first + second])
+            .unwrap();
+    assert_eq!(value, (42, "hello"));
+    assert_eq!(consumed, first);
+
+    let (value, consumed) =
+        read_packet_with::<Leb128, [u16], [u16; 3]>(&buffer[first..first + second]).unwrap();
+    assert_eq!(value, [1, 2, 3]);
+    assert_eq!(consumed, second);
+
+    // Header takes two bytes instead of a full reference.
+    let size = write_packet_with::<u8, u32, _>(7u32, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[4, 4, 7, 0, 0, 0]);
+    assert_eq!(
+        read_packet_with::<u8, u32, u32>(&buffer[..size]).unwrap(),
+        (7, size)
+    );
+
+    // Leb128 grows with the packet.
+    let big = [0u8; 200];
+    let size = write_packet_with::<Leb128, Bytes, _>(&big[..], &mut buffer).unwrap();
+    assert_eq!(size, 204);
+    let (bytes, _) = read_packet_with::<Leb128, Bytes, &[u8]>(&buffer[..size]).unwrap();
+    assert_eq!(bytes, &big[..]);
+
+    assert!(matches!(
+        read_packet_with::<u8, u32, u32>(&[4, 4, 7, 0]),
+        Err(DeserializeError::OutOfBounds)
+    ));
+    assert!(matches!(
+        read_packet_with::<Leb128, u32, u32>(&[0x80]),
+        Err(DeserializeError::OutOfBounds)
+    ));
+    assert!(write_packet_with::<u16, u32, _>(7u32, &mut buffer[..5]).is_err());
+}
+
+#[test]
+fn test_packet_with_too_large() {
+    use crate::{write_packet_with, WritePacketError};
+
+    let big = [0u8; 300];
+    let mut buffer = [0u8; 512];
+    assert_eq!(
+        write_packet_with::<u8, Bytes, _>(&big[..], &mut buffer),
+        Err(WritePacketError::TooLarge)
+    );
+    assert_eq!(
+        write_packet_with::<u16, Bytes, _>(&big[..], &mut buffer[..301]),
+        Err(WritePacketError::BufferExhausted)
+    );
+}
+
+#[cfg(all(feature = "fixed64", target_pointer_width = "32", debug_assertions))]
+#[test]
+fn test_oversized_usize_errors() {
+    let input = u64::MAX.to_le_bytes();
+    assert!(matches!(
+        try_read_packet_size::<[u8]>(&input),
+        Err(DeserializeError::InvalidUsize(u64::MAX))
+    ));
+
+    let mut input = [0u8; 16];
+    input[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let de = Deserializer::new(16, &input).unwrap();
+    assert!(matches!(
+        de.deref::<[u8]>(),
+        Err(DeserializeError::InvalidUsize(_))
+    ));
+}
+
+#[test]
+fn test_malformed_sizes_error() {
+    // Reference of all-ones words points far outside of any input.
+    let input = [0xFFu8; 16];
+    let de = Deserializer::new(16, &input).unwrap();
+    assert!(de.deref::<[u8]>().is_err());
+
+    // Vlq header announcing chained length.
+    for header in 0xC0..=0xFF {
+        assert!(matches!(
+            deserialize::<Vlq, u64>(&[header]),
+            Err(DeserializeError::IntegerOverflow)
+        ));
+        assert!(matches!(
+            deserialize::<VlqSaturating, u64>(&[header]),
+            Err(DeserializeError::IntegerOverflow)
+        ));
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_path() {
+    use std::path::{Path, PathBuf};
+
+    use crate::PathUtf8;
+
+    let path = Path::new("assets/textures/stone.png");
+    let mut buffer = [0u8; 64];
+
+    let (size, root) = serialize::<Bytes, _>(path, &mut buffer).unwrap();
+    let de = deserialize_with_size::<Bytes, &Path>(&buffer[..size], root).unwrap();
+    assert_eq!(de, path);
+    let de = deserialize_with_size::<Bytes, PathBuf>(&buffer[..size], root).unwrap();
+    assert_eq!(de, path);
+
+    let (size, root) = serialize::<PathUtf8, _>(path.to_path_buf(), &mut buffer).unwrap();
+    let de = deserialize_with_size::<PathUtf8, &Path>(&buffer[..size], root).unwrap();
+    assert_eq!(de, path);
+    let mut de = PathBuf::from("old/path");
+    deserialize_in_place_with_size::<PathUtf8, PathBuf>(&mut de, &buffer[..size], root).unwrap();
+    assert_eq!(de, path);
+
+    let (size, root) = serialize::<Bytes, _>(&[0x66, 0xff, 0x6f][..], &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize_with_size::<PathUtf8, PathBuf>(&buffer[..size], root),
+        Err(DeserializeError::NonUtf8(_))
+    ));
+}
+
+#[cfg(all(feature = "std", unix))]
+#[test]
+fn test_path_utf8_non_utf8_hint() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::Path};
+
+    use crate::{serialize::SerializeRef, PathUtf8};
+
+    let path = Path::new(OsStr::from_bytes(&[0x66, 0xff, 0x6f]));
+    assert!(<Path as SerializeRef<PathUtf8>>::size_hint(path).is_none());
+    assert!(<_ as Serialize<PathUtf8>>::size_hint(&path.to_path_buf()).is_none());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_read_de_iter() {
+    use std::io::Cursor;
+
+    use crate::{ReadDeError, ReadDeIter};
+
+    let mut buffer = [0u8; 64];
+    let (size, _) = serialize::<[u32], _>([1u32, 2, 3, 4, 5], &mut buffer).unwrap();
+
+    let iter = ReadDeIter::<u32, u32, _>::new(Cursor::new(&buffer[..size]));
+    let mut values = iter.collect::<Result<Vec<u32>, _>>().unwrap();
+    values.reverse();
+    assert_eq!(values, [1, 2, 3, 4, 5]);
+
+    let (size, _) = serialize::<[()], _>([(), (), ()], &mut buffer).unwrap();
+    let iter = ReadDeIter::<(), (), _>::new(Cursor::new(&buffer[..size]));
+    assert_eq!(iter.map(Result::unwrap).count(), 3);
+
+    let mut iter = ReadDeIter::<u32, u32, _>::new(Cursor::new(&buffer[..3]));
+    assert!(matches!(iter.next(), Some(Err(ReadDeError::Io(_)))));
+}
+
+#[test]
+fn test_for_each() {
+    let mut buffer = [0u8; 64];
+    let (size, root) = serialize::<[u32], _>([1u32, 2, 3, 4, 5], &mut buffer).unwrap();
+
+    let mut sum = 0;
+    let de = Deserializer::new(root, &buffer[..size]).unwrap();
+    de.for_each::<u32, u32>(|value| {
+        sum += value;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(sum, 15);
+
+    let mut seen = 0;
+    let de = Deserializer::new(root, &buffer[..size]).unwrap();
+    let result = de.for_each::<u32, u32>(|value| {
+        seen += 1;
+        if value == 3 {
+            return Err(DeserializeError::Incompatible);
+        }
+        Ok(())
+    });
+    assert!(matches!(result, Err(DeserializeError::Incompatible)));
+    assert_eq!(seen, 3);
+}
+
+#[cfg(all(feature = "derive", feature = "alloc"))]
+#[test]
+fn test_field_as_slice() {
+    use alkahest_proc::alkahest;
+
+    /// First `n` even numbers.
+    struct Evens(u32);
+
+    impl IntoIterator for &Evens {
+        type Item = u32;
+        type IntoIter = core::iter::StepBy<core::ops::Range<u32>>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            (0..self.0 * 2).step_by(2)
+        }
+    }
+
+    impl IntoIterator for Evens {
+        type Item = u32;
+        type IntoIter = core::iter::StepBy<core::ops::Range<u32>>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            (&self).into_iter()
+        }
+    }
+
+    #[alkahest(Formula, Serialize, SerializeRef)]
+    struct Data {
+        id: u32,
+        #[alkahest(as = "[u32]")]
+        evens: Evens,
+    }
+
+    let data = Data {
+        id: 7,
+        evens: Evens(4),
+    };
+
+    let mut buffer = [0u8; 64];
+    let (size, root) = serialize::<Data, _>(&data, &mut buffer).unwrap();
+    let (id, evens) =
+        deserialize_with_size::<(u32, [u32]), (u32, Vec<u32>)>(&buffer[..size], root).unwrap();
+    assert_eq!(id, 7);
+    assert_eq!(evens, [0, 2, 4, 6]);
+
+    let (size, root) = serialize::<Data, _>(data, &mut buffer).unwrap();
+    let (id, evens) =
+        deserialize_with_size::<(u32, [u32]), (u32, Vec<u32>)>(&buffer[..size], root).unwrap();
+    assert_eq!(id, 7);
+    assert_eq!(evens, [0, 2, 4, 6]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_binary_heap() {
+    use alloc::collections::BinaryHeap;
+
+    let heap = BinaryHeap::from([3u32, 1, 4, 1, 5, 9, 2, 6]);
+
+    let mut buffer = [0u8; 64];
+    let (size, root) = serialize::<[u32], _>(&heap, &mut buffer).unwrap();
+    let mut de = deserialize_with_size::<[u32], BinaryHeap<u32>>(&buffer[..size], root).unwrap();
+    assert_eq!(de.len(), heap.len());
+    assert_eq!(de.pop(), Some(9));
+    assert_eq!(de.pop(), Some(6));
+
+    deserialize_in_place_with_size::<[u32], _>(&mut de, &buffer[..size], root).unwrap();
+    assert_eq!(de.into_sorted_vec(), heap.clone().into_sorted_vec());
+
+    let (size, root) = serialize::<[u32], _>(heap, &mut buffer).unwrap();
+    let de = deserialize_with_size::<[u32], Vec<u32>>(&buffer[..size], root).unwrap();
+    assert_eq!(de.len(), 8);
+}
+
+#[test]
+fn test_option_reversed() {
+    let mut buffer = [0u8; 16];
+
+    let (size, _) = serialize::<Option<u32>, _>(None::<u32>, &mut buffer).unwrap();
+    assert_eq!(buffer[..size], [0]);
+    let (size, _) = serialize::<Option<u32>, _>(Some(5u32), &mut buffer).unwrap();
+    assert_eq!(buffer[..size], [5, 0, 0, 0, 1]);
+
+    let (size, _) = serialize::<OptionReversed<u32>, _>(None::<u32>, &mut buffer).unwrap();
+    assert_eq!(buffer[..size], [1]);
+    let value = deserialize::<OptionReversed<u32>, Option<u32>>(&buffer[..size]).unwrap();
+    assert_eq!(value, None);
+
+    let (size, _) = serialize::<OptionReversed<u32>, _>(Some(5u32), &mut buffer).unwrap();
+    assert_eq!(buffer[..size], [5, 0, 0, 0, 0]);
+    let value = deserialize::<OptionReversed<u32>, Option<u32>>(&buffer[..size]).unwrap();
+    assert_eq!(value, Some(5));
+
+    let mut value = Some(1u32);
+    deserialize_in_place_with_size::<OptionReversed<u32>, _>(&mut value, &[1], 1).unwrap();
+    assert_eq!(value, None);
+}
+
+#[test]
+fn test_read_fixed_usize() {
+    // Converts little-endian bytes into the byte order of sizes.
+    fn sizes_order<const N: usize>(mut bytes: [u8; N]) -> [u8; N] {
+        if cfg!(feature = "big-endian-sizes") {
+            bytes.reverse();
+        }
+        bytes
+    }
+
+    fn read<const N: usize>(value: u64) -> Result<usize, DeserializeError> {
+        let mut bytes = [0u8; N];
+        bytes.copy_from_slice(&value.to_le_bytes()[..N]);
+        Deserializer::new(N, &sizes_order(bytes))
+            .unwrap()
+            .read_fixed_usize::<N>()
+    }
+
+    fn check<const N: usize>() {
+        let max = if N >= 8 {
+            u64::MAX
+        } else {
+            (1u64 << (N * 8)) - 1
+        };
+
+        for value in [0, 1, max >> 1, max] {
+            let fits = FixedUsizeType::try_from(value).is_ok() && usize::try_from(value).is_ok();
+            match read::<N>(value) {
+                Ok(read) => {
+                    assert!(fits);
+                    assert_eq!(read as u64, value);
+                }
+                Err(DeserializeError::IntegerOverflow) => assert!(!fits),
+                Err(err) => panic!("Unexpected error {err:?}"),
+            }
+        }
+    }
+
+    // Writers with any `fixedN` feature paired with this build's reader.
+    check::<1>();
+    check::<2>();
+    check::<4>();
+    check::<8>();
+
+    // Native width round-trips.
+    let mut buffer = [0u8; SIZE_STACK];
+    serialize::<usize, _>(usize::from(u8::MAX), &mut buffer).unwrap();
+    let mut de = Deserializer::new(SIZE_STACK, &buffer).unwrap();
+    assert_eq!(de.read_fixed_usize::<SIZE_STACK>().unwrap(), 255);
+
+    // Zero-extension beyond 16 bytes.
+    let mut bytes = [0u8; 20];
+    bytes[0] = 7;
+    let input = sizes_order(bytes);
+    let mut de = Deserializer::new(20, &input).unwrap();
+    assert_eq!(de.read_fixed_usize::<20>().unwrap(), 7);
+
+    bytes[19] = 1;
+    let input = sizes_order(bytes);
+    let mut de = Deserializer::new(20, &input).unwrap();
+    assert!(matches!(
+        de.read_fixed_usize::<20>(),
+        Err(DeserializeError::IntegerOverflow)
+    ));
+
+    // Not enough input.
+    let mut de = Deserializer::new(2, &bytes[..2]).unwrap();
+    assert!(matches!(
+        de.read_fixed_usize::<4>(),
+        Err(DeserializeError::WrongLength)
+    ));
+}
+
+#[test]
+fn test_sizes_const() {
+    use crate::{formula::reference_size, serialize::formula_fast_sizes};
+
+    const HEADER: Sizes = match formula_fast_sizes::<(u32, u64)>() {
+        Some(sizes) => sizes,
+        None => panic!("Header must have fixed size"),
+    };
+
+    // Header followed by reference to 5 bytes string.
+    const MESSAGE: Sizes = {
+        let mut sizes = HEADER.sum(Sizes::with_heap(5));
+        sizes.add_stack(reference_size::<str>());
+        sizes
+    };
+
+    const _: () = assert!(HEADER.heap() == 0 && HEADER.stack() == 12);
+    const _: () = assert!(MESSAGE.heap() == 5);
+    const _: () = assert!(MESSAGE.total() == 17 + reference_size::<str>());
+
+    let mut buffer = [0u8; 64];
+    let (size, stack) =
+        serialize::<((u32, u64), Ref<str>), _>(((1u32, 2u64), "hello"), &mut buffer).unwrap();
+    assert_eq!(Sizes::new(size - stack, stack), MESSAGE);
+    assert_eq!(
+        HEADER + Sizes::with_heap(5),
+        HEADER.sum(Sizes::with_heap(5))
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_ser_iter_mapped() {
+    let mut buffer = [0u8; 64];
+
+    let iter = [1u8, 2, 3].iter().map(|&x| u32::from(x) * 10);
+    let sizes = <SerIter<_> as Serialize<[u32]>>::size_hint(&SerIter::new(iter.clone()));
+    assert_eq!(sizes, Some(Sizes::with_stack(12)));
+
+    let (size, _) = serialize::<[u32], _>(SerIter::new(iter), &mut buffer).unwrap();
+    let values = deserialize::<[u32], Vec<u32>>(&buffer[..size]).unwrap();
+    assert_eq!(values, [10, 20, 30]);
+
+    let (size, _) = serialize::<[u16], _>(SerIter::from(vec![4u16, 5]), &mut buffer).unwrap();
+    let values = deserialize::<[u16], Vec<u16>>(&buffer[..size]).unwrap();
+    assert_eq!(values, [4, 5]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_array_from_slice() {
+    let mut buffer = [0u8; 64];
+
+    let (size, _) = serialize::<[u32], _>([1u32, 2, 3, 4, 5], &mut buffer).unwrap();
+    let array = deserialize::<[u32], [u32; 3]>(&buffer[..size]).unwrap();
+    assert_eq!(array, [1, 2, 3]);
+
+    let array = deserialize::<[u32], [u32; 5]>(&buffer[..size]).unwrap();
+    assert_eq!(array, [1, 2, 3, 4, 5]);
+
+    assert!(matches!(
+        deserialize::<[u32], [u32; 6]>(&buffer[..size]),
+        Err(DeserializeError::WrongLength)
+    ));
+
+    let mut array = [0u32; 2];
+    deserialize_in_place::<[u32], [u32; 2]>(&mut array, &buffer[..size]).unwrap();
+    assert_eq!(array, [1, 2]);
+
+    let (size, _) = serialize::<[As<str>], _>(["qwe", "rty", "uio"], &mut buffer).unwrap();
+    let array = deserialize::<[As<str>], [&str; 2]>(&buffer[..size]).unwrap();
+    assert_eq!(array, ["qwe", "rty"]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_vec_from_array() {
+    let mut buffer = [0u8; 64];
+
+    let (size, _) = serialize::<[u16; 3], _>([7u16, 8, 9], &mut buffer).unwrap();
+    let vec = deserialize::<[u16; 3], Vec<u16>>(&buffer[..size]).unwrap();
+    assert_eq!(vec, [7, 8, 9]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_vec_in_place_reuse() {
+    let mut buffer = [0u8; 256];
+
+    let value = vec![vec![1u32, 2, 3], vec![4, 5], vec![6]];
+    let (size, _) = serialize::<Vec<Vec<u32>>, _>(&value, &mut buffer).unwrap();
+
+    let mut place: Vec<Vec<u32>> = Vec::with_capacity(8);
+    place.push(Vec::with_capacity(8));
+    place.push(Vec::with_capacity(8));
+    place.push(Vec::with_capacity(8));
+
+    let outer = (place.as_ptr(), place.capacity());
+    let inner: Vec<_> = place.iter().map(|v| (v.as_ptr(), v.capacity())).collect();
+
+    deserialize_in_place::<Vec<Vec<u32>>, _>(&mut place, &buffer[..size]).unwrap();
+    assert_eq!(place, value);
+
+    assert_eq!((place.as_ptr(), place.capacity()), outer);
+    let reused: Vec<_> = place.iter().map(|v| (v.as_ptr(), v.capacity())).collect();
+    assert_eq!(reused, inner);
+
+    // Shorter input truncates, longer appends.
+    let (size, _) = serialize::<Vec<Vec<u32>>, _>(vec![vec![7u32]], &mut buffer).unwrap();
+    deserialize_in_place::<Vec<Vec<u32>>, _>(&mut place, &buffer[..size]).unwrap();
+    assert_eq!(place, [vec![7]]);
+    assert_eq!((place.as_ptr(), place[0].as_ptr()), (outer.0, inner[0].0));
+
+    let (size, _) = serialize::<Vec<Vec<u32>>, _>(&value, &mut buffer).unwrap();
+    deserialize_in_place::<Vec<Vec<u32>>, _>(&mut place, &buffer[..size]).unwrap();
+    assert_eq!(place, value);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_explicit_discriminants() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Debug, PartialEq, Eq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[repr(u32)]
+    enum E {
+        A = 10,
+        B = 20,
+        C,
+        D(u8) = 0x1_0000,
+    }
+
+    let mut buffer = [0u8; 16];
+
+    let (size, _) = serialize::<E, _>(E::A, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[10, 0, 0, 0]);
+
+    let (size, _) = serialize::<E, _>(E::B, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[20, 0, 0, 0]);
+
+    let (size, _) = serialize::<E, _>(E::C, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[21, 0, 0, 0]);
+    assert_eq!(deserialize::<E, E>(&buffer[..size]).unwrap(), E::C);
+
+    let (size, _) = serialize::<E, _>(E::D(7), &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[7, 0, 0, 1, 0]);
+    assert_eq!(deserialize::<E, E>(&buffer[..size]).unwrap(), E::D(7));
+
+    assert!(matches!(
+        deserialize::<E, E>(&[0, 0, 0, 0]),
+        Err(DeserializeError::WrongVariant { got: 0, count: 4 })
+    ));
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_wrong_variant() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Debug)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    enum E {
+        A,
+        B(u8),
+        C { c: u16 },
+    }
+
+    let mut buffer = [0u8; 16];
+    let (size, _) = serialize::<(u32, u16), _>((7u32, 0u16), &mut buffer).unwrap();
+
+    match deserialize::<E, E>(&buffer[..size]) {
+        Err(DeserializeError::WrongVariant { got, count }) => {
+            assert_eq!(got, 7);
+            assert_eq!(count, 3);
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    let mut e = E::A;
+    assert!(matches!(
+        deserialize_in_place::<E, E>(&mut e, &buffer[..size]),
+        Err(DeserializeError::WrongVariant { got: 7, count: 3 })
+    ));
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_unknown_variant() {
+    use alkahest_proc::alkahest;
+
+    use crate::UnknownTag;
+
+    #[derive(Debug, PartialEq, Eq)]
+    #[alkahest(Formula, Serialize, SerializeRef, Deserialize)]
+    enum Old {
+        A,
+        #[alkahest(unknown)]
+        Unknown(UnknownTag<Self>),
+        B(u8),
+    }
+
+    #[alkahest(Formula, Serialize)]
+    enum New {
+        A,
+        B(u8),
+        C(u16),
+    }
+
+    // `unknown` variant does not shift tags of the following variants.
+    assert_eq!(Old::__ALKAHEST_FORMULA_VARIANT_B_IDX, 1);
+    assert_eq!(Old::__ALKAHEST_FORMULA_VARIANT_COUNT, 2);
+
+    let mut buffer = [0u8; 16];
+    let (size, _) = serialize::<New, _>(New::B(3), &mut buffer).unwrap();
+    assert_eq!(deserialize::<Old, Old>(&buffer[..size]).unwrap(), Old::B(3));
+
+    let (size, _) = serialize::<New, _>(New::C(5), &mut buffer).unwrap();
+    let old = deserialize::<Old, Old>(&buffer[..size]).unwrap();
+    assert!(matches!(old, Old::Unknown(tag) if tag.tag() == 2));
+
+    let mut in_place = Old::B(1);
+    deserialize_in_place::<Old, Old>(&mut in_place, &buffer[..size]).unwrap();
+    assert_eq!(in_place, old);
+
+    // Raw tag is written back as is.
+    let (size, _) = serialize::<Old, _>(&old, &mut buffer).unwrap();
+    assert_eq!(buffer[..size], [2, 0, 0, 0]);
+    assert_eq!(deserialize::<Old, Old>(&buffer[..size]).unwrap(), old);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_unknown_variant_small_tag() {
+    use alkahest_proc::alkahest;
+
+    use crate::UnknownTag;
+
+    #[derive(Debug)]
+    #[alkahest(Formula, Serialize, Deserialize, tag = u8)]
+    enum Small {
+        A,
+        #[alkahest(unknown)]
+        Unknown(UnknownTag<Self>),
+    }
+
+    let mut buffer = [0u8; 16];
+    let small = deserialize::<Small, Small>(&[200]).unwrap();
+    assert!(matches!(small, Small::Unknown(tag) if tag.tag() == 200));
+
+    let (size, _) = serialize::<Small, _>(small, &mut buffer).unwrap();
+    assert_eq!(buffer[..size], [200]);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_unit_variants() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    enum E {
+        A,
+        B,
+        Data(u64),
+        C,
+    }
+
+    assert_eq!(<E as Formula>::MAX_STACK_SIZE, Some(12));
+    const _: () = assert!(
+        !<E as Formula>::EXACT_SIZE,
+        "Unit variants are smaller than data variant"
+    );
+    const _: () = assert!(<E as Formula>::HEAPLESS);
+
+    let mut buffer = [0u8; 32];
+
+    for (value, tag) in [(E::A, 0u8), (E::B, 1), (E::C, 3)] {
+        let (size, _) = serialize::<E, _>(value, &mut buffer).unwrap();
+        assert_eq!(&buffer[..size], &[tag, 0, 0, 0]);
+        assert_eq!(deserialize::<E, E>(&buffer[..size]).unwrap(), value);
+    }
+
+    let (size, _) = serialize::<E, _>(E::Data(5), &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[5, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0]);
+    assert_eq!(deserialize::<E, E>(&buffer[..size]).unwrap(), E::Data(5));
+
+    // Unit variant that is not the last field is padded to the largest variant.
+    let (size, _) = serialize::<(E, u8), _>((E::B, 7), &mut buffer).unwrap();
+    assert_eq!(size, 13);
+    assert_eq!(
+        deserialize::<(E, u8), (E, u8)>(&buffer[..size]).unwrap(),
+        (E::B, 7)
+    );
+
+    let mut value = E::Data(1);
+    let (size, _) = serialize::<E, _>(E::C, &mut buffer).unwrap();
+    deserialize_in_place::<E, E>(&mut value, &buffer[..size]).unwrap();
+    assert_eq!(value, E::C);
+
+    let (size, _) = serialize::<E, _>(E::Data(9), &mut buffer).unwrap();
+    deserialize_in_place::<E, E>(&mut value, &buffer[..size]).unwrap();
+    assert_eq!(value, E::Data(9));
+
+    // Data variant tag without payload.
+    assert!(deserialize::<E, E>(&[2, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn test_read_reference_raw() {
+    use crate::formula::reference_size;
+
+    let mut buffer = [0u8; 64];
+    let (size, stack) = serialize::<(u32, Ref<str>), _>((7u32, "hello"), &mut buffer).unwrap();
+
+    let mut de = Deserializer::new(stack, &buffer[..size]).unwrap();
+    assert_eq!(de.read_value::<u32, u32>(false).unwrap(), 7);
+    let (address, len) = de.read_reference_raw::<str>().unwrap();
+    assert!(address <= size - stack);
+    assert_eq!(len, 5);
+    assert_eq!(&buffer[address - len..address], b"hello");
+    assert!(de.is_consumed());
+
+    // Exact size formula reports its size.
+    let (size, stack) = serialize::<Ref<u64>, _>(42u64, &mut buffer).unwrap();
+    let mut de = Deserializer::new(stack, &buffer[..size]).unwrap();
+    let (address, len) = de.read_reference_raw::<u64>().unwrap();
+    assert_eq!(len, 8);
+    assert_eq!(&buffer[address - len..address], &42u64.to_le_bytes());
+
+    let short = reference_size::<str>() - 1;
+    let mut de = Deserializer::new(short, &buffer[..short]).unwrap();
+    assert!(de.read_reference_raw::<str>().is_err());
+}
+
+#[test]
+fn test_error_offset() {
+    let mut buffer = [0u8; 32];
+    let (size, stack) =
+        serialize::<(u32, (u32, u64)), _>((1u32, (2u32, 3u64)), &mut buffer).unwrap();
+
+    // Stack 2 bytes short of the value truncates the nested `u64`.
+    let mut de = Deserializer::new(stack - 2, &buffer[..size]).unwrap();
+    assert_eq!(de.offset(), size - stack + 2);
+    assert_eq!(de.read_value::<u32, u32>(false).unwrap(), 1);
+
+    // Nested tuple is the last field and starts at the reported offset.
+    let offset = de.offset();
+    assert!(matches!(
+        de.read_value::<(u32, u64), (u32, u64)>(true),
+        Err(DeserializeError::WrongLength)
+    ));
+    assert_eq!(offset, size - stack + 2);
+}
+
+#[test]
+fn test_deserializer_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Deserializer<'static>>();
+    assert_send_sync::<Lazy<'static, str>>();
+    assert_send_sync::<crate::deserialize::DeIter<'static, u32, u32>>();
+    assert_eq!(
+        core::mem::size_of::<Deserializer<'static>>(),
+        core::mem::size_of::<(&[u8], usize, usize)>()
+    );
+}
+
+#[test]
+#[should_panic(expected = "The value must be either sized or heap-less")]
+fn test_deserialize_unsized_with_heap() {
+    let _ = deserialize::<[Ref<u32>], Lazy<[Ref<u32>]>>(&[]);
+}
+
+#[test]
+fn test_raw_reference() {
+    use crate::advanced::write_raw_reference;
+
+    struct Cached<'a> {
+        id: u32,
+        blob: &'a [u8],
+    }
+
+    impl Serialize<(u32, Ref<Bytes>)> for Cached<'_> {
+        fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            crate::advanced::write_field::<u32, _, _>(self.id, sizes, buffer.reborrow(), false)?;
+            write_raw_reference::<Bytes, _>(self.blob, sizes, buffer)
+        }
+
+        fn size_hint(&self) -> Option<Sizes> {
+            None
+        }
+    }
+
+    let mut cache = [0u8; 16];
+    let (len, _) = serialize::<Bytes, _>(&b"cached"[..], &mut cache).unwrap();
+    let blob = &cache[..len];
+
+    let mut buffer = [0u8; 64];
+    let (size, _) =
+        serialize::<(u32, Ref<Bytes>), _>(Cached { id: 42, blob }, &mut buffer).unwrap();
+
+    let (id, bytes) = deserialize::<(u32, Ref<Bytes>), (u32, &[u8])>(&buffer[..size]).unwrap();
+    assert_eq!(id, 42);
+    assert_eq!(bytes, b"cached");
+
+    let mut de = Deserializer::new(size - len, &buffer[..size]).unwrap();
+    assert_eq!(de.read_value::<u32, u32>(false).unwrap(), 42);
+    assert_eq!(de.read_raw_reference::<Bytes>().unwrap(), blob);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_vlq_str() {
+    use alloc::string::String;
+
+    fn check(s: &str, prefix: usize) {
+        let mut buffer = vec![0u8; s.len() + 16];
+        let (size, _) = serialize::<VlqStr, _>(s, &mut buffer).unwrap();
+        assert_eq!(size, s.len() + prefix);
+        assert_eq!(deserialize::<VlqStr, &str>(&buffer[..size]).unwrap(), s);
+
+        let mut string = String::from("previous");
+        deserialize_in_place::<VlqStr, String>(&mut string, &buffer[..size]).unwrap();
+        assert_eq!(string, s);
+    }
+
+    check("", 1);
+    check("hello", 1);
+    check(&"a".repeat(100), 2);
+    check(&"b".repeat(70000), 3);
+
+    // Strings are self-delimiting in a stream.
+    let mut buffer = [0u8; 32];
+    let mut buf = CheckedFixedBuffer::new(&mut buffer);
+    let mut sizes = Sizes::ZERO;
+    <&str as Serialize<VlqStr>>::serialize("first", &mut sizes, buf.reborrow()).unwrap();
+    <&str as Serialize<VlqStr>>::serialize("second", &mut sizes, buf.reborrow()).unwrap();
+    assert_eq!(sizes.stack, 13);
+
+    let mut de = Deserializer::new(sizes.stack, &buffer).unwrap();
+    assert_eq!(de.read_vlq_str().unwrap(), "first");
+    assert_eq!(de.read_vlq_str().unwrap(), "second");
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_vlq_str_malformed_header() {
+    assert!(matches!(
+        deserialize::<VlqStr, &str>(&[0, 0, 0xFF]),
+        Err(DeserializeError::IntegerOverflow)
+    ));
+
+    let mut de = Deserializer::new(3, &[0, 0, 0xC0]).unwrap();
+    assert!(matches!(
+        de.read_vlq_str(),
+        Err(DeserializeError::IntegerOverflow)
+    ));
+}
+
+#[test]
+fn test_deref_iter() {
+    let mut buffer = [0u8; 128];
+    let values = [u64::MAX, 0, 42, 1 << 40];
+
+    let (size, stack) = serialize::<[Ref<u64>], _>(values, &mut buffer).unwrap();
+    let de = Deserializer::new(stack, &buffer[..size]).unwrap();
+
+    let mut iter = de.deref_iter::<u64, u64>();
+    for value in values {
+        assert_eq!(iter.next().unwrap().unwrap(), value);
+    }
+    assert!(iter.next().is_none());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_read_bytes_to_writer() {
+    use crate::read_bytes_to_writer;
+
+    let blob: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let mut buffer = vec![0u8; blob.len()];
+    let (size, stack) = serialize::<Bytes, _>(&blob[..], &mut buffer).unwrap();
+
+    let mut output = Vec::new();
+    let de = Deserializer::new(stack, &buffer[..size]).unwrap();
+    let written = read_bytes_to_writer(de, &mut output).unwrap();
+    assert_eq!(written, blob.len());
+    assert_eq!(output, blob);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_io_error_from() {
+    use std::io;
+
+    fn read_u32(input: &[u8]) -> io::Result<u32> {
+        Ok(deserialize::<u32, u32>(input)?)
+    }
+
+    fn write_u32(value: u32, output: &mut [u8]) -> io::Result<usize> {
+        Ok(serialize::<u32, _>(value, output)?.0)
+    }
+
+    assert_eq!(read_u32(&[1, 0, 0, 0]).unwrap(), 1);
+    let err = read_u32(&[1, 0]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().starts_with("failed to deserialize"));
+
+    assert_eq!(write_u32(1, &mut [0; 4]).unwrap(), 4);
+    let err = write_u32(1, &mut [0; 2]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    assert_eq!(err.to_string(), "buffer exhausted");
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_generic_enum_bounds() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    enum Either<T> {
+        Left(T),
+        Right(u8),
+    }
+
+    let mut buffer = [0u8; 16];
+    let (size, _) = serialize::<Either<u32>, _>(Either::Left(7u32), &mut buffer).unwrap();
+    let value = deserialize::<Either<u32>, Either<u32>>(&buffer[..size]).unwrap();
+    assert_eq!(value, Either::Left(7));
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_const_generics() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, SerializeRef, Deserialize)]
+    struct Matrix<const R: usize, const C: usize> {
+        data: [[f32; C]; R],
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, SerializeRef, Deserialize)]
+    enum Cells<T, const N: usize> {
+        Dense([T; N]),
+        Empty,
+    }
+
+    let mut buffer = [0u8; 64];
+
+    let matrix = Matrix::<2, 3> {
+        data: [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]],
+    };
+    let (size, _) = serialize::<Matrix<2, 3>, _>(&matrix, &mut buffer).unwrap();
+    assert_eq!(size, 24);
+    let value = deserialize::<Matrix<2, 3>, Matrix<2, 3>>(&buffer[..size]).unwrap();
+    assert_eq!(value, matrix);
+
+    let cells = Cells::<u16, 3>::Dense([7, 8, 9]);
+    let (size, _) = serialize::<Cells<u16, 3>, _>(cells.clone(), &mut buffer).unwrap();
+    let value = deserialize::<Cells<u16, 3>, Cells<u16, 3>>(&buffer[..size]).unwrap();
+    assert_eq!(value, cells);
+
+    let (size, _) = serialize::<Cells<u16, 3>, _>(&Cells::Empty, &mut buffer).unwrap();
+    let value = deserialize::<Cells<u16, 3>, Cells<u16, 3>>(&buffer[..size]).unwrap();
+    assert_eq!(value, Cells::Empty);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_enum_tag() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize, tag = u16)]
+    enum Message {
+        #[alkahest(tag_value = 300)]
+        Ping,
+        #[alkahest(tag_value = 7)]
+        Data(u8),
+        Other,
+    }
+
+    #[alkahest(Serialize<Message @Data>)]
+    struct Data(u8);
+
+    assert_eq!(<Message as Formula>::MAX_STACK_SIZE, Some(3));
+
+    let mut buffer = [0u8; 16];
+
+    let (size, _) = serialize::<Message, _>(Message::Ping, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[0x2c, 0x01]);
+
+    let (size, _) = serialize::<Message, _>(Message::Data(42), &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[42, 7, 0]);
+    assert_eq!(
+        deserialize::<Message, Message>(&buffer[..size]).unwrap(),
+        Message::Data(42)
+    );
+
+    let (size, _) = serialize::<Message, _>(Data(5), &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[5, 7, 0]);
+
+    let (size, _) = serialize::<Message, _>(Message::Other, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[8, 0]);
+    assert_eq!(
+        deserialize::<Message, Message>(&buffer[..size]).unwrap(),
+        Message::Other
+    );
+
+    assert!(matches!(
+        deserialize::<Message, Message>(&[1, 0]),
+        Err(DeserializeError::WrongVariant { got: 1, count: 3 })
+    ));
+}
+
+#[test]
+fn test_option_bytes_borrow() {
+    let mut buffer = [0u8; 64];
+
+    let (size, _) = serialize::<Option<Bytes>, _>(Some(&[1u8, 2, 3][..]), &mut buffer).unwrap();
+    let input = &buffer[..size];
+    let value = deserialize::<Option<Bytes>, Option<&[u8]>>(input).unwrap();
+    let bytes = value.unwrap();
+    assert_eq!(bytes, &[1, 2, 3]);
+    assert!(input.as_ptr_range().contains(&bytes.as_ptr()));
+
+    let (size, _) = serialize::<Option<Bytes>, _>(None::<&[u8]>, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<Option<Bytes>, Option<&[u8]>>(&buffer[..size]).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_ip_addr() {
+    use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    let mut buffer = [0u8; 64];
+
+    let v4 = Ipv4Addr::new(192, 168, 0, 1);
+    let (size, _) = serialize::<Ipv4Addr, _>(v4, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[192, 168, 0, 1]);
+    assert_eq!(
+        deserialize::<Ipv4Addr, Ipv4Addr>(&buffer[..size]).unwrap(),
+        v4
+    );
+
+    let v6 = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let (size, _) = serialize::<Ipv6Addr, _>(&v6, &mut buffer).unwrap();
+    assert_eq!(size, 16);
+    assert_eq!(
+        deserialize::<Ipv6Addr, Ipv6Addr>(&buffer[..size]).unwrap(),
+        v6
+    );
+
+    let (size, _) = serialize::<IpAddr, _>(IpAddr::V4(v4), &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[192, 168, 0, 1, 0]);
+
+    let pair = (IpAddr::V4(v4), IpAddr::V6(v6));
+    let (size, _) = serialize::<(IpAddr, IpAddr), _>(pair, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<(IpAddr, IpAddr), (IpAddr, IpAddr)>(&buffer[..size]).unwrap(),
+        pair
+    );
+
+    assert!(matches!(
+        deserialize::<IpAddr, IpAddr>(&[0, 0, 0, 0, 2]),
+        Err(DeserializeError::WrongVariant { got: 2, count: 2 })
+    ));
+}
+
+#[cfg(feature = "smallvec")]
+#[test]
+fn test_smallvec() {
+    use smallvec::{smallvec, SmallVec};
+
+    let mut buffer = [0u8; 64];
+
+    let short: SmallVec<[u32; 4]> = smallvec![1, 2, 3];
+    let (size, _) = serialize::<[u32], _>(&short, &mut buffer).unwrap();
+    let value = deserialize::<[u32], SmallVec<[u32; 4]>>(&buffer[..size]).unwrap();
+    assert_eq!(value, short);
+    assert!(!value.spilled());
+
+    let long: SmallVec<[u32; 4]> = smallvec![1, 2, 3, 4, 5, 6];
+    let (size, _) = serialize::<[u32], _>(long.clone(), &mut buffer).unwrap();
+    let value = deserialize::<[u32], SmallVec<[u32; 4]>>(&buffer[..size]).unwrap();
+    assert_eq!(value, long);
+    assert!(value.spilled());
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_framed() {
+    use alkahest_proc::alkahest;
+
+    use crate::{framed::Framed, skip::Skip};
+
+    #[alkahest(Formula)]
+    struct Body {
+        id: u64,
+        tags: Bytes,
+    }
+
+    #[alkahest(Formula)]
+    struct Envelope {
+        header: u32,
+        body: Framed<Body>,
+        trailer: u16,
+    }
+
+    #[alkahest(Serialize<Envelope>)]
+    struct EnvelopeWrite<'a> {
+        header: u32,
+        body: BodyWrite<'a>,
+        trailer: u16,
+    }
+
+    #[alkahest(Serialize<Body>)]
+    struct BodyWrite<'a> {
+        id: u64,
+        tags: &'a [u8],
+    }
+
+    #[alkahest(Deserialize<'de, Body>)]
+    struct BodyRead<'de> {
+        id: u64,
+        tags: &'de [u8],
+    }
+
+    #[alkahest(Deserialize<'de, Envelope>)]
+    struct EnvelopeRead<'de> {
+        header: u32,
+        body: BodyRead<'de>,
+        trailer: u16,
+    }
+
+    #[alkahest(Deserialize<'_, Envelope>)]
+    struct EnvelopeSkip {
+        header: u32,
+        body: Skip,
+        trailer: u16,
+    }
+
+    let mut buffer = [0u8; 64];
+    let value = EnvelopeWrite {
+        header: 1,
+        body: BodyWrite {
+            id: 2,
+            tags: &[3, 4, 5],
+        },
+        trailer: 6,
+    };
+    let (size, _) = serialize::<Envelope, _>(value, &mut buffer).unwrap();
+
+    let read = deserialize::<Envelope, EnvelopeRead>(&buffer[..size]).unwrap();
+    assert_eq!(read.header, 1);
+    assert_eq!(read.body.id, 2);
+    assert_eq!(read.body.tags, &[3, 4, 5]);
+    assert_eq!(read.trailer, 6);
+
+    let skipped = deserialize::<Envelope, EnvelopeSkip>(&buffer[..size]).unwrap();
+    assert_eq!(skipped.header, 1);
+    assert_eq!(skipped.trailer, 6);
+
+    let (size, _) = serialize::<Framed<u32>, _>(7u32, &mut buffer).unwrap();
+    assert_eq!(size, SIZE_STACK + 4);
+    assert_eq!(deserialize::<Framed<u32>, u32>(&buffer[..size]).unwrap(), 7);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_sorted() {
+    use crate::sorted::Sorted;
+
+    let mut buffer = [0u8; 64];
+
+    let (size, _) = serialize::<Sorted<u32>, _>(&[1u32, 2, 2, 5][..], &mut buffer).unwrap();
+    let vec = deserialize::<Sorted<u32>, Vec<u32>>(&buffer[..size]).unwrap();
+    assert_eq!(vec, [1, 2, 2, 5]);
+
+    let (size, _) = serialize::<Sorted<u32>, _>(vec![3u32, 1, 2], &mut buffer).unwrap();
+    let vec = deserialize::<Sorted<u32>, Vec<u32>>(&buffer[..size]).unwrap();
+    assert_eq!(vec, [1, 2, 3]);
+
+    let (size, _) = serialize::<[u32], _>(&[3u32, 1, 2][..], &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize::<Sorted<u32>, Vec<u32>>(&buffer[..size]),
+        Err(DeserializeError::Incompatible)
+    ));
+
+    let (size, _) = serialize::<(As<Sorted<u8>>, u8), _>((vec![2u8, 1], 7u8), &mut buffer).unwrap();
+    let (vec, tail) = deserialize::<(As<Sorted<u8>>, u8), (Vec<u8>, u8)>(&buffer[..size]).unwrap();
+    assert_eq!(vec, [1, 2]);
+    assert_eq!(tail, 7);
+}
+
+#[cfg(all(feature = "half", feature = "derive"))]
+#[test]
+fn test_half() {
+    use alkahest_proc::alkahest;
+    use half::{bf16, f16};
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Clone, Copy, Debug)]
+    struct Sample {
+        a: f16,
+        b: bf16,
+    }
+
+    let mut buffer = [0u8; 16];
+
+    for (a, b) in [
+        (f16::from_f32(1.5), bf16::from_f32(-2.25)),
+        (f16::INFINITY, bf16::NEG_INFINITY),
+        (f16::NAN, bf16::NAN),
+        (f16::from_bits(0x7e01), bf16::from_bits(0xffc1)),
+    ] {
+        let (size, _) = serialize::<Sample, _>(Sample { a, b }, &mut buffer).unwrap();
+        assert_eq!(size, 4);
+        let value = deserialize::<Sample, Sample>(&buffer[..size]).unwrap();
+        assert_eq!(value.a.to_bits(), a.to_bits());
+        assert_eq!(value.b.to_bits(), b.to_bits());
+    }
+
+    let (size, _) = serialize::<f16, _>(&f16::ONE, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &f16::ONE.to_bits().to_le_bytes());
+}
+
+#[cfg(all(feature = "chrono", feature = "derive"))]
+#[test]
+fn test_chrono() {
+    use alkahest_proc::alkahest;
+    use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+    #[alkahest(Formula, SerializeRef, Deserialize)]
+    #[derive(Debug, PartialEq, Eq)]
+    struct Record {
+        naive: NaiveDateTime,
+        utc: DateTime<Utc>,
+    }
+
+    let pre_epoch = NaiveDate::from_ymd_opt(1969, 7, 20)
+        .unwrap()
+        .and_hms_nano_opt(20, 17, 40, 123_456_789)
+        .unwrap();
+    let leap = NaiveDate::from_ymd_opt(2016, 12, 31)
+        .unwrap()
+        .and_hms_nano_opt(23, 59, 59, 1_500_000_000)
+        .unwrap();
+
+    let mut buffer = [0u8; 24];
+    for naive in [pre_epoch, leap, NaiveDateTime::MIN, NaiveDateTime::MAX] {
+        let record = Record {
+            naive,
+            utc: naive.and_utc(),
+        };
+        let (size, _) = serialize::<Record, _>(&record, &mut buffer).unwrap();
+        assert_eq!(size, 24);
+        assert_eq!(
+            deserialize::<Record, Record>(&buffer[..size]).unwrap(),
+            record
+        );
+    }
+
+    serialize::<NaiveDateTime, _>(pre_epoch, &mut buffer).unwrap();
+    assert_eq!(buffer[..8], (-14_182_940i64).to_le_bytes());
+    assert_eq!(buffer[8..12], 123_456_789u32.to_le_bytes());
+
+    // Seconds beyond supported range.
+    buffer[..8].copy_from_slice(&i64::MAX.to_le_bytes());
+    buffer[8..12].copy_from_slice(&0u32.to_le_bytes());
+    assert!(matches!(
+        deserialize::<NaiveDateTime, NaiveDateTime>(&buffer[..12]),
+        Err(DeserializeError::Incompatible)
+    ));
+
+    // Nanoseconds beyond leap second.
+    buffer[..8].copy_from_slice(&59i64.to_le_bytes());
+    buffer[8..12].copy_from_slice(&2_000_000_000u32.to_le_bytes());
+    assert!(matches!(
+        deserialize::<DateTime<Utc>, DateTime<Utc>>(&buffer[..12]),
+        Err(DeserializeError::Incompatible)
+    ));
+}
+
+#[test]
+fn test_ascii() {
+    use crate::str::Ascii;
+
+    let mut buffer = [0u8; 64];
+
+    let (size, _) = serialize::<(As<Ascii>, u8), _>(("Host", 1u8), &mut buffer).unwrap();
+    let (value, tail) = deserialize::<(As<Ascii>, u8), (&str, u8)>(&buffer[..size]).unwrap();
+    assert_eq!(value, "Host");
+    assert_eq!(tail, 1);
+
+    let (size, _) = serialize::<str, _>("Größe", &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize::<Ascii, &str>(&buffer[..size]),
+        Err(DeserializeError::Incompatible)
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_ser_iter_buffered() {
+    struct OneShot(u32);
+
+    impl Iterator for OneShot {
+        type Item = u32;
+
+        fn next(&mut self) -> Option<u32> {
+            self.0 = self.0.checked_sub(1)?;
+            Some(self.0)
+        }
+    }
+
+    let mut buffer = [0u8; 64];
+
+    let iter = SerIter::buffered(OneShot(4));
+    assert_eq!(
+        <_ as Serialize<[u32]>>::size_hint(&iter),
+        Some(Sizes::with_stack(16))
+    );
+
+    let (size, _) = serialize::<[u32], _>(iter, &mut buffer).unwrap();
+    let values = deserialize::<[u32], Vec<u32>>(&buffer[..size]).unwrap();
+    assert_eq!(values, [3, 2, 1, 0]);
+
+    let (size, _) = serialize::<Vec<u32>, _>(SerIter::buffered(OneShot(3)), &mut buffer).unwrap();
+    let values = deserialize::<Vec<u32>, Vec<u32>>(&buffer[..size]).unwrap();
+    assert_eq!(values, [2, 1, 0]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_hash_map_hasher() {
+    use alloc::string::String;
+    use std::collections::HashMap;
+
+    let mut map = HashMap::with_hasher(ahash::RandomState::new());
+    map.insert(String::from("one"), 1u32);
+    map.insert(String::from("two"), 2u32);
+
+    let mut buffer = [0u8; 128];
+    let (size, _) = serialize::<Vec<(String, u32)>, _>(&map, &mut buffer).unwrap();
+    let value = deserialize::<Vec<(String, u32)>, HashMap<String, u32, ahash::RandomState>>(
+        &buffer[..size],
+    )
+    .unwrap();
+    assert_eq!(value, map);
+
+    let mut place = HashMap::<String, u32, ahash::RandomState>::default();
+    place.insert(String::from("three"), 3);
+    deserialize_in_place::<Vec<(String, u32)>, _>(&mut place, &buffer[..size]).unwrap();
+    assert_eq!(place, map);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_deserialize_with_limit() {
+    use alloc::string::String;
+
+    use crate::deserialize::deserialize_with_limit;
+
+    // Slice of zero-sized elements stores element count.
+    let buffer = FixedUsizeType::MAX.to_le_bytes();
+    assert!(matches!(
+        deserialize_with_limit::<[()], Vec<()>>(&buffer, FixedUsizeType::MAX as usize - 1),
+        Err(DeserializeError::Incompatible)
+    ));
+
+    let mut buffer = [0u8; 64];
+    let (size, _) = serialize::<Vec<u32>, _>([1u32, 2, 3, 4, 5], &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize_with_limit::<Vec<u32>, Vec<u32>>(&buffer[..size], 4),
+        Err(DeserializeError::Incompatible)
+    ));
+    assert_eq!(
+        deserialize_with_limit::<Vec<u32>, Vec<u32>>(&buffer[..size], 5).unwrap(),
+        [1, 2, 3, 4, 5]
+    );
+
+    let (size, stack) =
+        serialize::<[Vec<u8>], _>([vec![1u8; 8], vec![2u8; 2]], &mut buffer).unwrap();
+    let de = Deserializer::new(stack, &buffer[..size])
+        .unwrap()
+        .with_max_elements(4);
+    assert!(matches!(
+        <Vec<Vec<u8>> as Deserialize<[Vec<u8>]>>::deserialize(de),
+        Err(DeserializeError::Incompatible)
+    ));
+
+    // Elements of unsized formula are counted as they are read.
+    let strings = ["a"; 20];
+    let mut buffer = [0u8; 256];
+    let (size, _) = serialize::<[As<str>], _>(strings, &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize_with_limit::<[As<str>], Vec<String>>(&buffer[..size], 10),
+        Err(DeserializeError::Incompatible)
+    ));
+    assert_eq!(
+        deserialize_with_limit::<[As<str>], Vec<String>>(&buffer[..size], 20).unwrap(),
+        strings
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+#[should_panic(expected = "The value must be either sized or heap-less")]
+fn test_deserialize_with_limit_unsized_with_heap() {
+    use crate::deserialize::deserialize_with_limit;
+
+    let _ = deserialize_with_limit::<(u8, [Vec<u32>]), (u8, Vec<Vec<u32>>)>(&[], 10);
+}
+
+#[test]
+fn test_bytes_str() {
+    let mut buffer = [0u8; 64];
+
+    let (size, _) = serialize::<Bytes, _>("Hello, world!".as_bytes(), &mut buffer).unwrap();
+    let s = deserialize::<Bytes, &str>(&buffer[..size]).unwrap();
+    assert_eq!(s, "Hello, world!");
+
+    let (size, _) = serialize::<Bytes, _>(&[0x66u8, 0xff, 0x6f][..], &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize::<Bytes, &str>(&buffer[..size]),
+        Err(DeserializeError::NonUtf8(_))
+    ));
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_untagged_enum() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize, untagged)]
+    enum Reply {
+        Empty,
+        Value(u32),
+    }
+
+    assert_eq!(<Reply as Formula>::MAX_STACK_SIZE, None);
+
+    let mut buffer = [0u8; 16];
+
+    let (size, _) = serialize::<Reply, _>(Reply::Empty, &mut buffer).unwrap();
+    assert_eq!(size, 0);
+    assert_eq!(
+        deserialize::<Reply, Reply>(&buffer[..size]).unwrap(),
+        Reply::Empty
+    );
+
+    let (size, _) = serialize::<Reply, _>(Reply::Value(42), &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[42, 0, 0, 0]);
+    assert_eq!(
+        deserialize::<Reply, Reply>(&buffer[..size]).unwrap(),
+        Reply::Value(42)
+    );
+
+    let mut reply = Reply::Empty;
+    crate::deserialize::deserialize_in_place::<Reply, Reply>(&mut reply, &buffer[..size]).unwrap();
+    assert_eq!(reply, Reply::Value(42));
+
+    assert!(matches!(
+        deserialize::<Reply, Reply>(&[1, 2]),
+        Err(DeserializeError::Incompatible)
+    ));
+
+    // Values are not padded to the largest variant when followed by other fields.
+    let (size, _) = serialize::<(Reply, u8), _>((Reply::Empty, 7u8), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<(Reply, u8), (Reply, u8)>(&buffer[..size]).unwrap(),
+        (Reply::Empty, 7)
+    );
+
+    let (size, _) = serialize::<(Reply, u8), _>((Reply::Value(5), 7u8), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<(Reply, u8), (Reply, u8)>(&buffer[..size]).unwrap(),
+        (Reply::Value(5), 7)
+    );
+
+    let mut buffer = [0u8; 64];
+    let (size, _) =
+        serialize::<[Reply], _>([Reply::Empty, Reply::Value(3), Reply::Empty], &mut buffer)
+            .unwrap();
+    assert_eq!(
+        deserialize::<[Reply], [Reply; 3]>(&buffer[..size]).unwrap(),
+        [Reply::Empty, Reply::Value(3), Reply::Empty]
+    );
+}
+
+#[test]
+fn test_counting_buffer() {
+    use crate::{
+        buffer::CountingBuffer,
+        serialize::{serialize_into, write_slice},
+    };
+
+    // Manual implementation without size hint.
+    struct Evens(u32);
+
+    impl Serialize<[u32]> for Evens {
+        fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            write_slice::<u32, _, _>((0..self.0).filter(|n| n % 2 == 0), sizes, buffer)
+        }
+
+        fn size_hint(&self) -> Option<Sizes> {
+            None
+        }
+    }
+
+    let mut counter = CountingBuffer::new();
+    assert!(counter.is_empty());
+
+    let (size, _) = serialize_into::<[u32], _, _>(Evens(10), &mut counter).unwrap();
+    assert_eq!(counter.len(), size);
+
+    let mut buffer = [0u8; 64];
+    let (size, _) = serialize::<[u32], _>(Evens(10), &mut buffer).unwrap();
+    assert_eq!(counter.len(), size);
+    assert_eq!(
+        deserialize::<[u32], [u32; 5]>(&buffer[..size]).unwrap(),
+        [0, 2, 4, 6, 8]
+    );
+
+    let mut counter = CountingBuffer::new();
+    serialize_into::<(u8, Ref<[u32]>), _, _>((1u8, Evens(7)), &mut counter).unwrap();
+    let (size, _) = serialize::<(u8, Ref<[u32]>), _>((1u8, Evens(7)), &mut buffer).unwrap();
+    assert_eq!(counter.len(), size);
+}
+
+#[cfg(all(feature = "derive", feature = "alloc"))]
+#[test]
+fn test_from_tuple() {
+    use alkahest_proc::alkahest;
+    use alloc::string::String;
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(for<'de> Deserialize<'de, (u32, Ref<str>)>, from_tuple)]
+    struct Named {
+        id: u32,
+        name: String,
+    }
+
+    type Pair = (u8, u16);
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(for<'de> Deserialize<'de, Pair>, from_tuple)]
+    struct Small(u8, u16);
+
+    let mut buffer = [0u8; 64];
+
+    let (size, _) = serialize::<(u32, Ref<str>), _>((42u32, "Alice"), &mut buffer).unwrap();
+    let named = deserialize::<(u32, Ref<str>), Named>(&buffer[..size]).unwrap();
+    assert_eq!(
+        named,
+        Named {
+            id: 42,
+            name: String::from("Alice"),
+        }
+    );
+
+    let mut named = Named {
+        id: 0,
+        name: String::new(),
+    };
+    deserialize_in_place::<(u32, Ref<str>), Named>(&mut named, &buffer[..size]).unwrap();
+    assert_eq!(named.name, "Alice");
+
+    let (size, _) = serialize::<Pair, _>((1u8, 2u16), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<Pair, Small>(&buffer[..size]).unwrap(),
+        Small(1, 2)
+    );
+}
+
+#[test]
+fn test_size_byte_order() {
+    let mut buffer = [0u8; 64];
+    let (size, _) = serialize::<Ref<str>, _>("hello", &mut buffer).unwrap();
+
+    // Length of the string is the last value on the stack.
+    let len = &buffer[size - SIZE_STACK..size];
+    assert_eq!(len, fixed_usize_to_bytes(5));
+
+    #[cfg(feature = "big-endian-sizes")]
+    assert_eq!(len[SIZE_STACK - 1], 5);
+
+    #[cfg(not(feature = "big-endian-sizes"))]
+    assert_eq!(len[0], 5);
+
+    assert_eq!(
+        deserialize::<Ref<str>, &str>(&buffer[..size]).unwrap(),
+        "hello"
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_cow_slice() {
+    use alloc::borrow::Cow;
+
+    let mut buffer = [0u8; 64];
+
+    let (size, _) = serialize::<Bytes, _>(&[1u8, 2, 3][..], &mut buffer).unwrap();
+    let bytes = deserialize::<Bytes, Cow<[u8]>>(&buffer[..size]).unwrap();
+    assert!(matches!(bytes, Cow::Borrowed([1, 2, 3])));
+
+    let pairs = [(1u32, 2u16), (3, 4)];
+    let (size, _) = serialize::<[(u32, u16)], _>(&pairs[..], &mut buffer).unwrap();
+    let mut cow = deserialize::<[(u32, u16)], Cow<[(u32, u16)]>>(&buffer[..size]).unwrap();
+    assert!(matches!(&cow, Cow::Owned(vec) if vec[..] == pairs));
+
+    cow = Cow::Borrowed(&[]);
+    deserialize_in_place::<[(u32, u16)], _>(&mut cow, &buffer[..size]).unwrap();
+    assert_eq!(cow[..], pairs);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_cow_serialize() {
+    use alloc::{borrow::Cow, string::String};
+
+    // Fails the test if borrowed slice is converted to owned.
+    struct NoClone(u32);
+
+    impl Clone for NoClone {
+        fn clone(&self) -> Self {
+            panic!("Borrowed `Cow` must not be cloned");
+        }
+    }
+
+    impl Serialize<u32> for &NoClone {
+        #[inline(always)]
+        fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <u32 as Serialize<u32>>::serialize(self.0, sizes, buffer)
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> Option<Sizes> {
+            Some(Sizes::with_stack(4))
+        }
+    }
+
+    let mut buffer = [0u8; 64];
+
+    let elems = [NoClone(1), NoClone(2), NoClone(3)];
+    let cow = Cow::Borrowed(&elems[..]);
+    let (size, _) = serialize::<[u32], _>(&cow, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<[u32], Vec<u32>>(&buffer[..size]).unwrap(),
+        [1, 2, 3]
+    );
+
+    let cow: Cow<str> = Cow::Borrowed("borrowed");
+    let (size, _) = serialize::<str, _>(&cow, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<str, &str>(&buffer[..size]).unwrap(),
+        "borrowed"
+    );
+    let (size, _) = serialize::<String, _>(cow, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<String, &str>(&buffer[..size]).unwrap(),
+        "borrowed"
+    );
+
+    let cow: Cow<[u8]> = Cow::Owned(vec![4, 5]);
+    let (size, _) = serialize::<Bytes, _>(&cow, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<Bytes, &[u8]>(&buffer[..size]).unwrap(),
+        [4, 5]
+    );
+    let (size, _) = serialize::<Bytes, _>(cow, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<Bytes, &[u8]>(&buffer[..size]).unwrap(),
+        [4, 5]
+    );
+}
+
+#[test]
+fn test_read_fixed_bytes() {
+    let mut input = [0u8; 40];
+    for (idx, byte) in input.iter_mut().enumerate() {
+        *byte = idx as u8;
+    }
+
+    let mut de = Deserializer::new(input.len(), &input).unwrap();
+    let hash = de.read_fixed_bytes::<32>().unwrap();
+    assert_eq!(hash[0], 8);
+    assert_eq!(hash[31], 39);
+    assert!(input.as_ptr_range().contains(&hash.as_ptr()));
+
+    assert!(matches!(
+        de.read_fixed_bytes::<32>(),
+        Err(DeserializeError::WrongLength)
+    ));
+    assert_eq!(
+        de.read_fixed_bytes::<8>().unwrap(),
+        &[0, 1, 2, 3, 4, 5, 6, 7]
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_ser_iter_exact_size() {
+    let values = vec![(1u32, 2u8), (3, 4), (5, 6)];
+
+    let iter = SerIter::new(values.iter().copied());
+    assert_eq!(iter.0.len(), 3);
+    assert_eq!(
+        <_ as Serialize<[(u32, u8)]>>::size_hint(&iter),
+        Some(Sizes::with_stack(15))
+    );
+
+    let mut expected = [0u8; 64];
+    let mut buffer = [0u8; 64];
+
+    let (expected_size, _) = serialize::<[(u32, u8)], _>(&values, &mut expected).unwrap();
+    let (size, _) = serialize::<[(u32, u8)], _>(iter, &mut buffer).unwrap();
+    assert_eq!(buffer[..size], expected[..expected_size]);
+
+    let (expected_size, _) = serialize::<Vec<(u32, u8)>, _>(&values, &mut expected).unwrap();
+    let (size, _) =
+        serialize::<Vec<(u32, u8)>, _>(SerIter::new(values.iter().copied()), &mut buffer).unwrap();
+    assert_eq!(buffer[..size], expected[..expected_size]);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_skip_serializing_if() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, SerializeRef, Deserialize)]
+    struct Sparse {
+        id: u32,
+        #[alkahest(skip_serializing_if = "Option::is_none")]
+        extra: Option<u64>,
+        #[alkahest(skip_serializing_if = "Option::is_none")]
+        flag: Option<u8>,
+    }
+
+    let mut buffer = [0u8; 64];
+
+    let full = Sparse {
+        id: 1,
+        extra: Some(2),
+        flag: Some(3),
+    };
+    let (full_size, _) = serialize::<Sparse, _>(&full, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<Sparse, Sparse>(&buffer[..full_size]).unwrap(),
+        full
+    );
+
+    let sparse = Sparse {
+        id: 1,
+        extra: None,
+        flag: Some(3),
+    };
+    let (size, _) = serialize::<Sparse, _>(&sparse, &mut buffer).unwrap();
+    assert!(size < full_size);
+    assert_eq!(serialized_size::<Sparse, _>(&sparse), (size, size));
+    assert_eq!(
+        deserialize::<Sparse, Sparse>(&buffer[..size]).unwrap(),
+        sparse
+    );
+
+    let mut value = full;
+    crate::deserialize::deserialize_in_place::<Sparse, Sparse>(&mut value, &buffer[..size])
+        .unwrap();
+    assert_eq!(value, sparse);
+
+    let empty = Sparse {
+        id: 1,
+        extra: None,
+        flag: None,
+    };
+    let (size, _) = serialize::<Sparse, _>(empty, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<Sparse, Sparse>(&buffer[..size]).unwrap(),
+        Sparse {
+            id: 1,
+            extra: None,
+            flag: None,
+        }
+    );
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_skip_serializing_if_generic() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, SerializeRef, Deserialize)]
+    struct Sparse<T> {
+        id: u32,
+        #[alkahest(skip_serializing_if = "Option::is_none")]
+        extra: Option<T>,
+    }
+
+    let mut buffer = [0u8; 64];
+    for value in [
+        Sparse {
+            id: 1,
+            extra: Some(2u64),
+        },
+        Sparse { id: 1, extra: None },
+    ] {
+        let (size, _) = serialize::<Sparse<u64>, _>(&value, &mut buffer).unwrap();
+        assert_eq!(
+            deserialize::<Sparse<u64>, Sparse<u64>>(&buffer[..size]).unwrap(),
+            value
+        );
+    }
+}
+
+#[cfg(all(feature = "derive", feature = "alloc"))]
+#[test]
+fn test_field_as_generic() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, SerializeRef, Deserialize)]
+    struct Items<T> {
+        id: u32,
+        #[alkahest(as = "[T]")]
+        items: Vec<T>,
+    }
+
+    let value = Items {
+        id: 1,
+        items: vec![2u16, 3, 4],
+    };
+
+    let mut buffer = [0u8; 64];
+    let (size, _) = serialize::<Items<u16>, _>(&value, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<Items<u16>, Items<u16>>(&buffer[..size]).unwrap(),
+        value
+    );
+    let (size, _) = serialize::<Items<u16>, _>(value, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<Items<u16>, Items<u16>>(&buffer[..size])
+            .unwrap()
+            .items,
+        [2, 3, 4]
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_ref_dedup() {
+    use alloc::{rc::Rc, string::String};
+
+    use crate::{
+        formula::reference_size,
+        serialize::{write_ref, write_reference},
+        Lazy, RefDedup,
+    };
+
+    // Writes string once and two references to it.
+    struct Shared<'a>(&'a str);
+
+    impl Serialize<(Ref<str>, Ref<str>)> for Shared<'_> {
+        fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            let size = write_ref::<str, _, _>(self.0, sizes, buffer.reborrow())?;
+            for _ in 0..2 {
+                write_reference::<str, _>(
+                    size,
+                    sizes.heap,
+                    sizes.heap,
+                    sizes.stack,
+                    buffer.reborrow(),
+                )?;
+                sizes.stack += reference_size::<str>();
+            }
+            Ok(())
+        }
+
+        fn size_hint(&self) -> Option<Sizes> {
+            None
+        }
+    }
+
+    let mut buffer = [0u8; 64];
+    let (size, _) =
+        serialize::<(Ref<str>, Ref<str>), _>(Shared("shared node"), &mut buffer).unwrap();
+
+    let (a, b) =
+        deserialize::<(Ref<str>, Ref<str>), (Lazy<str>, Lazy<str>)>(&buffer[..size]).unwrap();
+
+    let mut dedup = RefDedup::<String>::new();
+    let a = dedup.get(&a).unwrap();
+    let b = dedup.get(&b).unwrap();
+
+    assert_eq!(*a, "shared node");
+    assert!(Rc::ptr_eq(&a, &b));
+    assert_eq!(dedup.len(), 1);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_boxed_str() {
+    use alloc::boxed::Box;
+
+    let mut buffer = [0u8; 64];
+
+    let (size, _) = serialize::<Ref<str>, _>("hello", &mut buffer).unwrap();
+    let boxed = deserialize::<Ref<str>, Box<str>>(&buffer[..size]).unwrap();
+    assert_eq!(&*boxed, "hello");
+
+    let (size, _) = serialize::<(u8, Ref<str>), _>((1u8, "world"), &mut buffer).unwrap();
+    let mut value = (0u8, Box::<str>::from(""));
+    deserialize_in_place::<(u8, Ref<str>), _>(&mut value, &buffer[..size]).unwrap();
+    assert_eq!(value, (1, Box::from("world")));
+}
+
+#[test]
+fn test_prefixed_str() {
+    use crate::Str;
+
+    let mut buffer = [0u8; 512];
+
+    let (size, _) = serialize::<Str<1>, _>("hello", &mut buffer).unwrap();
+    assert_eq!(size, 6);
+    assert_eq!(
+        deserialize::<Str<1>, &str>(&buffer[..size]).unwrap(),
+        "hello"
+    );
+
+    // Truncated input is shorter than encoded length.
+    assert!(matches!(
+        deserialize::<Str<1>, &str>(&buffer[1..size]),
+        Err(DeserializeError::WrongLength)
+    ));
+
+    let long = core::str::from_utf8(&[b'a'; 300]).unwrap();
+    let (size, _) = serialize::<Str<2>, _>(long, &mut buffer).unwrap();
+    assert_eq!(size, 302);
+    assert_eq!(deserialize::<Str<2>, &str>(&buffer[..size]).unwrap(), long);
+
+    let (size, _) =
+        serialize::<[(As<Str<1>>, u8)], _>([("a", 1u8), ("bc", 2)], &mut buffer).unwrap();
+    let pairs = deserialize::<[(As<Str<1>>, u8)], [(&str, u8); 2]>(&buffer[..size]).unwrap();
+    assert_eq!(pairs, [("a", 1), ("bc", 2)]);
+}
+
+#[test]
+fn test_prefixed_str_too_long_hint() {
+    use crate::serialize::SerializeRef;
+
+    let long = core::str::from_utf8(&[b'a'; 256]).unwrap();
+    assert!(<str as SerializeRef<crate::Str<1>>>::size_hint(long).is_none());
+    assert_eq!(
+        <str as SerializeRef<crate::Str<2>>>::size_hint(long),
+        Some(Sizes::with_stack(258))
+    );
+}
+
+#[test]
+#[should_panic(expected = "String is too long")]
+fn test_prefixed_str_too_long() {
+    let long = core::str::from_utf8(&[b'a'; 256]).unwrap();
+    let mut buffer = [0u8; 512];
+    let _ = serialize::<crate::Str<1>, _>(long, &mut buffer);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_any_registry() {
+    use alloc::string::String;
+
+    use crate::AnyRegistry;
+
+    let mut registry = AnyRegistry::new();
+    registry.register::<u32>(1).register::<String>(2);
+    assert!(registry.contains(1));
+    assert!(!registry.contains(3));
+
+    let mut buffer = [0u8; 64];
+
+    let (size, _) = serialize::<(u32, u32), _>((1u32, 42u32), &mut buffer).unwrap();
+    let value = registry.deserialize_any(&buffer[..size]).unwrap();
+    assert_eq!(value.downcast_ref::<u32>(), Some(&42));
+
+    let (size, _) = serialize::<(u32, String), _>((2u32, "hello"), &mut buffer).unwrap();
+    let value = registry.deserialize_any(&buffer[..size]).unwrap();
+    assert_eq!(value.downcast_ref::<String>().unwrap(), "hello");
+
+    let (size, _) = serialize::<(u32, u32), _>((3u32, 42u32), &mut buffer).unwrap();
+    assert!(matches!(
+        registry.deserialize_any(&buffer[..size]),
+        Err(DeserializeError::WrongVariant { got: 3, count: 2 })
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_lazy_eq() {
+    use alloc::{vec, vec::Vec};
+
+    use crate::Lazy;
+
+    let mut buffer = [0u8; 64];
+    let (size, _) = serialize::<[u32], _>([1u32, 2, 3], &mut buffer).unwrap();
+    let lazy = deserialize::<[u32], Lazy<[u32]>>(&buffer[..size]).unwrap();
+
+    let equal: Vec<u32> = vec![1, 2, 3];
+    let unequal: Vec<u32> = vec![1, 2, 4];
+    let shorter: Vec<u32> = vec![1, 2];
+    let longer: Vec<u32> = vec![1, 2, 3, 4];
+
+    assert!(lazy.eq_slice(&equal).unwrap());
+    assert!(!lazy.eq_slice(&unequal).unwrap());
+    assert!(!lazy.eq_slice(&shorter).unwrap());
+    assert!(!lazy.eq_slice(&longer).unwrap());
+
+    assert!(lazy.eq_value(&equal).unwrap());
+    assert!(!lazy.eq_value(&unequal).unwrap());
+
+    let (size, _) = serialize::<[As<str>], _>(["a", "b"], &mut buffer).unwrap();
+    let lazy = deserialize::<[As<str>], Lazy<[As<str>]>>(&buffer[..size]).unwrap();
+    assert!(lazy.eq_slice(&["a", "b"]).unwrap());
+    assert!(!lazy.eq_slice(&["a", "c"]).unwrap());
+    assert!(!lazy.eq_slice(&["a"]).unwrap());
+}
+
+#[test]
+fn test_buffer_reset() {
+    use crate::{buffer::MaybeFixedBuffer, serialize::serialize_into};
+
+    let mut bytes = [0u8; 16];
+    let mut exhausted = false;
+    let mut buffer = MaybeFixedBuffer::new(&mut bytes, &mut exhausted);
+
+    // Too large for the buffer.
+    serialize_into::<[u64], _, _>([1u64, 2, 3], buffer.reborrow()).unwrap();
+    assert!(buffer.is_exhausted());
+
+    buffer.reset();
+    assert!(!buffer.is_exhausted());
+
+    let (size, _) = serialize_into::<(u32, u16), _, _>((7u32, 8u16), buffer.reborrow()).unwrap();
+    assert!(!buffer.is_exhausted());
+    assert_eq!(
+        deserialize::<(u32, u16), (u32, u16)>(&bytes[..size]).unwrap(),
+        (7, 8)
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_vec_buffer_reset() {
+    use alloc::vec::Vec;
+
+    use crate::{buffer::VecBuffer, serialize::serialize_into};
+
+    let mut vec = Vec::new();
+    let mut buffer = VecBuffer::new(&mut vec);
+
+    serialize_into::<[u64], _, _>([1u64, 2, 3], buffer.reborrow()).unwrap();
+    buffer.reset();
+    let (size, _) = serialize_into::<(u32, u16), _, _>((7u32, 8u16), buffer.reborrow()).unwrap();
+
+    assert_eq!(
+        deserialize::<(u32, u16), (u32, u16)>(&vec[..size]).unwrap(),
+        (7, 8)
+    );
+}
+
+#[test]
+fn test_duration() {
+    use core::time::Duration;
+
+    let mut buffer = [0u8; 12];
+    let duration = Duration::new(3, 500);
+    let (size, _) = serialize::<Duration, _>(duration, &mut buffer).unwrap();
+    assert_eq!(size, 12);
+    assert_eq!(
+        deserialize::<Duration, Duration>(&buffer[..size]).unwrap(),
+        duration
+    );
+
+    // Sub-second nanoseconds out of range.
+    buffer[8..].copy_from_slice(&1_000_000_000u32.to_le_bytes());
+    assert!(matches!(
+        deserialize::<Duration, Duration>(&buffer),
+        Err(DeserializeError::Incompatible)
+    ));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_instant_since() {
+    use std::time::{Duration, Instant};
+
+    use crate::InstantSince;
+
+    let reference = Instant::now();
+    let instant = reference + Duration::from_millis(2500);
+
+    let mut buffer = [0u8; 64];
+    let (size, _) =
+        serialize::<(u8, Duration), _>((1u8, InstantSince::new(reference, instant)), &mut buffer)
+            .unwrap();
+
+    let (_, duration) = deserialize::<(u8, Duration), (u8, Duration)>(&buffer[..size]).unwrap();
+    assert_eq!(duration, Duration::from_millis(2500));
+
+    let restored = InstantSince::from_duration(reference, duration).unwrap();
+    assert_eq!(restored.instant, instant);
+
+    // Earlier instants saturate to zero.
+    let (size, _) =
+        serialize::<Duration, _>(InstantSince::new(instant, reference), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<Duration, Duration>(&buffer[..size]).unwrap(),
+        Duration::ZERO
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_ctx_interner() {
+    use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+    use crate::{
+        deserialize_with_ctx, serialize::write_slice, serialize_with_ctx, DeserializeCtx,
+        SerializeCtx,
+    };
+
+    // Assigns ids to strings, each unique string is stored once.
+    #[derive(Default)]
+    struct Interner {
+        ids: BTreeMap<String, u32>,
+        strings: Vec<String>,
+    }
+
+    impl Interner {
+        fn intern(&mut self, s: &str) -> u32 {
+            if let Some(&id) = self.ids.get(s) {
+                return id;
+            }
+            let id = self.strings.len() as u32;
+            self.ids.insert(String::from(s), id);
+            self.strings.push(String::from(s));
+            id
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Sentence(Vec<String>);
+
+    impl SerializeCtx<[u32], Interner> for &[&str] {
+        fn serialize_ctx<B>(
+            self,
+            ctx: &mut Interner,
+            sizes: &mut Sizes,
+            buffer: B,
+        ) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            write_slice::<u32, _, _>(self.iter().map(|s| ctx.intern(s)), sizes, buffer)
+        }
+    }
+
+    impl<'de> DeserializeCtx<'de, [u32], Interner> for Sentence {
+        fn deserialize_ctx(
+            ctx: &mut Interner,
+            deserializer: Deserializer<'de>,
+        ) -> Result<Self, DeserializeError> {
+            deserializer
+                .into_unsized_iter::<u32, u32>()
+                .map(|id| match ctx.strings.get(id? as usize) {
+                    Some(s) => Ok(s.clone()),
+                    None => Err(DeserializeError::Incompatible),
+                })
+                .collect::<Result<_, _>>()
+                .map(Sentence)
+        }
+    }
+
+    let words = ["to", "be", "or", "not", "to", "be"];
+
+    let mut interner = Interner::default();
+    let mut buffer = [0u8; 64];
+    let (size, _) =
+        serialize_with_ctx::<[u32], _, _>(&words[..], &mut buffer, &mut interner).unwrap();
+
+    assert_eq!(interner.strings, ["to", "be", "or", "not"]);
+    assert_eq!(
+        deserialize::<[u32], [u32; 6]>(&buffer[..size]).unwrap(),
+        [0, 1, 2, 3, 0, 1]
+    );
+
+    let sentence =
+        deserialize_with_ctx::<[u32], Sentence, _>(&buffer[..size], &mut interner).unwrap();
+    assert_eq!(sentence.0, words);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_batch_serializer() {
+    use crate::{serialize::write_field, BatchHeap, BatchSerializer, Ref, SerializeCtx};
+
+    struct Message<'a> {
+        name: &'a str,
+        id: u32,
+    }
+
+    impl SerializeCtx<(Ref<str>, u32), BatchHeap> for Message<'_> {
+        fn serialize_ctx<B>(
+            self,
+            ctx: &mut BatchHeap,
+            sizes: &mut Sizes,
+            mut buffer: B,
+        ) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            self.name.serialize_ctx(ctx, sizes, buffer.reborrow())?;
+            write_field::<u32, _, _>(self.id, sizes, buffer, true)
+        }
+    }
+
+    let shared = "shared payload";
+
+    let mut batch = BatchSerializer::new();
+    let a = batch.push_ctx::<(Ref<str>, u32), _>(Message {
+        name: shared,
+        id: 1,
+    });
+    let plain = batch.push::<u64, _>(42u64);
+    let b = batch.push_ctx::<(Ref<str>, u32), _>(Message {
+        name: shared,
+        id: 2,
+    });
+    assert_eq!(batch.heap().len(), 1);
+    assert_eq!(
+        batch
+            .as_bytes()
+            .windows(shared.len())
+            .filter(|w| *w == shared.as_bytes())
+            .count(),
+        1
+    );
+
+    assert_eq!(
+        batch
+            .deserialize::<(Ref<str>, u32), (&str, u32)>(a)
+            .unwrap(),
+        (shared, 1)
+    );
+    assert_eq!(batch.deserialize::<u64, u64>(plain).unwrap(), 42);
+    assert_eq!(
+        batch
+            .deserialize::<(Ref<str>, u32), (&str, u32)>(b)
+            .unwrap(),
+        (shared, 2)
+    );
+
+    let c = batch.push_ctx::<Ref<Bytes>, _>(&[1u8, 2, 3][..]);
+    let d = batch.push_ctx::<Ref<Bytes>, _>(&[1u8, 2, 3][..]);
+    assert_eq!(batch.heap().len(), 2);
+    assert_eq!(
+        batch.deserialize::<Ref<Bytes>, &[u8]>(c).unwrap(),
+        [1, 2, 3]
+    );
+    assert_eq!(
+        batch.deserialize::<Ref<Bytes>, &[u8]>(d).unwrap(),
+        [1, 2, 3]
+    );
+    assert_eq!(d.0 - c.0, d.1);
+}
+
+#[cfg(all(feature = "alloc", feature = "derive", feature = "arbitrary"))]
+#[test]
+fn test_arbitrary() {
+    use alkahest_proc::alkahest;
+    use alloc::string::String;
+    use arbitrary::{Arbitrary, Unstructured};
+    use rand::{rngs::SmallRng, RngCore, SeedableRng};
+
+    use crate::serialize_to_vec;
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize, arbitrary)]
+    struct Point<T> {
+        x: T,
+        y: T,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize, arbitrary)]
+    enum Shape {
+        Empty,
+        Circle(Point<i32>, u32),
+        Polygon {
+            points: Vec<Point<i32>>,
+            name: String,
+        },
+        // Never generated.
+        #[alkahest(unknown)]
+        Unknown(crate::UnknownTag<Self>),
+    }
+
+    let mut rng = SmallRng::seed_from_u64(42);
+    let mut data = vec![0u8; 1024];
+    let mut buffer = Vec::new();
+
+    for _ in 0..256 {
+        rng.fill_bytes(&mut data);
+        let mut u = Unstructured::new(&data);
+        let shape = Shape::arbitrary(&mut u).unwrap();
+
+        buffer.clear();
+        let (size, _) = serialize_to_vec::<Shape, _>(shape.clone(), &mut buffer);
+        assert_eq!(deserialize::<Shape, Shape>(&buffer[..size]).unwrap(), shape);
+    }
+}
+
+#[test]
+fn test_versioned() {
+    use crate::versioned::Versioned;
+
+    type V1 = Versioned<1, (u32, str)>;
+
+    let mut buffer = [0u8; 64];
+    let (size, _) = serialize::<V1, _>((7u32, "seven"), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<V1, (u32, &str)>(&buffer[..size]).unwrap(),
+        (7, "seven")
+    );
+
+    let mut value = (0u32, "");
+    deserialize_in_place::<V1, (u32, &str)>(&mut value, &buffer[..size]).unwrap();
+    assert_eq!(value, (7, "seven"));
+
+    assert!(matches!(
+        deserialize::<Versioned<2, (u32, str)>, (u32, &str)>(&buffer[..size]),
+        Err(DeserializeError::Incompatible)
+    ));
+
+    // Versions are checked for nested fields too.
+    type Outer = (u8, Versioned<3, u16>);
+    let (size, _) = serialize::<Outer, _>((1u8, 2u16), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<Outer, (u8, u16)>(&buffer[..size]).unwrap(),
+        (1, 2)
+    );
+    assert!(matches!(
+        deserialize::<(u8, Versioned<4, u16>), (u8, u16)>(&buffer[..size]),
+        Err(DeserializeError::Incompatible)
+    ));
+
+    const _: () = assert!(matches!(
+        <Versioned<0, u16> as Formula>::MAX_STACK_SIZE,
+        Some(6)
+    ));
+}
+
+#[cfg(feature = "indexmap")]
+#[test]
+fn test_indexmap_order() {
+    use indexmap::{IndexMap, IndexSet};
+
+    type Map = IndexMap<u32, u64, ahash::RandomState>;
+    type Set = IndexSet<u16, ahash::RandomState>;
+
+    let keys = [42u32, 7, 100, 1, 13, 99, 0];
+
+    let mut map = Map::default();
+    for &key in &keys {
+        map.insert(key, u64::from(key) * 10);
+    }
+
+    let mut buffer = [0u8; 256];
+    let (size, _) = serialize::<[(u32, u64)], _>(&map, &mut buffer).unwrap();
+    let read = deserialize::<[(u32, u64)], Map>(&buffer[..size]).unwrap();
+    assert!(read.iter().eq(map.iter()));
+
+    let mut place = Map::default();
+    place.insert(5, 5);
+    deserialize_in_place::<[(u32, u64)], Map>(&mut place, &buffer[..size]).unwrap();
+    assert!(place.iter().eq(map.iter()));
+
+    let set: Set = keys.iter().map(|&key| key as u16).rev().collect();
+    let (size, _) = serialize::<[u16], _>(&set, &mut buffer).unwrap();
+    let read = deserialize::<[u16], Set>(&buffer[..size]).unwrap();
+    assert!(read.iter().eq(set.iter()));
+
+    // Duplicate keys keep position of the first occurrence and the last value.
+    let (size, _) =
+        serialize::<[(u32, u64)], _>([(3u32, 1u64), (1, 2), (3, 3)], &mut buffer).unwrap();
+    let read = deserialize::<[(u32, u64)], Map>(&buffer[..size]).unwrap();
+    assert!(read.into_iter().eq([(3, 3), (1, 2)]));
+}
+
+// Snapshots pin compiler messages, which change on other channels.
+#[cfg(feature = "derive")]
+#[rustversion::attr(not(stable), ignore)]
+#[test]
+fn test_compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("src/tests/compile_fail/*.rs");
+}
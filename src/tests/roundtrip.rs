@@ -0,0 +1,136 @@
+//! Property tests checking that values survive serialization round-trip.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::Debug;
+
+use proptest::{collection::vec, option, prelude::*};
+
+use crate::{
+    deserialize::{deserialize_with_size, Deserialize},
+    formula::Formula,
+    serialize::{serialize_to_vec, Serialize},
+    size::FixedUsizeType,
+};
+
+/// Max length of generated collections and strings.
+/// Keeps test run time reasonable.
+/// With `fixed8` feature serialized values must fit into 255 bytes,
+/// so that sizes and addresses can be written.
+const MAX_LEN: usize = if FixedUsizeType::MAX as u64 > u8::MAX as u64 {
+    32
+} else {
+    4
+};
+
+/// Generates strings of up to `max_len` characters.
+fn string(max_len: usize) -> impl Strategy<Value = String> {
+    vec(any::<char>(), 0..=max_len).prop_map(|chars| chars.into_iter().collect())
+}
+
+fn config() -> ProptestConfig {
+    ProptestConfig::with_cases(64)
+}
+
+/// Serializes `value` with formula `F` and deserializes it back.
+/// Checks that result is equal to the original value.
+fn roundtrip<F, T>(value: T) -> Result<(), TestCaseError>
+where
+    F: Formula + ?Sized,
+    T: Serialize<F> + for<'de> Deserialize<'de, F> + Clone + Debug + PartialEq,
+{
+    let mut buffer = Vec::new();
+    let (size, root) = serialize_to_vec::<F, T>(value.clone(), &mut buffer);
+    let de = deserialize_with_size::<F, T>(&buffer[..size], root)
+        .map_err(|err| TestCaseError::fail(alloc::format!("{err:?}")))?;
+    prop_assert_eq!(de, value);
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(config())]
+
+    #[test]
+    fn primitives(a: u8, b: u16, c: u32, d: u64, e: i8, f: i32, g: i64, h: bool) {
+        roundtrip::<u8, _>(a)?;
+        roundtrip::<u16, _>(b)?;
+        roundtrip::<u32, _>(c)?;
+        roundtrip::<u64, _>(d)?;
+        roundtrip::<i8, _>(e)?;
+        roundtrip::<i32, _>(f)?;
+        roundtrip::<i64, _>(g)?;
+        roundtrip::<bool, _>(h)?;
+    }
+
+    #[test]
+    fn floats(a in proptest::num::f32::NORMAL, b in proptest::num::f64::NORMAL) {
+        roundtrip::<f32, _>(a)?;
+        roundtrip::<f64, _>(b)?;
+    }
+
+    #[test]
+    fn tuples(a: (u8, u32), b: (u16, (i64, bool), u8)) {
+        roundtrip::<(u8, u32), _>(a)?;
+        roundtrip::<(u16, (i64, bool), u8), _>(b)?;
+    }
+
+    #[test]
+    fn vecs(a in vec(any::<u32>(), 0..MAX_LEN), b in vec(vec(any::<u8>(), 0..MAX_LEN), 0..MAX_LEN)) {
+        roundtrip::<Vec<u32>, _>(a)?;
+        roundtrip::<Vec<Vec<u8>>, _>(b)?;
+    }
+
+    #[test]
+    fn strings(a in string(MAX_LEN), b in vec(string(MAX_LEN / 4), 0..MAX_LEN)) {
+        roundtrip::<String, String>(a)?;
+        roundtrip::<Vec<String>, Vec<String>>(b)?;
+    }
+
+    #[test]
+    fn options(a: Option<u32>, b in option::of(string(MAX_LEN))) {
+        roundtrip::<Option<u32>, _>(a)?;
+        roundtrip::<Option<String>, Option<String>>(b)?;
+    }
+}
+
+#[cfg(feature = "derive")]
+mod derived {
+    use alloc::{string::String, vec::Vec};
+
+    use proptest::{collection::vec, option, prelude::*};
+
+    use super::{config, roundtrip, string, MAX_LEN};
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[alkahest_proc::alkahest(Formula, Serialize, Deserialize)]
+    struct Record {
+        id: u32,
+        name: String,
+        scores: Vec<u16>,
+        flag: Option<bool>,
+    }
+
+    fn record() -> impl Strategy<Value = Record> {
+        (
+            any::<u32>(),
+            string(MAX_LEN / 2),
+            vec(any::<u16>(), 0..MAX_LEN),
+            option::of(any::<bool>()),
+        )
+            .prop_map(|(id, name, scores, flag)| Record {
+                id,
+                name,
+                scores,
+                flag,
+            })
+    }
+
+    proptest! {
+        #![proptest_config(config())]
+
+        #[test]
+        fn derived_struct(a in record(), b in vec(record(), 0..=MAX_LEN / 4)) {
+            roundtrip::<Record, _>(a)?;
+            roundtrip::<Vec<Record>, _>(b)?;
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Serialization of time durations.
+//!
+//! `Duration` is serialized as seconds in `u64` followed by
+//! sub-second nanoseconds in `u32`, both in little-endian.
+//!
+//! `Instant` is opaque and cannot be serialized absolutely.
+//! [`InstantSince`] pairs it with a reference instant
+//! and serializes the duration between them.
+
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+};
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+impl Formula for Duration {
+    const MAX_STACK_SIZE: Option<usize> = Some(12);
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = true;
+}
+
+impl BareFormula for Duration {}
+
+#[inline(always)]
+fn duration_to_bytes(duration: Duration) -> [u8; 12] {
+    let mut bytes = [0; 12];
+    bytes[..8].copy_from_slice(&duration.as_secs().to_le_bytes());
+    bytes[8..].copy_from_slice(&duration.subsec_nanos().to_le_bytes());
+    bytes
+}
+
+#[inline(always)]
+fn read_duration(mut de: Deserializer) -> Result<Duration, DeserializeError> {
+    let bytes = de.read_byte_array::<12>()?;
+    let mut secs = [0; 8];
+    secs.copy_from_slice(&bytes[..8]);
+    let mut nanos = [0; 4];
+    nanos.copy_from_slice(&bytes[8..]);
+
+    let nanos = u32::from_le_bytes(nanos);
+    if nanos >= NANOS_PER_SEC {
+        return Err(DeserializeError::Incompatible);
+    }
+    Ok(Duration::new(u64::from_le_bytes(secs), nanos))
+}
+
+impl Serialize<Duration> for Duration {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&duration_to_bytes(self), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(12))
+    }
+}
+
+impl SerializeRef<Duration> for Duration {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&duration_to_bytes(*self), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(12))
+    }
+}
+
+impl Deserialize<'_, Duration> for Duration {
+    #[inline(always)]
+    fn deserialize(de: Deserializer) -> Result<Self, DeserializeError> {
+        read_duration(de)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+        *self = read_duration(de)?;
+        Ok(())
+    }
+}
+
+/// `Instant` relative to a reference instant.
+///
+/// Serializes with `Duration` formula as time elapsed since the reference,
+/// saturating to zero for instants earlier than the reference.
+///
+/// The reference is not serialized, so the reader must provide
+/// an equivalent one to restore the instant with [`InstantSince::from_duration`].
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// # use std::time::{Duration, Instant};
+/// let reference = Instant::now();
+/// let instant = reference + Duration::from_millis(1500);
+///
+/// let mut buffer = [0u8; 12];
+/// let (size, _) = serialize::<Duration, _>(InstantSince::new(reference, instant), &mut buffer).unwrap();
+///
+/// let duration = deserialize::<Duration, Duration>(&buffer[..size]).unwrap();
+/// let restored = InstantSince::from_duration(reference, duration).unwrap();
+/// assert_eq!(restored.instant, instant);
+/// ```
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstantSince {
+    /// Reference instant.
+    pub reference: Instant,
+
+    /// Serialized instant.
+    pub instant: Instant,
+}
+
+#[cfg(feature = "std")]
+impl InstantSince {
+    /// Pairs instant with reference instant.
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(reference: Instant, instant: Instant) -> Self {
+        InstantSince { reference, instant }
+    }
+
+    /// Restores instant from duration since reference.
+    /// Returns `None` if the instant is not representable.
+    #[must_use]
+    #[inline(always)]
+    pub fn from_duration(reference: Instant, duration: Duration) -> Option<Self> {
+        let instant = reference.checked_add(duration)?;
+        Some(InstantSince { reference, instant })
+    }
+
+    /// Returns time elapsed from reference to instant,
+    /// or zero if instant is earlier than reference.
+    #[must_use]
+    #[inline(always)]
+    pub fn duration(&self) -> Duration {
+        self.instant.saturating_duration_since(self.reference)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Serialize<Duration> for InstantSince {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&duration_to_bytes(self.duration()), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(12))
+    }
+}
+
+#[cfg(feature = "std")]
+impl SerializeRef<Duration> for InstantSince {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&duration_to_bytes(self.duration()), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(12))
+    }
+}
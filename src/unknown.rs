@@ -0,0 +1,101 @@
+use core::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+/// Raw tag of enum variant unknown to formula `F`.
+///
+/// Held by enum variant marked `#[alkahest(unknown)]`.
+/// Only deserialization with formula `F` constructs it,
+/// so the tag never belongs to a variant known to `F`
+/// and always fits its tag type.
+/// Serializing the variant writes the tag back.
+pub struct UnknownTag<F: ?Sized> {
+    tag: u32,
+    marker: PhantomData<fn(&F)>,
+}
+
+impl<F> UnknownTag<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    pub(crate) const fn new(tag: u32) -> Self {
+        UnknownTag {
+            tag,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns raw tag value.
+    #[must_use]
+    #[inline(always)]
+    pub const fn tag(&self) -> u32 {
+        self.tag
+    }
+}
+
+impl<F> Clone for UnknownTag<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F> Copy for UnknownTag<F> where F: ?Sized {}
+
+impl<F> fmt::Debug for UnknownTag<F>
+where
+    F: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("UnknownTag").field(&self.tag).finish()
+    }
+}
+
+impl<F> PartialEq for UnknownTag<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl<F> Eq for UnknownTag<F> where F: ?Sized {}
+
+impl<F> PartialOrd for UnknownTag<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<F> Ord for UnknownTag<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.tag.cmp(&other.tag)
+    }
+}
+
+impl<F> Hash for UnknownTag<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tag.hash(state);
+    }
+}
@@ -1,4 +1,4 @@
-use alloc::vec::Vec;
+use alloc::{borrow::Cow, vec::Vec};
 
 use crate::{
     buffer::Buffer,
@@ -100,6 +100,32 @@ where
     }
 }
 
+/// Serializes elements by reference,
+/// so borrowed slice is never cloned.
+impl<'ser, F, T> Serialize<[F]> for &'ser Cow<'_, [T]>
+where
+    F: Formula,
+    T: Clone,
+    &'ser T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_slice(self.iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        owned_iter_fast_sizes::<F, _, _>(self.iter())
+    }
+}
+
+/// Deserializes elements one by one.
+///
+/// Byte blobs are better serialized with [`Bytes`] formula,
+/// which deserializes into `Vec<u8>` with a single copy.
 impl<'de, F, T> Deserialize<'de, [F]> for Vec<T>
 where
     F: Formula,
@@ -114,10 +140,25 @@ where
         Ok(vec)
     }
 
-    #[inline(always)]
+    /// Deserializes existing elements in-place, reusing their allocations.
+    /// Extra elements are truncated and missing are appended.
+    /// Vector's own allocation is reused when capacity suffices.
+    ///
+    /// If deserialization of an element fails, the error is returned
+    /// immediately, leaving the vector partially updated.
+    #[inline]
     fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
-        self.clear();
-        let iter = de.into_unsized_iter();
+        let mut iter = de.into_unsized_iter();
+        let mut len = 0;
+        while let Some(elem) = self.get_mut(len) {
+            match iter.next_in_place(elem) {
+                None => break,
+                Some(result) => result?,
+            }
+            len += 1;
+        }
+        self.truncate(len);
+
         let (lower, _) = Iterator::size_hint(&iter);
         self.reserve(lower);
         deserialize_extend_iter(self, iter)
@@ -174,6 +215,36 @@ impl Serialize<Bytes> for &Vec<u8> {
     }
 }
 
+impl Serialize<Bytes> for Cow<'_, [u8]> {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len()))
+    }
+}
+
+impl Serialize<Bytes> for &Cow<'_, [u8]> {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len()))
+    }
+}
+
 impl<'de> Deserialize<'de, Bytes> for Vec<u8> {
     #[inline(always)]
     fn deserialize(de: Deserializer) -> Result<Self, DeserializeError> {
@@ -189,3 +260,38 @@ impl<'de> Deserialize<'de, Bytes> for Vec<u8> {
         Ok(())
     }
 }
+
+/// Always deserializes into [`Cow::Owned`].
+///
+/// Elements cannot be borrowed from the input in general.
+/// Use [`Bytes`] formula to borrow byte slices.
+impl<'de, F, T> Deserialize<'de, [F]> for Cow<'de, [T]>
+where
+    F: Formula,
+    T: Clone + Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let vec = <Vec<T> as Deserialize<'de, [F]>>::deserialize(de)?;
+        Ok(Cow::Owned(vec))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        <Vec<T> as Deserialize<'de, [F]>>::deserialize_in_place(self.to_mut(), de)
+    }
+}
+
+/// Deserializes into [`Cow::Borrowed`] without copying.
+impl<'de, 'fe: 'de> Deserialize<'fe, Bytes> for Cow<'de, [u8]> {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        Ok(Cow::Borrowed(de.read_all_bytes()))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = Cow::Borrowed(de.read_all_bytes());
+        Ok(())
+    }
+}
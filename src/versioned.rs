@@ -0,0 +1,89 @@
+use core::{marker::PhantomData, mem::size_of};
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{sum_size, Formula},
+    serialize::{field_size_hint, write_field, Serialize, Sizes},
+};
+
+/// Formula for values tagged with format version.
+///
+/// Writes version `V` as `u32` followed by the value serialized with formula `F`.
+/// Deserialization reads the version first and fails with
+/// [`DeserializeError::Incompatible`] if it is not `V`,
+/// so that data written with another version of the formula
+/// is rejected instead of being misinterpreted.
+///
+/// Serializable from and deserializable into any type
+/// that is serializable and deserializable with formula `F`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 64];
+/// let (size, _) = serialize::<Versioned<2, u64>, _>(42u64, &mut buffer).unwrap();
+///
+/// let value = deserialize::<Versioned<2, u64>, u64>(&buffer[..size]).unwrap();
+/// assert_eq!(value, 42);
+///
+/// let err = deserialize::<Versioned<3, u64>, u64>(&buffer[..size]).unwrap_err();
+/// assert!(matches!(err, DeserializeError::Incompatible));
+/// ```
+pub struct Versioned<const V: u32, F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<const V: u32, F> Formula for Versioned<V, F>
+where
+    F: Formula + ?Sized,
+{
+    const MAX_STACK_SIZE: Option<usize> = sum_size(Some(size_of::<u32>()), F::MAX_STACK_SIZE);
+    const EXACT_SIZE: bool = F::EXACT_SIZE;
+    const HEAPLESS: bool = F::HEAPLESS;
+}
+
+impl<const V: u32, F, T> Serialize<Versioned<V, F>> for T
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_field::<u32, u32, _>(V, sizes, buffer.reborrow(), false)?;
+        write_field::<F, T, _>(self, sizes, buffer, true)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        let mut sizes = field_size_hint::<F>(self, true)?;
+        sizes.add_stack(size_of::<u32>());
+        Some(sizes)
+    }
+}
+
+impl<'de, const V: u32, F, T> Deserialize<'de, Versioned<V, F>> for T
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    #[inline]
+    fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        if de.read_value::<u32, u32>(false)? != V {
+            return Err(DeserializeError::Incompatible);
+        }
+        de.read_value::<F, T>(true)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, mut de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        if de.read_value::<u32, u32>(false)? != V {
+            return Err(DeserializeError::Incompatible);
+        }
+        de.read_in_place::<F, T>(self, true)
+    }
+}
@@ -62,7 +62,40 @@ impl Formula for Vlq {
     const HEAPLESS: bool = true;
 }
 
-trait VlqType: Copy {
+/// Formula for Variable-Length Quantity encoding
+/// that saturates on deserialization.
+///
+/// Binary representation is the same as [`Vlq`].
+/// Unlike [`Vlq`], if the value does not fit into the target type,
+/// the maximum value of the target type is produced instead of an error.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let mut buffer = [0u8; 1024];
+///
+/// let (size, root) = serialize::<VlqSaturating, u32>(70000, &mut buffer).unwrap();
+/// let value = deserialize_with_size::<VlqSaturating, u16>(&buffer[..size], root).unwrap();
+/// assert_eq!(u16::MAX, value);
+///
+/// // Same data with `Vlq` formula.
+/// let err = deserialize_with_size::<Vlq, u16>(&buffer[..size], root).unwrap_err();
+/// assert!(matches!(err, DeserializeError::IntegerOverflow));
+/// ```
+pub struct VlqSaturating;
+
+impl Formula for VlqSaturating {
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+pub(crate) trait VlqType: Copy {
+    /// Maximum value of the type.
+    const MAX: Self;
+
     fn less_eq(&self, byte: u8) -> bool;
 
     /// Shifts the value right by 8 bits, and assigns the result to `self`.
@@ -77,6 +110,8 @@ trait VlqType: Copy {
 }
 
 impl VlqType for u8 {
+    const MAX: u8 = u8::MAX;
+
     #[inline(always)]
     fn less_eq(&self, byte: u8) -> bool {
         *self <= byte
@@ -106,6 +141,8 @@ macro_rules! impl_vlq_int {
     ($($a:ident)*) => {
         $(
             impl VlqType for $a {
+                const MAX: $a = $a::MAX;
+
                 #[inline(always)]
                 fn less_eq(&self, byte: u8) -> bool {
                     *self <= $a::from(byte)
@@ -176,6 +213,43 @@ where
     }
 }
 
+impl<T> Serialize<VlqSaturating> for T
+where
+    T: VlqType,
+{
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(size_hint(*self))
+    }
+
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        serialize(self, sizes, buffer)
+    }
+}
+
+impl<'de, T> Deserialize<'de, VlqSaturating> for T
+where
+    T: VlqType,
+{
+    #[inline(always)]
+    fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        read_vlq_impl(&mut de, true)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(
+        &mut self,
+        mut deserializer: Deserializer<'de>,
+    ) -> Result<(), DeserializeError> {
+        *self = read_vlq_impl(&mut deserializer, true)?;
+        Ok(())
+    }
+}
+
 #[inline]
 fn size_hint<T>(mut value: T) -> Sizes
 where
@@ -235,8 +309,29 @@ where
     }
 }
 
-#[inline]
+#[inline(always)]
 fn deserialize<T>(mut de: Deserializer) -> Result<T, DeserializeError>
+where
+    T: VlqType,
+{
+    read_vlq(&mut de)
+}
+
+/// Reads VLQ encoded value from the deserializer.
+/// Advances the deserializer.
+#[inline(always)]
+pub(crate) fn read_vlq<T>(de: &mut Deserializer) -> Result<T, DeserializeError>
+where
+    T: VlqType,
+{
+    read_vlq_impl(de, false)
+}
+
+/// Reads VLQ encoded value from the deserializer.
+/// If `saturating` is `true` then value that does not fit into `T`
+/// is replaced with `T::MAX`.
+#[inline]
+fn read_vlq_impl<T>(de: &mut Deserializer, saturating: bool) -> Result<T, DeserializeError>
 where
     T: VlqType,
 {
@@ -245,11 +340,10 @@ where
     let (tail, msb) = match header {
         0x00..=0x7F => (header >> 4, header & 0x0F),
         0x80..=0xBF => (header & 0x3F, 0),
-        0xC0..=0xFF => {
-            unimplemented!(
-                "Decoding for values that require more than 63 bytes is not implemented yet."
-            )
-        }
+        // Chained length is reserved for values longer than 63 bytes,
+        // which do not fit any supported type.
+        // Such value cannot be skipped either, so saturation does not apply.
+        0xC0..=0xFF => return Err(DeserializeError::IntegerOverflow),
     };
 
     let mut value = T::from_lsb(msb);
@@ -258,6 +352,9 @@ where
 
     for byte in tail.iter().rev() {
         if !value.shl_byte_set(*byte) {
+            if saturating {
+                return Ok(T::MAX);
+            }
             return Err(DeserializeError::IntegerOverflow);
         }
     }